@@ -0,0 +1,298 @@
+use crate::commodity::Totals;
+use rust_decimal::Decimal;
+
+/// enumerates the ways account balances can be laid out
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Layout {
+    Tree,
+    Flat,
+}
+
+/// classification of an account, taken from the first segment of its
+/// name, e.g. `asset:cash_checking`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AccountType {
+    Asset,
+    Liability,
+    Equity,
+    Income,
+    Expense,
+}
+
+impl AccountType {
+    /// classify `account` by its first segment, ignoring case. singular and
+    /// plural forms are accepted, along with `revenue` for income
+    pub fn of(account: &str) -> Option<AccountType> {
+        match account
+            .split(':')
+            .next()
+            .unwrap_or("")
+            .to_lowercase()
+            .as_str()
+        {
+            "asset" | "assets" => Some(AccountType::Asset),
+            "liability" | "liabilities" => Some(AccountType::Liability),
+            "equity" => Some(AccountType::Equity),
+            "income" | "revenue" | "revenues" => Some(AccountType::Income),
+            "expense" | "expenses" => Some(AccountType::Expense),
+            _ => None,
+        }
+    }
+
+    /// accounts of these types carry credit balances, which are stored as
+    /// negative amounts but reported as positive ones
+    pub fn is_credit(&self) -> bool {
+        matches!(
+            self,
+            AccountType::Liability | AccountType::Equity | AccountType::Income
+        )
+    }
+}
+
+/// account within the hierarchy formed by the `:` separated segments of
+/// account names. `totals` only holds amounts posted to the account
+/// itself, amounts of sub-accounts are kept by its `children`
+#[derive(Debug, PartialEq, Clone)]
+pub struct AccountNode {
+    pub name: String,
+    pub account: String,
+    pub totals: Totals,
+    pub children: Vec<AccountNode>,
+}
+
+/// row of a balance report
+#[derive(Debug, PartialEq, Clone)]
+pub struct AccountRow {
+    pub account: String,
+    pub name: String,
+    pub depth: usize,
+    pub totals: Totals,
+}
+
+/// hierarchy of accounts, where parent accounts are created as needed
+/// and do not have to be declared. accounts are kept in the order they
+/// were first added
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AccountTree {
+    pub roots: Vec<AccountNode>,
+}
+
+impl AccountNode {
+    fn new(name: &str, account: String) -> AccountNode {
+        AccountNode {
+            name: name.to_string(),
+            account,
+            totals: Totals::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// totals of this account and all of its sub-accounts
+    pub fn rolled_totals(&self) -> Totals {
+        let mut totals = self.totals.clone();
+
+        for child in &self.children {
+            for (code, amount) in child.rolled_totals() {
+                *totals.entry(code).or_default() += amount;
+            }
+        }
+
+        totals
+    }
+
+    fn tree_rows(&self, depth: usize, max_depth: Option<usize>, rows: &mut Vec<AccountRow>) {
+        if max_depth.is_some_and(|max| depth > max) {
+            return;
+        }
+
+        rows.push(AccountRow {
+            account: self.account.clone(),
+            name: self.name.clone(),
+            depth,
+            totals: self.rolled_totals(),
+        });
+
+        for child in &self.children {
+            child.tree_rows(depth + 1, max_depth, rows);
+        }
+    }
+
+    fn flat_rows(&self, depth: usize, max_depth: Option<usize>, rows: &mut Vec<AccountRow>) {
+        // accounts at the maximum depth absorb the totals of everything below
+        let collapsed = max_depth == Some(depth);
+        let totals = if collapsed {
+            self.rolled_totals()
+        } else {
+            self.totals.clone()
+        };
+
+        if !totals.is_empty() {
+            rows.push(AccountRow {
+                account: self.account.clone(),
+                name: self.name.clone(),
+                depth,
+                totals,
+            });
+        }
+
+        if !collapsed {
+            for child in &self.children {
+                child.flat_rows(depth + 1, max_depth, rows);
+            }
+        }
+    }
+}
+
+impl AccountTree {
+    pub fn new() -> AccountTree {
+        AccountTree { roots: Vec::new() }
+    }
+
+    /// add `amount` in `currency` to `account`, creating the account and
+    /// any of its parents that do not exist yet
+    pub fn add(&mut self, account: &str, currency: &str, amount: Decimal) {
+        let segments: Vec<&str> = account.split(':').collect();
+        let mut nodes = &mut self.roots;
+
+        for (i, segment) in segments.iter().enumerate() {
+            let index = match nodes.iter().position(|n| n.name == *segment) {
+                Some(index) => index,
+                None => {
+                    nodes.push(AccountNode::new(segment, segments[..=i].join(":")));
+                    nodes.len() - 1
+                }
+            };
+
+            if i == segments.len() - 1 {
+                *nodes[index].totals.entry(currency.to_string()).or_default() += amount;
+                return;
+            }
+
+            nodes = &mut nodes[index].children;
+        }
+    }
+
+    /// every account up to `max_depth` levels deep, each with the totals
+    /// of its sub-accounts rolled up into it. top level accounts have a
+    /// depth of 1
+    pub fn tree_rows(&self, max_depth: Option<usize>) -> Vec<AccountRow> {
+        let mut rows = Vec::new();
+        for root in &self.roots {
+            root.tree_rows(1, max_depth, &mut rows);
+        }
+        rows
+    }
+
+    /// accounts that amounts were added to, without their parents. accounts
+    /// below `max_depth` are collapsed into their parent at that depth
+    pub fn flat_rows(&self, max_depth: Option<usize>) -> Vec<AccountRow> {
+        let mut rows = Vec::new();
+        for root in &self.roots {
+            root.flat_rows(1, max_depth, &mut rows);
+        }
+        rows
+    }
+
+    /// top level accounts of the given type, along with their sub-accounts
+    pub fn section(&self, account_type: AccountType) -> AccountTree {
+        AccountTree {
+            roots: self
+                .roots
+                .iter()
+                .filter(|r| AccountType::of(&r.account) == Some(account_type))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// totals of all accounts of the tree
+    pub fn totals(&self) -> Totals {
+        let mut totals = Totals::new();
+        for root in &self.roots {
+            for (code, amount) in root.rolled_totals() {
+                *totals.entry(code).or_default() += amount;
+            }
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+fn get_tree() -> AccountTree {
+    let mut tree = AccountTree::new();
+    tree.add("asset:cash", "USD", dec!(100));
+    tree.add("expense:auto:fuel", "USD", dec!(40));
+    tree.add("expense:auto:repair", "USD", dec!(60));
+    tree.add("expense:food", "USD", dec!(25));
+    tree.add("expense:food", "EUR", dec!(5));
+    tree.add("expense:auto:fuel", "USD", dec!(10));
+    tree
+}
+
+#[test]
+fn tree_rows_roll_up_children() {
+    let rows = get_tree().tree_rows(None);
+    let accounts: Vec<(&str, usize)> = rows.iter().map(|r| (r.account.as_str(), r.depth)).collect();
+
+    assert_eq!(
+        accounts,
+        vec![
+            ("asset", 1),
+            ("asset:cash", 2),
+            ("expense", 1),
+            ("expense:auto", 2),
+            ("expense:auto:fuel", 3),
+            ("expense:auto:repair", 3),
+            ("expense:food", 2),
+        ]
+    );
+    assert_eq!(rows[2].totals["USD"], dec!(135));
+    assert_eq!(rows[2].totals["EUR"], dec!(5));
+    assert_eq!(rows[3].totals["USD"], dec!(110));
+    assert_eq!(rows[4].name, "fuel");
+}
+
+#[test]
+fn tree_rows_respect_depth() {
+    let rows = get_tree().tree_rows(Some(2));
+
+    assert_eq!(rows.len(), 5);
+    assert!(rows.iter().all(|r| r.depth <= 2));
+}
+
+#[test]
+fn flat_rows_collapse_below_depth() {
+    let tree = get_tree();
+    let rows = tree.flat_rows(None);
+    let accounts: Vec<&str> = rows.iter().map(|r| r.account.as_str()).collect();
+
+    assert_eq!(
+        accounts,
+        vec![
+            "asset:cash",
+            "expense:auto:fuel",
+            "expense:auto:repair",
+            "expense:food"
+        ]
+    );
+
+    let rows = tree.flat_rows(Some(1));
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1].totals["USD"], dec!(135));
+    assert_eq!(tree.totals()["USD"], dec!(235));
+}
+
+#[test]
+fn section_selects_roots_by_type() {
+    let tree = get_tree();
+
+    assert_eq!(AccountType::of("expenses:food"), Some(AccountType::Expense));
+    assert_eq!(AccountType::of("Assets:Checking"), Some(AccountType::Asset));
+    assert_eq!(AccountType::of("misc:food"), None);
+    assert_eq!(tree.section(AccountType::Expense).roots.len(), 1);
+    assert_eq!(tree.section(AccountType::Asset).totals()["USD"], dec!(100));
+    assert!(tree.section(AccountType::Income).roots.is_empty());
+}
@@ -1,12 +1,46 @@
 mod account;
 mod args;
 mod balance;
+mod balancesheet;
 mod budget;
+mod check;
+mod convert;
 mod csv;
+mod import;
+mod incomestatement;
+mod payees;
 mod register;
+mod rules;
 
-use crate::error::Result;
 use args::{Args, Command};
+use rust_ledger::error::Result;
+use rust_ledger::import::categorize::Fallback;
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::period::DateRange;
+use rust_ledger::price::Valuation;
+use rust_ledger::query::{Pivot, Query};
+use rust_ledger::report::Output;
+
+/// postings that reports are made of: those within `range` that match
+/// `query`, grouped by their value of `pivot` rather than by account
+pub struct Selection<'a> {
+    pub range: &'a DateRange,
+    pub query: &'a Query,
+    pub pivot: Option<&'a Pivot>,
+}
+
+impl Selection<'_> {
+    pub fn apply(&self, ledger_file: LedgerFile) -> LedgerFile {
+        let ledger_file = ledger_file
+            .filter_by_range(self.range)
+            .filter_by_query(self.query);
+
+        match self.pivot {
+            Some(pivot) => ledger_file.pivot(pivot),
+            None => ledger_file,
+        }
+    }
+}
 
 pub fn run() -> Result<()> {
     let mut matches = Args::new();
@@ -14,24 +48,131 @@ pub fn run() -> Result<()> {
     let Args {
         ledger_file,
         options_arg,
-        offset_arg,
+        rules_arg,
         group_arg,
-        invert_arg,
+        value_arg,
+        at_arg,
+        prices_arg,
+        depth_arg,
+        layout_arg,
+        begin_arg,
+        end_arg,
+        period_arg,
+        query_arg,
+        pivot_arg,
+        columns_arg,
+        account_arg,
+        running_arg,
+        reverse_arg,
+        output_format_arg,
+        output_file_arg,
+        payee_arg,
+        amount_arg,
+        date_arg,
+        uncategorized_arg,
+        min_confidence_arg,
+        write_arg,
+        dry_run_arg,
+        from_arg,
+        to_arg,
+        currency_arg,
+        strict_prices_arg,
         command,
     } = matches;
 
+    let valuation = Valuation::from_args(&value_arg, &at_arg, &prices_arg)?;
+    let range = DateRange::from_args(&begin_arg, &end_arg, &period_arg)?;
+    let query = Query::parse(&query_arg)?;
+    let pivot = Pivot::from_args(&pivot_arg)?;
+    let selection = Selection {
+        range: &range,
+        query: &query,
+        pivot: pivot.as_ref(),
+    };
+    let output = Output::from_args(&output_format_arg, &output_file_arg)?;
+    let fallback = Fallback::from_args(&uncategorized_arg, &min_confidence_arg)?;
+    let destination = import::Destination::from_args(write_arg, dry_run_arg);
+
     match command {
-        Command::Account => account::account(ledger_file.as_str()),
-        Command::Balance => balance::balance(ledger_file.as_str()),
-        Command::Budget => budget::budget(ledger_file.as_str(), options_arg.as_str(), group_arg),
-        Command::Register => {
-            register::register(ledger_file.as_str(), options_arg.as_str(), group_arg)
-        }
+        Command::Account => account::account(ledger_file.as_str(), &output),
+        Command::Balance => balance::balance(
+            ledger_file.as_str(),
+            valuation,
+            &selection,
+            layout_arg,
+            depth_arg.as_str(),
+            &output,
+        ),
+        Command::BalanceSheet => balancesheet::balancesheet(
+            ledger_file.as_str(),
+            valuation,
+            range.end,
+            depth_arg.as_str(),
+            &output,
+        ),
+        Command::IncomeStatement => incomestatement::incomestatement(
+            ledger_file.as_str(),
+            valuation,
+            &range,
+            depth_arg.as_str(),
+            &output,
+        ),
+        Command::Check => check::check(ledger_file.as_str(), strict_prices_arg),
+        Command::Convert => convert::convert(
+            ledger_file.as_str(),
+            from_arg.as_str(),
+            to_arg.as_str(),
+            currency_arg.as_str(),
+            &output,
+        ),
+        Command::Budget => budget::budget(
+            ledger_file.as_str(),
+            group_arg,
+            columns_arg,
+            &range,
+            &query,
+            &output,
+        ),
+        Command::Payees => payees::payees(ledger_file.as_str(), &range, &query, &output),
+        Command::Register if !account_arg.is_empty() => register::account_register(
+            ledger_file.as_str(),
+            account_arg.as_str(),
+            running_arg,
+            reverse_arg,
+            &range,
+            valuation,
+            &output,
+        ),
+        Command::Register => register::register(
+            ledger_file.as_str(),
+            group_arg,
+            columns_arg,
+            &selection,
+            valuation,
+            &output,
+        ),
         Command::Csv => csv::csv(
             ledger_file.as_str(),
             options_arg.as_str(),
-            offset_arg.as_str(),
-            invert_arg,
+            rules_arg.as_str(),
+            fallback,
+            destination,
+        ),
+        Command::ImportOfx => import::ofx(
+            ledger_file.as_str(),
+            options_arg.as_str(),
+            account_arg.as_str(),
+            fallback,
+            destination,
+        ),
+        Command::RulesTest => rules::test(
+            ledger_file.as_str(),
+            payee_arg.as_str(),
+            amount_arg.as_str(),
+            date_arg.as_str(),
+            account_arg.as_str(),
+            fallback,
+            &output,
         ),
         Command::None => unreachable!(),
     }
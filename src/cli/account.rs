@@ -1,14 +1,10 @@
-extern crate serde_yaml;
-
-use crate::error::Result;
-use crate::ledger::LedgerFile;
+use rust_ledger::error::Result;
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::report::Output;
 
 /// returns all general ledger accounts
-pub fn account(filename: &str) -> Result<()> {
-    let file = std::fs::File::open(filename)?;
-    let deserialized_file: LedgerFile = serde_yaml::from_reader(file).unwrap();
-
-    LedgerFile::print_accounts(deserialized_file);
+pub fn account(filename: &str, output: &Output) -> Result<()> {
+    let deserialized_file = LedgerFile::read(filename)?;
 
-    Ok(())
+    output.write(deserialized_file.accounts_report())
 }
@@ -1,24 +1,58 @@
 extern crate clap;
 
-use crate::error::Error;
-use crate::ledger::Group;
 use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use rust_ledger::account_tree::Layout;
+use rust_ledger::error::{Error, Result};
+use rust_ledger::ledger::Group;
 
 pub struct Args {
     pub ledger_file: String,
     pub options_arg: String,
     pub group_arg: Group,
-    pub offset_arg: String,
-    pub invert_arg: bool,
+    pub rules_arg: String,
+    pub value_arg: String,
+    pub at_arg: String,
+    pub prices_arg: String,
+    pub depth_arg: String,
+    pub layout_arg: Layout,
+    pub begin_arg: String,
+    pub end_arg: String,
+    pub period_arg: String,
+    pub query_arg: String,
+    pub pivot_arg: String,
+    pub columns_arg: bool,
+    pub account_arg: String,
+    pub running_arg: bool,
+    pub reverse_arg: bool,
+    pub output_format_arg: String,
+    pub output_file_arg: String,
+    pub payee_arg: String,
+    pub amount_arg: String,
+    pub date_arg: String,
+    pub uncategorized_arg: String,
+    pub min_confidence_arg: String,
+    pub write_arg: bool,
+    pub dry_run_arg: bool,
+    pub from_arg: String,
+    pub to_arg: String,
+    pub currency_arg: String,
+    pub strict_prices_arg: bool,
     pub command: Command,
 }
 
 pub enum Command {
     Account,
     Balance,
+    BalanceSheet,
     Budget,
+    Check,
+    Convert,
+    IncomeStatement,
+    Payees,
     Register,
     Csv,
+    ImportOfx,
+    RulesTest,
     None,
 }
 
@@ -28,8 +62,34 @@ impl Args {
             ledger_file: String::from(""),
             options_arg: String::from(""),
             group_arg: Group::None,
-            offset_arg: String::from(""),
-            invert_arg: false,
+            rules_arg: String::from(""),
+            value_arg: String::from(""),
+            at_arg: String::from(""),
+            prices_arg: String::from(""),
+            depth_arg: String::from(""),
+            layout_arg: Layout::Tree,
+            begin_arg: String::from(""),
+            end_arg: String::from(""),
+            period_arg: String::from(""),
+            query_arg: String::from(""),
+            pivot_arg: String::from(""),
+            columns_arg: false,
+            account_arg: String::from(""),
+            running_arg: false,
+            reverse_arg: false,
+            output_format_arg: String::from(""),
+            output_file_arg: String::from(""),
+            payee_arg: String::from(""),
+            amount_arg: String::from(""),
+            date_arg: String::from(""),
+            uncategorized_arg: String::from(""),
+            min_confidence_arg: String::from(""),
+            write_arg: false,
+            dry_run_arg: false,
+            from_arg: String::from(""),
+            to_arg: String::from(""),
+            currency_arg: String::from(""),
+            strict_prices_arg: false,
             command: Command::None,
         }
     }
@@ -44,11 +104,171 @@ impl Args {
         };
     }
 
+    /// arguments that choose the format and destination of reports. they
+    /// are accepted before or after the subcommand
+    fn output_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+        [
+            Arg::with_name("output-format")
+                .short("O")
+                .long("output-format")
+                .help("format of reports")
+                .possible_values(&["table", "csv", "tsv", "json", "yaml", "markdown", "html"])
+                .takes_value(true)
+                .global(true),
+            Arg::with_name("output-file")
+                .long("output-file")
+                .help("write reports to file instead of stdout")
+                .takes_value(true)
+                .global(true),
+        ]
+    }
+
+    /// arguments that convert report amounts into a single currency
+    fn valuation_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+        [
+            Arg::with_name("value")
+                .long("value")
+                .help("convert amounts to currency using latest prices")
+                .takes_value(true),
+            Arg::with_name("at")
+                .long("at")
+                .help("valuation date (YYYY-MM-DD), defaults to today")
+                .takes_value(true)
+                .requires("value"),
+            Arg::with_name("prices")
+                .short("p")
+                .long("prices")
+                .help("location of price history file")
+                .takes_value(true)
+                .requires("value"),
+        ]
+    }
+
+    /// argument that collapses accounts below a depth of the account tree
+    fn depth_arg<'a, 'b>() -> Arg<'a, 'b> {
+        Arg::with_name("depth")
+            .short("d")
+            .long("depth")
+            .help("collapse accounts below depth")
+            .takes_value(true)
+    }
+
+    /// arguments that limit a report to the transactions of a date range
+    fn range_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+        [
+            Arg::with_name("begin")
+                .short("b")
+                .long("begin")
+                .help("include transactions on or after date (YYYY-MM-DD)")
+                .takes_value(true),
+            Arg::with_name("end")
+                .short("e")
+                .long("end")
+                .help("include transactions on or before date (YYYY-MM-DD)")
+                .takes_value(true),
+            Arg::with_name("period")
+                .long("period")
+                .help("include transactions within period, e.g. 2024-Q2, last month or 2023-07..2024-06")
+                .takes_value(true),
+        ]
+    }
+
+    fn resolve_range(&mut self, sub: &ArgMatches) {
+        self.begin_arg = sub.value_of("begin").unwrap_or("").to_string();
+        self.end_arg = sub.value_of("end").unwrap_or("").to_string();
+        self.period_arg = sub.value_of("period").unwrap_or("").to_string();
+    }
+
+    /// arguments for transactions that cannot be categorized confidently
+    fn fallback_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+        [
+            Arg::with_name("uncategorized")
+                .short("u")
+                .long("uncategorized")
+                .help("account of transactions that cannot be categorized [default: expense:uncategorized]")
+                .takes_value(true),
+            Arg::with_name("min-confidence")
+                .long("min-confidence")
+                .help("confidence between 0 and 1 needed to use an account learned from history [default: 0.5]")
+                .takes_value(true),
+        ]
+    }
+
+    fn resolve_fallback(&mut self, sub: &ArgMatches) {
+        self.uncategorized_arg = sub.value_of("uncategorized").unwrap_or("").to_string();
+        self.min_confidence_arg = sub.value_of("min-confidence").unwrap_or("").to_string();
+    }
+
+    /// arguments that merge imported transactions into the ledger file
+    /// instead of printing them
+    fn write_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+        [
+            Arg::with_name("write")
+                .short("w")
+                .long("write")
+                .help("merge new transactions into the ledger file, keeping a backup"),
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("show the changes --write would make to the ledger file as a diff")
+                .conflicts_with("write"),
+        ]
+    }
+
+    fn resolve_write(&mut self, sub: &ArgMatches) {
+        self.write_arg = sub.is_present("write");
+        self.dry_run_arg = sub.is_present("dry-run");
+    }
+
+    /// argument that limits a report to the postings matching a query
+    fn query_arg<'a, 'b>() -> Arg<'a, 'b> {
+        Arg::with_name("query")
+            .short("q")
+            .long("query")
+            .help("include postings matching query, e.g. acct:expense desc:/shell/i amt:>100")
+            .takes_value(true)
+    }
+
+    /// argument that groups postings by payee, tag or metadata value in
+    /// place of their account
+    fn pivot_arg<'a, 'b>() -> Arg<'a, 'b> {
+        Arg::with_name("pivot")
+            .long("pivot")
+            .help("group postings by payee or by the value of a tag or metadata key instead of account, e.g. payee or tag:project")
+            .takes_value(true)
+    }
+
+    /// flag that lays out grouped reports with periods as columns
+    fn columns_arg<'a, 'b>() -> Arg<'a, 'b> {
+        Arg::with_name("columns")
+            .short("C")
+            .long("columns")
+            .help("show accounts as rows and periods as columns")
+    }
+
+    fn resolve_group(&mut self, sub: &ArgMatches) {
+        self.group_arg = match sub.value_of("group") {
+            Some("yearly") => Group::Yearly,
+            Some("quarterly") => Group::Quarterly,
+            Some("monthly") => Group::Monthly,
+            Some("weekly") => Group::Weekly,
+            Some("daily") => Group::Daily,
+            _ => Group::None,
+        };
+        self.columns_arg = sub.is_present("columns");
+    }
+
+    fn resolve_valuation(&mut self, sub: &ArgMatches) {
+        self.value_arg = sub.value_of("value").unwrap_or("").to_string();
+        self.at_arg = sub.value_of("at").unwrap_or("").to_string();
+        self.prices_arg = sub.value_of("prices").unwrap_or("").to_string();
+    }
+
     pub fn populate_args(&mut self) {
         let matches = App::new("rust_ledger")
             .version(crate_version!())
             .author("Eric Crowder <eric@ebcrowder.dev>")
             .setting(AppSettings::ArgRequiredElseHelp)
+            .args(&Args::output_args())
             .subcommand(
                 SubCommand::with_name("account")
                     .about("account module")
@@ -71,11 +291,27 @@ impl Args {
                             .help("location of ledger file")
                             .takes_value(true)
                             .required(true),
-                    ),
+                    )
+                    .arg(Args::depth_arg())
+                    .arg(
+                        Arg::with_name("flat")
+                            .long("flat")
+                            .help("list accounts by full name without parents")
+                            .conflicts_with("tree"),
+                    )
+                    .arg(
+                        Arg::with_name("tree")
+                            .long("tree")
+                            .help("list accounts as a tree with parent subtotals (default)"),
+                    )
+                    .args(&Args::range_args())
+                    .arg(Args::query_arg())
+                    .arg(Args::pivot_arg())
+                    .args(&Args::valuation_args()),
             )
             .subcommand(
-                SubCommand::with_name("budget")
-                    .about("budget module")
+                SubCommand::with_name("balancesheet")
+                    .about("balance sheet module")
                     .arg(
                         Arg::with_name("filename")
                             .short("f")
@@ -85,10 +321,23 @@ impl Args {
                             .required(true),
                     )
                     .arg(
-                        Arg::with_name("option")
-                            .short("o")
-                            .long("option")
-                            .help("filter output by optional value")
+                        Arg::with_name("end")
+                            .short("e")
+                            .long("end")
+                            .help("report balances as of date (YYYY-MM-DD), defaults to today")
+                            .takes_value(true),
+                    )
+                    .arg(Args::depth_arg())
+                    .args(&Args::valuation_args()),
+            )
+            .subcommand(
+                SubCommand::with_name("budget")
+                    .about("budget module")
+                    .arg(
+                        Arg::with_name("filename")
+                            .short("f")
+                            .long("filename")
+                            .help("location of ledger file")
                             .takes_value(true)
                             .required(true),
                     )
@@ -98,13 +347,33 @@ impl Args {
                             .long("group")
                             .help("group budget output by value")
                             .takes_value(true)
-                            .possible_values(&["daily", "monthly", "yearly"])
+                            .possible_values(&["daily", "weekly", "monthly", "quarterly", "yearly"])
+                            .required(true),
+                    )
+                    .arg(Args::columns_arg())
+                    .args(&Args::range_args())
+                    .arg(Args::query_arg()),
+            )
+            .subcommand(
+                SubCommand::with_name("check")
+                    .about("check module")
+                    .arg(
+                        Arg::with_name("filename")
+                            .short("f")
+                            .long("filename")
+                            .help("location of ledger file")
+                            .takes_value(true)
                             .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("strict-prices")
+                            .long("strict-prices")
+                            .help("require exchanges between two currencies to balance at the prices of their date"),
                     ),
             )
             .subcommand(
-                SubCommand::with_name("register")
-                    .about("register module")
+                SubCommand::with_name("convert")
+                    .about("convert between yaml ledger files, ledger journals and beancount files")
                     .arg(
                         Arg::with_name("filename")
                             .short("f")
@@ -114,20 +383,100 @@ impl Args {
                             .required(true),
                     )
                     .arg(
-                        Arg::with_name("option")
-                            .short("o")
-                            .long("option")
-                            .help("filter output by optional value")
+                        Arg::with_name("from")
+                            .long("from")
+                            .help("format of ledger file [default: yaml for .yaml and .yml files, beancount for .beancount and .bean files, else journal]")
+                            .possible_values(&["yaml", "journal", "beancount"])
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("to")
+                            .long("to")
+                            .help("format to convert to [default: journal for yaml files, else yaml]")
+                            .possible_values(&["yaml", "journal", "beancount"])
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("currency")
+                            .short("c")
+                            .long("currency")
+                            .help("default currency of journals and beancount files, unless set by a D directive or operating_currency option")
                             .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("incomestatement")
+                    .about("income statement module")
+                    .arg(
+                        Arg::with_name("filename")
+                            .short("f")
+                            .long("filename")
+                            .help("location of ledger file")
+                            .takes_value(true)
+                            .required(true),
                     )
+                    .args(&Args::range_args())
+                    .arg(Args::depth_arg())
+                    .args(&Args::valuation_args()),
+            )
+            .subcommand(
+                SubCommand::with_name("payees")
+                    .about("list payees with their number of transactions and totals")
+                    .arg(
+                        Arg::with_name("filename")
+                            .short("f")
+                            .long("filename")
+                            .help("location of ledger file")
+                            .takes_value(true)
+                            .required(true),
+                    )
+                    .args(&Args::range_args())
+                    .arg(Args::query_arg()),
+            )
+            .subcommand(
+                SubCommand::with_name("register")
+                    .about("register module")
+                    .arg(
+                        Arg::with_name("filename")
+                            .short("f")
+                            .long("filename")
+                            .help("location of ledger file")
+                            .takes_value(true)
+                            .required(true),
+                    )
+                    .arg(Args::query_arg())
+                    .arg(Args::pivot_arg())
                     .arg(
                         Arg::with_name("group")
                             .short("g")
                             .long("group")
                             .help("group register output by value")
-                            .possible_values(&["daily", "monthly", "yearly"])
+                            .possible_values(&["daily", "weekly", "monthly", "quarterly", "yearly"])
                             .takes_value(true),
-                    ),
+                    )
+                    .arg(Args::columns_arg().requires("group"))
+                    .arg(
+                        Arg::with_name("account")
+                            .short("a")
+                            .long("account")
+                            .help("list postings to account and its sub-accounts")
+                            .takes_value(true)
+                            .conflicts_with_all(&["query", "pivot", "group"]),
+                    )
+                    .arg(
+                        Arg::with_name("running")
+                            .long("running")
+                            .help("show the running balance of account")
+                            .requires("account"),
+                    )
+                    .arg(
+                        Arg::with_name("reverse")
+                            .long("reverse")
+                            .help("list the latest postings first")
+                            .requires("account"),
+                    )
+                    .args(&Args::range_args())
+                    .args(&Args::valuation_args()),
             )
             .subcommand(
                 SubCommand::with_name("csv")
@@ -149,66 +498,239 @@ impl Args {
                             .required(true),
                     )
                     .arg(
-                        Arg::with_name("offset")
-                            .short("o")
-                            .long("offset")
-                            .help("offset account for each csv transaction")
-                            .takes_value(true),
+                        Arg::with_name("rules")
+                            .short("r")
+                            .long("rules")
+                            .help("path of rules file describing the csv file")
+                            .takes_value(true)
+                            .required(true),
                     )
-                    .arg(
-                        Arg::with_name("invert")
-                            .short("i")
-                            .long("invert")
-                            .help("invert amount for each csv transaction")
-                            .takes_value(false),
+                    .args(&Args::fallback_args())
+                    .args(&Args::write_args()),
+            )
+            .subcommand(
+                SubCommand::with_name("import")
+                    .about("import module")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        SubCommand::with_name("ofx")
+                            .about("import ofx or qfx bank statements")
+                            .arg(
+                                Arg::with_name("filename")
+                                    .short("f")
+                                    .long("filename")
+                                    .help("location of ledger file")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::with_name("ofx")
+                                    .short("o")
+                                    .long("ofx")
+                                    .help("path of ofx or qfx file")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::with_name("account")
+                                    .short("a")
+                                    .long("account")
+                                    .help("ledger account of the statement")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .args(&Args::fallback_args())
+                            .args(&Args::write_args()),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("rules")
+                    .about("categorization rules module")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        SubCommand::with_name("test")
+                            .about("show how the rules of the ledger file categorize a transaction")
+                            .arg(
+                                Arg::with_name("filename")
+                                    .short("f")
+                                    .long("filename")
+                                    .help("location of ledger file")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::with_name("payee")
+                                    .short("p")
+                                    .long("payee")
+                                    .help("payee as named by the bank")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::with_name("amount")
+                                    .long("amount")
+                                    .help("amount booked to the categorized account, positive for expenses")
+                                    .takes_value(true)
+                                    .allow_hyphen_values(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::with_name("date")
+                                    .short("d")
+                                    .long("date")
+                                    .help("date of the transaction (YYYY-MM-DD), defaults to today")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("account")
+                                    .short("a")
+                                    .long("account")
+                                    .help("ledger account the transaction is imported into")
+                                    .takes_value(true),
+                            )
+                            .args(&Args::fallback_args()),
                     ),
             )
             .get_matches();
 
+        // global arguments are propagated to the matches of the innermost
+        // subcommand
+        let mut innermost = matches.subcommand().1;
+        while let Some(nested) = innermost.and_then(|m| m.subcommand().1) {
+            innermost = Some(nested);
+        }
+        if let Some(sub) = innermost {
+            self.output_format_arg = sub.value_of("output-format").unwrap_or("").to_string();
+            self.output_file_arg = sub.value_of("output-file").unwrap_or("").to_string();
+        }
+
         if let Some(sub) = matches.subcommand_matches("register") {
             Args::resolve_ledger_file(self, sub);
-            self.options_arg = sub.value_of("option").unwrap_or("").to_string();
-            self.group_arg = match sub.value_of("group") {
-                Some("yearly") => Group::Yearly,
-                Some("monthly") => Group::Monthly,
-                Some("daily") => Group::Daily,
-                _ => Group::None,
-            }
+            Args::resolve_valuation(self, sub);
+            Args::resolve_group(self, sub);
+            Args::resolve_range(self, sub);
+            self.query_arg = sub.value_of("query").unwrap_or("").to_string();
+            self.pivot_arg = sub.value_of("pivot").unwrap_or("").to_string();
+            self.account_arg = sub.value_of("account").unwrap_or("").to_string();
+            self.running_arg = sub.is_present("running");
+            self.reverse_arg = sub.is_present("reverse");
+        }
+
+        if let Some(sub) = matches.subcommand_matches("payees") {
+            Args::resolve_ledger_file(self, sub);
+            Args::resolve_range(self, sub);
+            self.query_arg = sub.value_of("query").unwrap_or("").to_string();
         }
 
         if let Some(sub) = matches.subcommand_matches("budget") {
             Args::resolve_ledger_file(self, sub);
-            self.options_arg = sub.value_of("option").unwrap_or("").to_string();
-            self.group_arg = match sub.value_of("group") {
-                Some("yearly") => Group::Yearly,
-                Some("monthly") => Group::Monthly,
-                Some("daily") => Group::Daily,
-                _ => Group::None,
-            }
+            Args::resolve_group(self, sub);
+            Args::resolve_range(self, sub);
+            self.query_arg = sub.value_of("query").unwrap_or("").to_string();
         }
 
         if let Some(sub) = matches.subcommand_matches("csv") {
             Args::resolve_ledger_file(self, sub);
             self.options_arg = sub.value_of("csv").unwrap_or("").to_string();
-            self.offset_arg = sub.value_of("offset").unwrap_or("").to_string();
-            self.invert_arg = sub.is_present("invert");
+            self.rules_arg = sub.value_of("rules").unwrap_or("").to_string();
+            Args::resolve_fallback(self, sub);
+            Args::resolve_write(self, sub);
+        }
+
+        if let Some(sub) = matches
+            .subcommand_matches("import")
+            .and_then(|m| m.subcommand_matches("ofx"))
+        {
+            Args::resolve_ledger_file(self, sub);
+            self.options_arg = sub.value_of("ofx").unwrap_or("").to_string();
+            self.account_arg = sub.value_of("account").unwrap_or("").to_string();
+            Args::resolve_fallback(self, sub);
+            Args::resolve_write(self, sub);
+        }
+
+        if let Some(sub) = matches
+            .subcommand_matches("rules")
+            .and_then(|m| m.subcommand_matches("test"))
+        {
+            Args::resolve_ledger_file(self, sub);
+            self.payee_arg = sub.value_of("payee").unwrap_or("").to_string();
+            self.amount_arg = sub.value_of("amount").unwrap_or("").to_string();
+            self.date_arg = sub.value_of("date").unwrap_or("").to_string();
+            self.account_arg = sub.value_of("account").unwrap_or("").to_string();
+            Args::resolve_fallback(self, sub);
         }
 
         if let Some(sub) = matches.subcommand_matches("account") {
             Args::resolve_ledger_file(self, sub);
         }
 
+        if let Some(sub) = matches.subcommand_matches("check") {
+            Args::resolve_ledger_file(self, sub);
+            self.strict_prices_arg = sub.is_present("strict-prices");
+        }
+
+        if let Some(sub) = matches.subcommand_matches("convert") {
+            Args::resolve_ledger_file(self, sub);
+            self.from_arg = sub.value_of("from").unwrap_or("").to_string();
+            self.to_arg = sub.value_of("to").unwrap_or("").to_string();
+            self.currency_arg = sub.value_of("currency").unwrap_or("").to_string();
+        }
+
         if let Some(sub) = matches.subcommand_matches("balance") {
             Args::resolve_ledger_file(self, sub);
+            Args::resolve_valuation(self, sub);
+            self.depth_arg = sub.value_of("depth").unwrap_or("").to_string();
+            self.layout_arg = match sub.is_present("flat") {
+                true => Layout::Flat,
+                false => Layout::Tree,
+            };
+            Args::resolve_range(self, sub);
+            self.query_arg = sub.value_of("query").unwrap_or("").to_string();
+            self.pivot_arg = sub.value_of("pivot").unwrap_or("").to_string();
+        }
+
+        if let Some(sub) = matches.subcommand_matches("balancesheet") {
+            Args::resolve_ledger_file(self, sub);
+            Args::resolve_valuation(self, sub);
+            self.depth_arg = sub.value_of("depth").unwrap_or("").to_string();
+            self.end_arg = sub.value_of("end").unwrap_or("").to_string();
+        }
+
+        if let Some(sub) = matches.subcommand_matches("incomestatement") {
+            Args::resolve_ledger_file(self, sub);
+            Args::resolve_valuation(self, sub);
+            self.depth_arg = sub.value_of("depth").unwrap_or("").to_string();
+            Args::resolve_range(self, sub);
         }
 
         match matches.subcommand_name() {
             Some("account") => self.command = Command::Account,
             Some("balance") => self.command = Command::Balance,
+            Some("balancesheet") => self.command = Command::BalanceSheet,
             Some("budget") => self.command = Command::Budget,
+            Some("check") => self.command = Command::Check,
+            Some("convert") => self.command = Command::Convert,
+            Some("incomestatement") => self.command = Command::IncomeStatement,
+            Some("payees") => self.command = Command::Payees,
             Some("register") => self.command = Command::Register,
             Some("csv") => self.command = Command::Csv,
+            Some("import") => self.command = Command::ImportOfx,
+            Some("rules") => self.command = Command::RulesTest,
             _ => self.command = Command::None,
         };
     }
 }
+
+/// parse an optional depth argument, which must be a positive number
+pub fn parse_depth(depth: &str) -> Result<Option<usize>> {
+    match depth {
+        "" => Ok(None),
+        d => match d.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Some(n)),
+            _ => Err(Error::InvalidArg(format!(
+                "invalid depth {}: expected a positive number",
+                d
+            ))),
+        },
+    }
+}
@@ -1,14 +1,26 @@
-extern crate serde_yaml;
-
-use crate::error::Result;
-use crate::ledger::LedgerFile;
+use crate::cli::args::parse_depth;
+use crate::cli::Selection;
+use rust_ledger::account_tree::Layout;
+use rust_ledger::error::Result;
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::price::Valuation;
+use rust_ledger::report::Output;
 
 /// returns balances of all general ledger accounts
-pub fn balance(filename: &str) -> Result<()> {
-    let file = std::fs::File::open(filename)?;
-    let deserialized_file: LedgerFile = serde_yaml::from_reader(file).unwrap();
+pub fn balance(
+    filename: &str,
+    valuation: Option<Valuation>,
+    selection: &Selection,
+    layout: Layout,
+    depth: &str,
+    output: &Output,
+) -> Result<()> {
+    let depth = parse_depth(depth)?;
+    let mut deserialized_file = selection.apply(LedgerFile::read(filename)?);
 
-    LedgerFile::print_balances(deserialized_file);
+    if let Some(v) = valuation {
+        deserialized_file = deserialized_file.value(&v)?;
+    }
 
-    Ok(())
+    output.write(deserialized_file.balance_report(layout, depth))
 }
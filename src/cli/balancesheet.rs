@@ -0,0 +1,25 @@
+use crate::cli::args::parse_depth;
+use chrono::NaiveDate;
+use rust_ledger::error::Result;
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::price::Valuation;
+use rust_ledger::report::Output;
+
+/// returns assets, liabilities and equity as of a date
+pub fn balancesheet(
+    filename: &str,
+    valuation: Option<Valuation>,
+    end: Option<NaiveDate>,
+    depth: &str,
+    output: &Output,
+) -> Result<()> {
+    let date = end.unwrap_or_else(|| chrono::Local::today().naive_local());
+    let depth = parse_depth(depth)?;
+    let mut deserialized_file = LedgerFile::read(filename)?;
+
+    if let Some(v) = valuation {
+        deserialized_file = deserialized_file.value(&v)?;
+    }
+
+    output.write(deserialized_file.balance_sheet_report(date, depth))
+}
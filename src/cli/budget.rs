@@ -1,15 +1,26 @@
-extern crate serde_yaml;
-
-use crate::error::Result;
-use crate::ledger::{Group, LedgerFile};
+use rust_ledger::error::Result;
+use rust_ledger::ledger::{Group, LedgerFile};
+use rust_ledger::period::DateRange;
+use rust_ledger::query::Query;
+use rust_ledger::report::Output;
 
 /// generates budget to actual report for transactions
-/// by month or year
-pub fn budget(filename: &str, option: &str, group: Group) -> Result<()> {
-    let file = std::fs::File::open(filename)?;
-    let deserialized_file: LedgerFile = serde_yaml::from_reader(file).unwrap();
-
-    LedgerFile::print_budget_actual(deserialized_file, option, group);
+/// by period
+pub fn budget(
+    filename: &str,
+    group: Group,
+    columns: bool,
+    range: &DateRange,
+    query: &Query,
+    output: &Output,
+) -> Result<()> {
+    let deserialized_file = LedgerFile::read(filename)?
+        .filter_by_range(range)
+        .filter_by_query(query);
 
-    Ok(())
+    if columns {
+        output.write(deserialized_file.budget_column_report(group, range)?)
+    } else {
+        output.write(deserialized_file.budget_period_report(group))
+    }
 }
@@ -0,0 +1,20 @@
+use rust_ledger::error::Result;
+use rust_ledger::ledger::LedgerFile;
+
+/// validates the ledger file and reports a summary of its contents. with
+/// `strict_prices`, exchanges must balance at the prices of their date
+pub fn check(filename: &str, strict_prices: bool) -> Result<()> {
+    let deserialized_file = match strict_prices {
+        true => LedgerFile::read_strict(filename)?,
+        false => LedgerFile::read(filename)?,
+    };
+
+    println!(
+        "{}: {} accounts and {} transactions checked, no errors found",
+        filename,
+        deserialized_file.accounts.len(),
+        deserialized_file.transactions.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use rust_ledger::convert::{self, Change, Format};
+use rust_ledger::error::{Error, Result};
+use rust_ledger::report::Output;
+use std::fs;
+use std::io::{stdout, Write};
+
+/// converts the ledger file between yaml, journal and beancount formats,
+/// writing the converted file to stdout or the output file. features that
+/// were dropped or approximated are listed on stderr
+pub fn convert(
+    filename: &str,
+    from: &str,
+    to: &str,
+    currency: &str,
+    output: &Output,
+) -> Result<()> {
+    let from = Format::from_args(from, filename)?;
+    let to = match to {
+        "" => from.counterpart(),
+        t => Format::parse(t)?,
+    };
+    let currency = Some(currency).filter(|c| !c.is_empty());
+
+    let (ledger_file, mut notes) = convert::read(filename, from, currency)?;
+    let (converted, rendered) = convert::render(&ledger_file, to)?;
+    notes.extend(rendered);
+
+    match &output.file {
+        None => stdout().write_all(converted.as_bytes())?,
+        Some(file) => fs::write(file, converted)
+            .map_err(|e| Error::InvalidArg(format!("could not write {}: {}", file, e)))?,
+    }
+
+    for (change, heading) in [
+        (Change::Dropped, "dropped:"),
+        (Change::Approximated, "approximated:"),
+    ] {
+        let mut notes = notes.iter().filter(|n| n.change == change).peekable();
+        if notes.peek().is_some() {
+            eprintln!("{}", heading);
+        }
+        for note in notes {
+            eprintln!("  {}", note);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use rust_ledger::error::Result;
+use rust_ledger::import::categorize::Fallback;
+use rust_ledger::import::merge::Merge;
+use rust_ledger::import::{ofx, Import};
+use rust_ledger::ledger::{LedgerFile, Transaction};
+use rust_ledger::yaml;
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+#[derive(Serialize)]
+struct ImportOutput {
+    transactions: Vec<Transaction>,
+}
+
+/// where imported transactions are written to
+pub enum Destination {
+    Stdout,
+    /// merged into the ledger file
+    Ledger,
+    /// shown as a diff of the ledger file, which is left untouched
+    DryRun,
+}
+
+impl Destination {
+    pub fn from_args(write: bool, dry_run: bool) -> Destination {
+        if dry_run {
+            Destination::DryRun
+        } else if write {
+            Destination::Ledger
+        } else {
+            Destination::Stdout
+        }
+    }
+}
+
+/// convert ofx and qfx bank statements of `account` to yaml transactions
+pub fn ofx(
+    ledger_file: &str,
+    ofx_file: &str,
+    account: &str,
+    fallback: Fallback,
+    destination: Destination,
+) -> Result<()> {
+    let deserialized_file = LedgerFile::read(ledger_file)?;
+
+    // OFX 1.x files are often encoded in a legacy charset
+    let bytes =
+        fs::read(ofx_file).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", ofx_file, e)))?;
+
+    let import = ofx::import(&bytes, account, &deserialized_file, fallback)?;
+    write(import, &deserialized_file, ledger_file, destination)
+}
+
+/// write imported transactions to `destination`, either to stdout in the
+/// yaml format of ledger files or merged into `ledger_file`. a summary of
+/// the import, the transactions that need to be reviewed and the accounts
+/// that need to be declared are written to stderr, so they do not end up
+/// in redirected output
+pub fn write(
+    import: Import,
+    deserialized_file: &LedgerFile,
+    ledger_file: &str,
+    destination: Destination,
+) -> Result<()> {
+    eprintln!(
+        "{} new, {} skipped, {} ambiguous",
+        import.transactions.len(),
+        import.skipped.len(),
+        import.ambiguous.len()
+    );
+
+    let duplicates = [
+        ("skipped as imported before:", &import.skipped),
+        ("imported, but possibly imported before:", &import.ambiguous),
+    ];
+    for (title, list) in duplicates {
+        if !list.is_empty() {
+            eprintln!("\n{}", title);
+        }
+        for d in list {
+            eprintln!(
+                "  {} {} {} (matches {} {})",
+                d.transaction.date,
+                d.transaction.description,
+                d.transaction.amount.unwrap_or_default(),
+                d.existing.date,
+                d.existing.description
+            );
+        }
+    }
+
+    if !import.uncategorized.is_empty() {
+        eprintln!("\ntransactions that could not be categorized:");
+    }
+    for u in &import.uncategorized {
+        match &u.suggestion {
+            Some(s) => eprintln!(
+                "  {} {} {} (suggested {}, confidence {:.2})",
+                u.date, u.description, u.amount, s.account, s.confidence
+            ),
+            None => eprintln!("  {} {} {}", u.date, u.description, u.amount),
+        }
+    }
+
+    if !import.unasserted.is_empty() {
+        eprintln!("\nstatement balances not asserted, as the last transaction up to their date was not imported:");
+    }
+    for b in &import.unasserted {
+        eprintln!("  {} {} {}", b.date, b.account, b.balance);
+    }
+
+    // merging declares them along with the transactions
+    let undeclared = import.undeclared_accounts(deserialized_file);
+    if !undeclared.is_empty() {
+        match destination {
+            Destination::Stdout => {
+                eprintln!("\naccounts that are not declared in {}:", ledger_file)
+            }
+            _ => eprintln!("\naccounts to declare in {}:", ledger_file),
+        }
+    }
+    for account in &undeclared {
+        eprintln!("  {}", account);
+    }
+
+    match destination {
+        Destination::Stdout => print!(
+            "{}",
+            yaml::to_string(&ImportOutput {
+                transactions: import.transactions,
+            })?
+        ),
+        Destination::DryRun => {
+            print!(
+                "{}",
+                Merge::read(ledger_file, &import.transactions, &undeclared)?.diff(ledger_file)
+            )
+        }
+        Destination::Ledger => {
+            let merge = Merge::read(ledger_file, &import.transactions, &undeclared)?;
+            if !merge.is_empty() {
+                let backup = merge.write(ledger_file)?;
+                eprintln!(
+                    "\nmerged into {}, previous version kept as {}",
+                    ledger_file, backup
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use crate::cli::args::parse_depth;
+use rust_ledger::error::Result;
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::period::DateRange;
+use rust_ledger::price::Valuation;
+use rust_ledger::report::Output;
+
+/// returns income and expenses over a date range
+pub fn incomestatement(
+    filename: &str,
+    valuation: Option<Valuation>,
+    range: &DateRange,
+    depth: &str,
+    output: &Output,
+) -> Result<()> {
+    let depth = parse_depth(depth)?;
+    let mut deserialized_file = LedgerFile::read(filename)?.filter_by_range(range);
+
+    if let Some(v) = valuation {
+        deserialized_file = deserialized_file.value(&v)?;
+    }
+
+    output.write(deserialized_file.income_statement_report(depth))
+}
@@ -0,0 +1,12 @@
+use rust_ledger::error::Result;
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::period::DateRange;
+use rust_ledger::query::Query;
+use rust_ledger::report::Output;
+
+/// returns every payee with its number of transactions and total
+pub fn payees(filename: &str, range: &DateRange, query: &Query, output: &Output) -> Result<()> {
+    let deserialized_file = LedgerFile::read(filename)?.filter_by_range(range);
+
+    output.write(deserialized_file.payees_report(query))
+}
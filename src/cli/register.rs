@@ -1,18 +1,51 @@
-extern crate serde_yaml;
-
-use crate::error::Result;
-use crate::ledger::{Group, LedgerFile};
+use crate::cli::Selection;
+use rust_ledger::error::Result;
+use rust_ledger::ledger::{Group, LedgerFile};
+use rust_ledger::period::DateRange;
+use rust_ledger::price::Valuation;
+use rust_ledger::report::Output;
 
 /// returns all general ledger transactions
-pub fn register(filename: &str, option: &str, group: Group) -> Result<()> {
-    let file = std::fs::File::open(filename)?;
-    let deserialized_file: LedgerFile = serde_yaml::from_reader(file).unwrap();
+pub fn register(
+    filename: &str,
+    group: Group,
+    columns: bool,
+    selection: &Selection,
+    valuation: Option<Valuation>,
+    output: &Output,
+) -> Result<()> {
+    let mut deserialized_file = selection.apply(LedgerFile::read(filename)?);
+
+    if let Some(v) = valuation {
+        deserialized_file = deserialized_file.value(&v)?;
+    }
+
+    match group {
+        Group::None => output.write(deserialized_file.register_report()),
+        _ if columns => {
+            output.write(deserialized_file.register_column_report(group, selection.range)?)
+        }
+        _ => output.write(deserialized_file.register_period_report(group)),
+    }
+}
+
+/// returns the postings of a single account. postings before the range
+/// are needed for the running balance, so the file is not filtered by
+/// date up front
+pub fn account_register(
+    filename: &str,
+    account: &str,
+    running: bool,
+    reverse: bool,
+    range: &DateRange,
+    valuation: Option<Valuation>,
+    output: &Output,
+) -> Result<()> {
+    let mut deserialized_file = LedgerFile::read(filename)?;
 
-    if group == Group::None {
-        LedgerFile::print_register(deserialized_file, option)
-    } else {
-        LedgerFile::print_register_group(deserialized_file, option, group)
+    if let Some(v) = valuation {
+        deserialized_file = deserialized_file.value(&v)?;
     }
 
-    Ok(())
+    output.write(deserialized_file.account_register_report(account, range, running, reverse)?)
 }
@@ -0,0 +1,35 @@
+use rust_decimal::Decimal;
+use rust_ledger::error::{Error, Result};
+use rust_ledger::import::categorize::{Candidate, Fallback};
+use rust_ledger::ledger::LedgerFile;
+use rust_ledger::period::parse_date;
+use rust_ledger::report::Output;
+use std::str::FromStr;
+
+/// categorize a single transaction with the rules of the ledger file
+pub fn test(
+    filename: &str,
+    payee: &str,
+    amount: &str,
+    date: &str,
+    account: &str,
+    fallback: Fallback,
+    output: &Output,
+) -> Result<()> {
+    let deserialized_file = LedgerFile::read(filename)?;
+
+    let amount = Decimal::from_str(amount)
+        .map_err(|_| Error::InvalidArg(format!("invalid amount: {}", amount)))?;
+    let date = match date {
+        "" => chrono::Local::today().naive_local(),
+        d => parse_date(d)?,
+    };
+
+    let candidate = Candidate {
+        date,
+        payee,
+        amount,
+        account,
+    };
+    output.write(deserialized_file.rules_test_report(&candidate, fallback)?)
+}
@@ -0,0 +1,87 @@
+use crate::error::{Diagnostic, Error, Result};
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// declares a commodity that is not an ISO 4217 currency, such as
+/// a stock or fund ticker, along with the number of decimal places
+/// its amounts may carry
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Commodity {
+    pub commodity: String,
+    pub precision: u32,
+}
+
+/// running totals keyed by currency / commodity code, so that
+/// amounts of unlike units are never added together
+pub type Totals = BTreeMap<String, Decimal>;
+
+/// unit that an amount is denominated in
+#[derive(Debug, PartialEq, Clone)]
+pub enum Unit {
+    Currency(&'static iso::Currency),
+    Commodity(Commodity),
+}
+
+impl Unit {
+    /// resolve `code` to an ISO 4217 currency or a declared commodity.
+    /// unknown codes are an error rather than a silent fallback
+    pub fn find(code: &str, commodities: &[Commodity]) -> Result<Unit> {
+        if let Some(c) = commodities.iter().find(|c| c.commodity == code) {
+            return Ok(Unit::Commodity(c.clone()));
+        }
+
+        match iso::find(code) {
+            Some(c) => Ok(Unit::Currency(c)),
+            None => Err(Error::Money(Diagnostic::new(
+                format!("unknown currency or commodity: {}", code),
+                "",
+            ))),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        match self {
+            Unit::Currency(c) => c.iso_alpha_code,
+            Unit::Commodity(c) => &c.commodity,
+        }
+    }
+
+    /// number of decimal places an amount in this unit may carry
+    pub fn exponent(&self) -> u32 {
+        match self {
+            Unit::Currency(c) => c.exponent,
+            Unit::Commodity(c) => c.precision,
+        }
+    }
+
+    /// format `amount` padded to the minor unit of this unit. currencies
+    /// use their locale format (e.g. `$1,000.00`), commodities are
+    /// suffixed with their code (e.g. `10.5000 VTI`)
+    pub fn format(&self, mut amount: Decimal) -> String {
+        amount.rescale(self.exponent());
+
+        match self {
+            Unit::Currency(c) => Money::from_decimal(amount, *c).to_string(),
+            Unit::Commodity(c) => format!("{} {}", amount, c.commodity),
+        }
+    }
+}
+
+#[test]
+fn find_unknown_currency_is_error() {
+    assert!(Unit::find("XYZ", &[]).is_err());
+}
+
+#[test]
+fn find_declared_commodity() {
+    let commodities = vec![Commodity {
+        commodity: "VTI".to_string(),
+        precision: 4,
+    }];
+    let unit = Unit::find("VTI", &commodities).unwrap();
+
+    assert_eq!(unit.format(Decimal::new(105, 1)), "10.5000 VTI");
+    assert_eq!(Unit::find("EUR", &commodities).unwrap().code(), "EUR");
+}
@@ -0,0 +1,256 @@
+pub mod beancount;
+mod book;
+pub mod journal;
+
+use crate::commodity::{Commodity, Unit};
+use crate::error::{Error, Result};
+use crate::ledger::{LedgerFile, Transaction, TransactionList};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// description of the transaction that holds the opening balances of
+/// accounts in plain text formats
+const OPENING_BALANCES: &str = "opening balances";
+
+/// file formats ledger files can be converted from and to
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// the yaml format of rust_ledger
+    Yaml,
+    /// plain text journals of ledger and hledger
+    Journal,
+    /// plain text files of beancount
+    Beancount,
+}
+
+/// what became of a feature that the format converted to cannot represent
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Change {
+    Dropped,
+    Approximated,
+}
+
+/// feature of a converted file that was not carried over exactly
+#[derive(Debug, PartialEq, Clone)]
+pub struct Note {
+    pub change: Change,
+    /// line of the feature in the file converted from, if known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Format> {
+        match format {
+            "yaml" => Ok(Format::Yaml),
+            "journal" => Ok(Format::Journal),
+            "beancount" => Ok(Format::Beancount),
+            f => Err(Error::InvalidArg(format!("unknown format: {}", f))),
+        }
+    }
+
+    /// format named by `format`, or else the format suggested by the
+    /// extension of `filename`. files without a yaml or beancount
+    /// extension are taken to be journals
+    pub fn from_args(format: &str, filename: &str) -> Result<Format> {
+        match format {
+            "" if filename.ends_with(".yaml") || filename.ends_with(".yml") => Ok(Format::Yaml),
+            "" if filename.ends_with(".beancount") || filename.ends_with(".bean") => {
+                Ok(Format::Beancount)
+            }
+            "" => Ok(Format::Journal),
+            f => Format::parse(f),
+        }
+    }
+
+    /// format a file of this format is converted to by default
+    pub fn counterpart(&self) -> Format {
+        match self {
+            Format::Yaml => Format::Journal,
+            Format::Journal | Format::Beancount => Format::Yaml,
+        }
+    }
+}
+
+impl Note {
+    pub fn dropped(line: Option<usize>, message: String) -> Note {
+        Note {
+            change: Change::Dropped,
+            line,
+            message,
+        }
+    }
+
+    pub fn approximated(line: Option<usize>, message: String) -> Note {
+        Note {
+            change: Change::Approximated,
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// read the ledger file located at `filename` in `format`, along with
+/// notes on what could not be read exactly. `currency` is the default
+/// currency of files that do not set one themselves. converted files are
+/// validated like yaml ledger files, with errors pointing into the
+/// converted yaml
+pub fn read(
+    filename: &str,
+    format: Format,
+    currency: Option<&str>,
+) -> Result<(LedgerFile, Vec<Note>)> {
+    if format == Format::Yaml {
+        return Ok((LedgerFile::read(filename)?, Vec::new()));
+    }
+
+    let source = std::fs::read_to_string(filename)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+    let (ledger_file, notes) = match format {
+        Format::Beancount => beancount::parse(&source, currency),
+        _ => journal::parse(&source, currency).map(|f| (f, Vec::new())),
+    }
+    .map_err(|e| e.locate(filename, &source))?;
+
+    let yaml = ledger_file.to_yaml()?;
+    let ledger_file = LedgerFile::parse(&yaml)
+        .map_err(|e| e.locate(&format!("{} (converted)", filename), &yaml))?;
+
+    Ok((ledger_file, notes))
+}
+
+/// render `ledger_file` in `format`, along with notes on what `format`
+/// cannot represent
+pub fn render(ledger_file: &LedgerFile, format: Format) -> Result<(String, Vec<Note>)> {
+    match format {
+        Format::Yaml => Ok((ledger_file.to_yaml()?, Vec::new())),
+        Format::Journal => Ok(journal::write(ledger_file)),
+        Format::Beancount => Ok(beancount::write(ledger_file)),
+    }
+}
+
+/// amount in `code`, padded to the decimal places of its currency
+pub(crate) fn format_amount(value: Decimal, code: &str, commodities: &[Commodity]) -> String {
+    let mut value = value;
+    if let Ok(unit) = Unit::find(code, commodities) {
+        if value.scale() < unit.exponent() {
+            value.rescale(unit.exponent());
+        }
+    }
+
+    format!("{} {}", value, code)
+}
+
+/// transaction that sets the opening amounts of accounts in plain text
+/// formats, dated on the first transaction of the ledger file. amounts that
+/// do not add up to zero are offset against `offset_account`
+pub(crate) fn opening_balances(
+    ledger_file: &LedgerFile,
+    offset_account: &str,
+) -> Option<Transaction> {
+    let mut lists = Vec::new();
+    let mut totals: BTreeMap<String, Decimal> = BTreeMap::new();
+    for account in ledger_file.accounts.iter().filter(|a| !a.amount.is_zero()) {
+        let currency = ledger_file.resolve_currency(&account.currency, &account.account);
+        *totals.entry(currency.clone()).or_default() += account.amount;
+        lists.push(TransactionList {
+            account: account.account.clone(),
+            amount: account.amount,
+            currency: Some(currency),
+            assert_balance: None,
+            status: None,
+            comment: None,
+            tags: None,
+            meta: None,
+        });
+    }
+    for (currency, total) in totals.into_iter().filter(|(_, t)| !t.is_zero()) {
+        lists.push(TransactionList {
+            account: offset_account.to_string(),
+            amount: -total,
+            currency: Some(currency),
+            assert_balance: None,
+            status: None,
+            comment: None,
+            tags: None,
+            meta: None,
+        });
+    }
+    if lists.is_empty() {
+        return None;
+    }
+
+    let date = ledger_file
+        .transactions
+        .iter()
+        .map(|t| t.date)
+        .min()
+        .unwrap_or_else(|| chrono::Local::today().naive_local());
+
+    Some(Transaction {
+        date,
+        account: None,
+        amount: None,
+        currency: None,
+        description: OPENING_BALANCES.to_string(),
+        offset_account: None,
+        assert_balance: None,
+        transactions: Some(lists),
+        external_id: None,
+        payee: None,
+        tags: None,
+        meta: None,
+        status: None,
+        code: None,
+        comment: None,
+    })
+}
+
+/// categorization rules and payee aliases have no counterpart in plain
+/// text formats
+pub(crate) fn rules_notes(ledger_file: &LedgerFile) -> Vec<Note> {
+    let counts = [
+        (
+            ledger_file.rules.as_ref().map_or(0, |r| r.len()),
+            "categorization rules",
+        ),
+        (
+            ledger_file.payee_aliases.as_ref().map_or(0, |a| a.len()),
+            "payee aliases",
+        ),
+    ];
+
+    counts
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, what)| Note::dropped(None, format!("{} {} dropped", n, what)))
+        .collect()
+}
+
+#[test]
+fn format_from_args() {
+    assert_eq!(Format::from_args("", "ledger.yaml").unwrap(), Format::Yaml);
+    assert_eq!(
+        Format::from_args("", "2020.journal").unwrap(),
+        Format::Journal
+    );
+    assert_eq!(
+        Format::from_args("", "2020.beancount").unwrap(),
+        Format::Beancount
+    );
+    assert_eq!(
+        Format::from_args("yaml", "ledger.dat").unwrap(),
+        Format::Yaml
+    );
+    assert!(Format::from_args("qif", "ledger.qif").is_err());
+}
@@ -0,0 +1,1516 @@
+use super::book::{error, Amount, Book, Check, Entry, EntryPosting, Pad};
+use super::{format_amount, opening_balances, rules_notes, Note};
+use crate::account_tree::AccountType;
+use crate::commodity::{Commodity, Unit};
+use crate::error::Result;
+use crate::ledger::{LedgerFile, Meta, Status, Transaction};
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// account that opening balances which do not add up to zero are offset
+/// against
+const OPENING_BALANCES_ACCOUNT: &str = "Equity:Opening-Balances";
+
+/// metadata of transactions that have fields of their own in ledger files
+const TRANSACTION_METADATA: [&str; 3] = ["code", "comment", "external_id"];
+
+/// metadata of postings that have fields of their own in ledger files
+const POSTING_METADATA: [&str; 2] = ["comment", "tags"];
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    Str(String),
+}
+
+/// directive that the indented lines following it belong to
+enum Block {
+    None,
+    Entry,
+    Commodity(String),
+    /// directives whose metadata is not kept, described for the notes
+    Other(String),
+}
+
+#[derive(Default)]
+struct Parser {
+    book: Book,
+    /// tags of `pushtag` directives
+    tags: Vec<String>,
+    /// metadata of `pushmeta` directives
+    meta: Vec<(String, String)>,
+}
+
+/// lines of the file, where strings spanning several lines are joined into
+/// the line they start on
+fn lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut open: Option<(usize, String)> = None;
+
+    for (i, text) in source.lines().enumerate() {
+        let (line, joined) = match open.take() {
+            Some((line, joined)) => (line, format!("{}\n{}", joined, text)),
+            None => (i + 1, text.to_string()),
+        };
+        match ends_in_string(&joined) {
+            true => open = Some((line, joined)),
+            false => lines.push((line, joined)),
+        }
+    }
+
+    lines.extend(open);
+    lines
+}
+
+fn ends_in_string(text: &str) -> bool {
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => return false,
+            _ => {}
+        }
+    }
+    quoted
+}
+
+/// words and strings of a line up to its comment
+fn tokenize(text: &str, line: usize) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => break,
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => string.extend(chars.next()),
+                        Some(c) => string.push(c),
+                        None => return Err(error(line, "unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Str(string));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '"' || c == ';' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn text(token: &Token) -> &str {
+    match token {
+        Token::Word(w) | Token::Str(w) => w,
+    }
+}
+
+fn is_currency(word: &str) -> bool {
+    word.len() <= 24
+        && word.starts_with(|c: char| c.is_ascii_uppercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || "'._-".contains(c))
+}
+
+fn parse_number(word: &str, line: usize) -> Result<Decimal> {
+    Decimal::from_str(&word.replace(',', "")).map_err(|_| {
+        let message = match word.contains(['(', ')', '+', '*', '/']) {
+            true => format!("arithmetic expressions are not supported: {}", word),
+            false => format!("invalid number: {}", word),
+        };
+        error(line, message)
+    })
+}
+
+/// number followed by its currency, which may be left out. returns the
+/// number of tokens taken
+fn parse_amount(tokens: &[Token], line: usize) -> Result<(Amount, usize)> {
+    let value = match tokens.first() {
+        Some(Token::Word(w)) => parse_number(w, line)?,
+        _ => return Err(error(line, "missing amount".to_string())),
+    };
+
+    match tokens.get(1) {
+        Some(Token::Word(c)) if is_currency(c) => Ok(((value, c.clone()), 2)),
+        _ => Ok(((value, String::new()), 1)),
+    }
+}
+
+fn parse_date(word: &str, line: usize) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&word.replace('/', "-"), "%Y-%m-%d")
+        .map_err(|_| error(line, format!("invalid date: {}", word)))
+}
+
+/// key of a metadata line such as `invoice: "2020-14"`
+fn metadata_key(tokens: &[Token]) -> Option<&str> {
+    match tokens.first() {
+        Some(Token::Word(w)) => w.strip_suffix(':').filter(|key| {
+            key.starts_with(|c: char| c.is_ascii_lowercase())
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }),
+        _ => None,
+    }
+}
+
+fn metadata_value(tokens: &[Token]) -> String {
+    tokens[1..]
+        .iter()
+        .map(text)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+impl Parser {
+    fn status(&mut self, flag: &str, line: usize, owner: &str) -> Option<Status> {
+        match flag {
+            "*" => Some(Status::Cleared),
+            "!" => Some(Status::Pending),
+            "txn" => None,
+            f => {
+                self.book.notes.push(Note::dropped(
+                    Some(line),
+                    format!("flag {} of {} dropped", f, owner),
+                ));
+                None
+            }
+        }
+    }
+
+    fn parse(&mut self, source: &str) -> Result<()> {
+        let mut block = Block::None;
+
+        for (line, text) in lines(source) {
+            let text = text.trim_end();
+            if text.trim().is_empty() {
+                block = Block::None;
+                continue;
+            }
+
+            if text.starts_with([' ', '\t']) {
+                let text = text.trim();
+                match block {
+                    Block::Entry => self.entry_line(text, line)?,
+                    _ if text.starts_with(';') => {}
+                    ref b => self.directive_line(b, text, line)?,
+                }
+                continue;
+            }
+
+            block = match text.chars().next() {
+                Some(c) if c.is_ascii_digit() => self.dated(tokenize(text, line)?, line)?,
+                Some(';' | '*' | '#' | ':' | '!' | '&' | '%' | '|') => Block::None,
+                _ => {
+                    self.undated(tokenize(text, line)?, line)?;
+                    Block::None
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    /// directives that are not dated, such as `option`
+    fn undated(&mut self, tokens: Vec<Token>, line: usize) -> Result<()> {
+        let args: Vec<&str> = tokens.iter().skip(1).map(text).collect();
+        let dropped = |what: String| Note::dropped(Some(line), format!("{} dropped", what));
+
+        match (text(&tokens[0]), &args[..]) {
+            ("option", ["operating_currency", currency])
+                if self.book.default_currency.is_none() =>
+            {
+                self.book.default_currency = Some(currency.to_string());
+            }
+            ("option", ["operating_currency", currency]) => self
+                .book
+                .notes
+                .push(dropped(format!("operating currency {}", currency))),
+            ("option", [name, ..]) => self.book.notes.push(dropped(format!("option {}", name))),
+            ("plugin", [name, ..]) => self.book.notes.push(dropped(format!("plugin {}", name))),
+            ("pushmeta", [..]) => match metadata_key(&tokens[1..]) {
+                Some(key) => self
+                    .meta
+                    .push((key.to_string(), metadata_value(&tokens[1..]))),
+                None => return Err(error(line, "invalid pushmeta".to_string())),
+            },
+            ("popmeta", [key, ..]) => {
+                let key = key.trim_end_matches(':');
+                if let Some(i) = self.meta.iter().rposition(|(k, _)| k == key) {
+                    self.meta.remove(i);
+                }
+            }
+            ("pushtag", [tag]) => self.tags.push(tag.trim_start_matches('#').to_string()),
+            ("poptag", [tag]) => {
+                let tag = tag.trim_start_matches('#');
+                if let Some(i) = self.tags.iter().rposition(|t| t == tag) {
+                    self.tags.remove(i);
+                }
+            }
+            ("include", _) => {
+                return Err(error(
+                    line,
+                    "include is not supported, convert the included file on its own".to_string(),
+                ))
+            }
+            (d, _) => return Err(error(line, format!("unsupported directive: {}", d))),
+        }
+
+        Ok(())
+    }
+
+    fn dated(&mut self, tokens: Vec<Token>, line: usize) -> Result<Block> {
+        let date = parse_date(text(&tokens[0]), line)?;
+        let keyword = tokens.get(1).map(text).unwrap_or("");
+        let args = tokens.get(2..).unwrap_or(&[]);
+        let word = |i: usize| match args.get(i) {
+            Some(Token::Word(w)) => Ok(w.clone()),
+            _ => Err(error(line, format!("invalid {} directive", keyword))),
+        };
+
+        match keyword {
+            "open" => {
+                let account = word(0)?;
+                self.book.declare(&account);
+
+                let currencies: Vec<String> = args[1..]
+                    .iter()
+                    .filter_map(|t| match t {
+                        Token::Word(w) => Some(w.split(',').filter(|c| !c.is_empty())),
+                        Token::Str(_) => None,
+                    })
+                    .flatten()
+                    .map(String::from)
+                    .collect();
+                if let [currency] = &currencies[..] {
+                    if let Some(a) = self.book.accounts.iter_mut().find(|(a, _)| *a == account) {
+                        a.1 = Some(currency.clone());
+                    }
+                } else if !currencies.is_empty() {
+                    self.book.notes.push(Note::dropped(
+                        Some(line),
+                        format!(
+                            "currencies {} of {} dropped, accounts have a single default currency",
+                            currencies.join(", "),
+                            account
+                        ),
+                    ));
+                }
+                if let Some(Token::Str(booking)) = args.last() {
+                    self.book.notes.push(Note::dropped(
+                        Some(line),
+                        format!("booking method {} of {} dropped", booking, account),
+                    ));
+                }
+
+                Ok(Block::Other(format!("open of {}", account)))
+            }
+            "close" => {
+                let account = word(0)?;
+                self.book.notes.push(Note::dropped(
+                    Some(line),
+                    format!("close of {} on {} dropped", account, date),
+                ));
+                Ok(Block::Other(format!("close of {}", account)))
+            }
+            "commodity" => {
+                let code = word(0)?;
+                self.book.precisions.entry(code.clone()).or_default();
+                Ok(Block::Commodity(code))
+            }
+            "balance" => {
+                let account = word(0)?;
+                let (amount, taken) = parse_amount(&args[1..], line)?;
+                if args.get(1 + taken).map(text) == Some("~") {
+                    self.book.notes.push(Note::dropped(
+                        Some(line),
+                        format!(
+                            "tolerance of the balance of {} on {} dropped",
+                            account, date
+                        ),
+                    ));
+                }
+                self.book.seen(&amount);
+                self.book.checks.push(Check {
+                    line,
+                    date,
+                    account: account.clone(),
+                    amount,
+                });
+                Ok(Block::Other(format!("balance of {}", account)))
+            }
+            "pad" => {
+                let account = word(0)?;
+                self.book.pads.push(Pad {
+                    line,
+                    date,
+                    account: account.clone(),
+                    source: word(1)?,
+                    index: self.book.entries.len(),
+                });
+                Ok(Block::Other(format!("pad of {}", account)))
+            }
+            "price" => {
+                let commodity = word(0)?;
+                let (price, _) = parse_amount(&args[1..], line)?;
+                self.book.seen(&price);
+                self.book.prices.push((date, commodity.clone(), price));
+                Ok(Block::Other(format!("price of {}", commodity)))
+            }
+            "custom" => self.custom(date, args, line),
+            "note" | "document" | "event" | "query" => {
+                self.book.notes.push(Note::dropped(
+                    Some(line),
+                    format!("{} on {} dropped", keyword, date),
+                ));
+                Ok(Block::Other(format!("{} on {}", keyword, date)))
+            }
+            flag if flag == "txn" || flag.chars().count() == 1 || flag.starts_with('\'') => {
+                self.transaction(date, flag, args, line)?;
+                Ok(Block::Entry)
+            }
+            d => Err(error(line, format!("unsupported directive: {}", d))),
+        }
+    }
+
+    /// `custom "budget"` directives of fava, which set the budget of an
+    /// account from their date on
+    fn custom(&mut self, date: NaiveDate, args: &[Token], line: usize) -> Result<Block> {
+        let kind = args.first().map(text).unwrap_or("");
+        let budget = match (kind, args.get(1), args.get(2)) {
+            ("budget", Some(Token::Word(account)), Some(Token::Str(period))) => {
+                Some((account.clone(), period.clone()))
+            }
+            _ => None,
+        };
+        let (account, period) = match budget {
+            Some(budget) => budget,
+            None => {
+                self.book.notes.push(Note::dropped(
+                    Some(line),
+                    format!("custom {} directive on {} dropped", kind, date),
+                ));
+                return Ok(Block::Other(format!("custom {} directive", kind)));
+            }
+        };
+
+        let ((amount, _), _) = parse_amount(&args[3..], line)?;
+        self.book.declare(&account);
+        let (monthly, yearly) = self.book.budgets.entry(account.clone()).or_default();
+        let budget = match period.as_str() {
+            "monthly" => monthly,
+            "yearly" => yearly,
+            p => {
+                self.book.notes.push(Note::dropped(
+                    Some(line),
+                    format!("{} budget of {} dropped", p, account),
+                ));
+                return Ok(Block::Other(format!("budget of {}", account)));
+            }
+        };
+        if budget.is_some_and(|b| b != amount) {
+            self.book.notes.push(Note::approximated(
+                Some(line),
+                format!(
+                    "{} budget of {} changes on {}, the latest amount is kept for all dates",
+                    period, account, date
+                ),
+            ));
+        }
+        *budget = Some(amount);
+
+        Ok(Block::Other(format!("budget of {}", account)))
+    }
+
+    fn transaction(
+        &mut self,
+        date: NaiveDate,
+        flag: &str,
+        args: &[Token],
+        line: usize,
+    ) -> Result<()> {
+        let owner = format!("transaction on {}", date);
+        let mut entry = Entry::new(date, "");
+        entry.status = self.status(flag, line, &owner);
+
+        let strings: Vec<&str> = args
+            .iter()
+            .filter_map(|t| match t {
+                Token::Str(s) => Some(s.as_str()),
+                Token::Word(_) => None,
+            })
+            .collect();
+        match strings[..] {
+            [payee, narration] => {
+                entry.payee = Some(payee.to_string());
+                entry.description = narration.to_string();
+            }
+            [narration] => entry.description = narration.to_string(),
+            _ => {}
+        }
+
+        for token in args {
+            match token {
+                Token::Word(w) if w.starts_with('#') => entry.tags.push(w[1..].to_string()),
+                Token::Word(w) if w.starts_with('^') => {
+                    entry.tags.push(w.clone());
+                    self.book.notes.push(Note::approximated(
+                        Some(line),
+                        format!("link {} of {} kept as tag", w, owner),
+                    ));
+                }
+                Token::Word(w) => return Err(error(line, format!("unexpected {}", w))),
+                Token::Str(_) => {}
+            }
+        }
+        for tag in &self.tags {
+            if !entry.tags.contains(tag) {
+                entry.tags.push(tag.clone());
+            }
+        }
+        for (key, value) in &self.meta {
+            entry.meta.insert(key.clone(), value.clone());
+        }
+
+        self.book.entries.push(entry);
+        Ok(())
+    }
+
+    /// indented line following a transaction, which is a comment, metadata
+    /// or a posting
+    fn entry_line(&mut self, text: &str, line: usize) -> Result<()> {
+        if let Some(comment) = text.strip_prefix(';') {
+            let entry = self.book.entries.last_mut().unwrap();
+            match entry.postings.last_mut() {
+                Some(posting) => posting.comment.push(comment.trim().to_string()),
+                None => entry.comment.push(comment.trim().to_string()),
+            }
+            return Ok(());
+        }
+
+        let tokens = tokenize(text, line)?;
+        if let Some(key) = metadata_key(&tokens) {
+            let value = metadata_value(&tokens);
+            let entry = self.book.entries.last_mut().unwrap();
+            match entry.postings.last_mut() {
+                // metadata after the first posting belongs to the latest one
+                Some(posting) => match key {
+                    "comment" => posting.comment.push(value),
+                    "tags" => posting
+                        .tags
+                        .extend(value.split_whitespace().map(|t| t.to_string())),
+                    _ => {
+                        posting.meta.insert(key.to_string(), value);
+                    }
+                },
+                None => match key {
+                    "code" => entry.code = Some(value),
+                    "comment" => entry.comment.push(value),
+                    "external_id" => entry.external_id = Some(value),
+                    _ => {
+                        entry.meta.insert(key.to_string(), value);
+                    }
+                },
+            }
+            return Ok(());
+        }
+
+        let posting = self.posting(&tokens, line)?;
+        self.book.entries.last_mut().unwrap().postings.push(posting);
+        Ok(())
+    }
+
+    fn posting(&mut self, tokens: &[Token], line: usize) -> Result<EntryPosting> {
+        let mut rest = tokens;
+        let mut posting = EntryPosting {
+            line,
+            ..EntryPosting::default()
+        };
+
+        if let Some(Token::Word(flag)) = rest.first().filter(|t| !text(t).contains(':')) {
+            posting.status = self.status(flag, line, "posting");
+            rest = &rest[1..];
+        }
+        posting.account = match rest.first() {
+            Some(Token::Word(account)) if account.contains(':') => account.clone(),
+            _ => return Err(error(line, "invalid posting".to_string())),
+        };
+        rest = &rest[1..];
+
+        if rest
+            .first()
+            .is_some_and(|t| !text(t).starts_with(['{', '@']))
+        {
+            let (amount, taken) = parse_amount(rest, line)?;
+            self.book.seen(&amount);
+            posting.amount = Some(amount);
+            rest = &rest[taken..];
+        }
+        let units = posting.amount.clone();
+        let per_unit = |total: Decimal| -> Result<Decimal> {
+            match &units {
+                Some((value, _)) if !value.is_zero() => Ok((total / *value).abs()),
+                _ => Err(error(
+                    line,
+                    "total cost or price of a posting without amount".to_string(),
+                )),
+            }
+        };
+
+        let mut cost = None;
+        if rest.first().is_some_and(|t| text(t).starts_with('{')) {
+            let end = rest
+                .iter()
+                .position(|t| text(t).ends_with('}'))
+                .ok_or_else(|| error(line, "unterminated cost".to_string()))?;
+            let spec: Vec<String> = rest[..=end]
+                .iter()
+                .map(|t| match t {
+                    Token::Word(w) => w.clone(),
+                    Token::Str(s) => format!("\"{}\"", s),
+                })
+                .collect();
+            cost = Some(self.cost(&spec.join(" "), &per_unit, line)?);
+            rest = &rest[end + 1..];
+        }
+
+        if let Some(Token::Word(at)) = rest.first().filter(|t| text(t).starts_with('@')) {
+            let ((price, currency), taken) = parse_amount(&rest[1..], line)?;
+            let price = match at.as_str() {
+                "@@" => per_unit(price)?,
+                _ => price,
+            };
+            posting.price = Some((price, currency));
+            rest = &rest[1 + taken..];
+        }
+
+        if let Some(token) = rest.first() {
+            return Err(error(line, format!("unexpected {}", text(token))));
+        }
+
+        let units = match &posting.amount {
+            Some((value, code)) => format!("{} {}", value, code),
+            None => String::new(),
+        };
+        match cost {
+            Some((Some(cost), spec)) => {
+                posting.cost = Some(cost);
+                self.book.notes.push(Note::approximated(
+                    Some(line),
+                    format!(
+                        "cost {} of {} in {} kept as a price, lots are not tracked",
+                        spec, units, posting.account
+                    ),
+                ));
+            }
+            Some((None, spec)) => {
+                let weighed = match posting.price {
+                    Some(_) => "weighed at its price",
+                    None => {
+                        posting.unknown_cost = true;
+                        "left out when balancing the transaction"
+                    }
+                };
+                self.book.notes.push(Note::approximated(
+                    Some(line),
+                    format!(
+                        "lot {} of {} in {} reduced at unknown cost, {}",
+                        spec, units, posting.account, weighed
+                    ),
+                ));
+            }
+            None => {}
+        }
+
+        Ok(posting)
+    }
+
+    /// cost of one unit of a posting, such as `{10.00 USD}`, `{{100.00 USD}}`
+    /// or `{10.00 # 9.95 USD, 2020-01-01, "lot"}`, along with the cost as
+    /// written. the cost of lots that are reduced, such as `{}`, is not
+    /// known
+    fn cost(
+        &mut self,
+        spec: &str,
+        per_unit: &dyn Fn(Decimal) -> Result<Decimal>,
+        line: usize,
+    ) -> Result<(Option<Amount>, String)> {
+        let total = spec.starts_with("{{");
+        let inner = spec.trim_matches(|c| c == '{' || c == '}');
+
+        let mut cost = None;
+        for part in inner.split(',').map(str::trim) {
+            let words: Vec<&str> = part.split_whitespace().collect();
+            let currency = match words.last() {
+                Some(c) if is_currency(c) => c.to_string(),
+                _ => continue,
+            };
+            let value = match &words[..words.len() - 1] {
+                [v] if total => per_unit(parse_number(v, line)?)?,
+                [v] => parse_number(v, line)?,
+                [v, "#", t] => parse_number(v, line)? + per_unit(parse_number(t, line)?)?,
+                ["#", t] => per_unit(parse_number(t, line)?)?,
+                _ => return Err(error(line, format!("invalid cost: {}", spec))),
+            };
+            cost = Some((value, currency));
+        }
+
+        Ok((cost, spec.to_string()))
+    }
+
+    /// metadata of a directive other than a transaction
+    fn directive_line(&mut self, block: &Block, text: &str, line: usize) -> Result<()> {
+        let tokens = tokenize(text, line)?;
+        let key = match metadata_key(&tokens) {
+            Some(key) => key,
+            None => return Err(error(line, "unexpected indented line".to_string())),
+        };
+
+        let owner = match block {
+            Block::Commodity(code) if key == "precision" => {
+                let value = metadata_value(&tokens);
+                let precision = value
+                    .parse()
+                    .map_err(|_| error(line, format!("invalid precision: {}", value)))?;
+                self.book.precisions.insert(code.clone(), precision);
+                return Ok(());
+            }
+            Block::Commodity(code) => format!("commodity {}", code),
+            Block::Other(owner) => owner.clone(),
+            Block::None | Block::Entry => {
+                return Err(error(line, "unexpected indented line".to_string()))
+            }
+        };
+        self.book.notes.push(Note::dropped(
+            Some(line),
+            format!("metadata {} of {} dropped", key, owner),
+        ));
+
+        Ok(())
+    }
+}
+
+/// parse a beancount file, along with notes on the features of the file
+/// that ledger files cannot represent. amounts are in the first operating
+/// currency of the file, or else `currency`, or else the currency of the
+/// first amount of the file. `balance` directives become balance
+/// assertions on the latest posting to their account before their date,
+/// `pad` directives become transactions, and `custom "budget"` directives
+/// become budgets
+pub fn parse(source: &str, currency: Option<&str>) -> Result<(LedgerFile, Vec<Note>)> {
+    let mut parser = Parser::default();
+    parser.parse(source)?;
+
+    parser.book.build(currency)
+}
+
+/// name of a ledger file account in beancount, where accounts start with
+/// one of the five account types and each segment starts with a capital
+/// letter or a digit, e.g. `asset:cash_checking` becomes
+/// `Assets:Cash-Checking`
+fn account_name(account: &str) -> String {
+    let mut segments: Vec<String> = account.split(':').map(segment_name).collect();
+    let root = match AccountType::of(account) {
+        Some(AccountType::Asset) => "Assets",
+        Some(AccountType::Liability) => "Liabilities",
+        Some(AccountType::Equity) => "Equity",
+        Some(AccountType::Income) => "Income",
+        Some(AccountType::Expense) => "Expenses",
+        // accounts of other types are kept below equity
+        None => {
+            segments.insert(0, "Equity".to_string());
+            return segments.join(":");
+        }
+    };
+
+    segments[0] = root.to_string();
+    segments.join(":")
+}
+
+fn segment_name(segment: &str) -> String {
+    let words: Vec<String> = segment
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    match words.join("-") {
+        name if name.starts_with(|c: char| c.is_uppercase() || c.is_ascii_digit()) => name,
+        name => format!("X{}", name),
+    }
+}
+
+/// `value` quoted as a beancount string
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// beancount file being written
+struct Writer<'a> {
+    ledger_file: &'a LedgerFile,
+    commodities: Vec<Commodity>,
+    /// beancount names of the accounts of the ledger file
+    accounts: BTreeMap<String, String>,
+    out: String,
+    notes: Vec<Note>,
+}
+
+/// posting of a transaction being written
+struct Line {
+    account: String,
+    amount: Decimal,
+    currency: String,
+    assert_balance: Option<Decimal>,
+    status: Option<Status>,
+    comment: Option<String>,
+    tags: Vec<String>,
+    meta: Meta,
+}
+
+impl<'a> Writer<'a> {
+    fn account(&mut self, account: &str) -> String {
+        if let Some(name) = self.accounts.get(account) {
+            return name.clone();
+        }
+
+        let mut name = account_name(account);
+        let base = name.clone();
+        for i in 2.. {
+            if !self.accounts.values().any(|n| *n == name) {
+                break;
+            }
+            name = format!("{}-{}", base, i);
+        }
+        if name != account {
+            self.notes.push(Note::approximated(
+                None,
+                format!("account {} renamed to {}", account, name),
+            ));
+        }
+
+        self.accounts.insert(account.to_string(), name.clone());
+        name
+    }
+
+    fn amount(&self, value: Decimal, code: &str) -> String {
+        format_amount(value, code, &self.commodities)
+    }
+
+    fn status(status: &Option<Status>) -> &'static str {
+        match status {
+            Some(Status::Cleared) => "*",
+            Some(Status::Pending) => "!",
+            None => "txn",
+        }
+    }
+
+    /// postings of a transaction in the currencies they are booked in
+    fn lines(&self, t: &Transaction) -> Vec<Line> {
+        let ledger_file = self.ledger_file;
+        match &t.transactions {
+            Some(list) => list
+                .iter()
+                .map(|p| {
+                    let currency = p.currency.clone().or_else(|| t.currency.clone());
+                    Line {
+                        account: p.account.clone(),
+                        amount: p.amount,
+                        currency: ledger_file.resolve_currency(&currency, &p.account),
+                        assert_balance: p.assert_balance,
+                        status: p.status,
+                        comment: p.comment.clone(),
+                        tags: p.tags.clone().unwrap_or_default(),
+                        meta: p.meta.clone().unwrap_or_default(),
+                    }
+                })
+                .collect(),
+            None => {
+                let account = t.account.clone().unwrap_or_default();
+                let currency = ledger_file.resolve_currency(&t.currency, &account);
+                let amount = t.amount.unwrap_or_default();
+                vec![
+                    Line {
+                        account,
+                        amount,
+                        currency: currency.clone(),
+                        assert_balance: t.assert_balance,
+                        status: None,
+                        comment: None,
+                        tags: Vec::new(),
+                        meta: Meta::new(),
+                    },
+                    Line {
+                        account: t.offset_account.clone().unwrap_or_default(),
+                        amount: -amount,
+                        currency,
+                        assert_balance: None,
+                        status: None,
+                        comment: None,
+                        tags: Vec::new(),
+                        meta: Meta::new(),
+                    },
+                ]
+            }
+        }
+    }
+
+    /// prices that balance transactions in two currencies, which beancount
+    /// requires and ledger files leave out. keyed by the index of the line
+    /// they are added to
+    fn prices(&mut self, t: &Transaction, lines: &[Line]) -> Vec<Option<String>> {
+        let mut totals: BTreeMap<&str, Decimal> = BTreeMap::new();
+        for line in lines {
+            *totals.entry(&line.currency).or_default() += line.amount;
+        }
+        totals.retain(|_, total| !total.is_zero());
+
+        let mut prices = vec![None; lines.len()];
+        let (priced, (currency, total)) = match totals.iter().collect::<Vec<_>>()[..] {
+            [] => return prices,
+            [(a, a_total), (b, b_total)] => match *b == self.ledger_file.currency {
+                true => (*a, (*b, *b_total / -*a_total)),
+                false => (*b, (*a, *a_total / -*b_total)),
+            },
+            _ => {
+                self.notes.push(Note::approximated(
+                    None,
+                    format!(
+                        "transaction on {} {} does not balance in beancount",
+                        t.date, t.description
+                    ),
+                ));
+                return prices;
+            }
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            if line.currency == priced {
+                let price = (line.amount * total).abs();
+                let price = match Unit::find(currency, &self.commodities) {
+                    Ok(unit) => price.round_dp(unit.exponent()),
+                    Err(_) => price,
+                };
+                prices[i] = Some(format!(" @@ {}", self.amount(price, currency)));
+            }
+        }
+        self.notes.push(Note::approximated(
+            None,
+            format!(
+                "prices in {} added to transaction on {} {} to balance {}",
+                currency, t.date, t.description, priced
+            ),
+        ));
+
+        prices
+    }
+
+    /// metadata key following the naming rules of beancount, which does not
+    /// clash with the `reserved` keys that stand for fields of ledger files
+    fn metadata_key(&mut self, key: &str, reserved: &[&str], of: &str) -> String {
+        let mut valid: String = key
+            .chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    true => c,
+                    false => '-',
+                },
+            )
+            .collect();
+        if !valid.starts_with(|c: char| c.is_ascii_lowercase()) || reserved.contains(&key) {
+            valid = format!("meta-{}", valid);
+        }
+        if valid != key {
+            self.notes.push(Note::approximated(
+                None,
+                format!("metadata {} of {} renamed to {}", key, of, valid),
+            ));
+        }
+        valid
+    }
+
+    fn transaction(&mut self, t: &Transaction) {
+        let mut header = format!("\n{} {}", t.date, Writer::status(&t.status));
+        if let Some(payee) = &t.payee {
+            header.push_str(&format!(" {}", quoted(payee)));
+        }
+        header.push_str(&format!(" {}", quoted(&t.description)));
+        for tag in t.tags.iter().flatten() {
+            let (prefix, name) = match tag.strip_prefix('^') {
+                Some(link) => ("^", link),
+                None => ("#", tag.as_str()),
+            };
+            let valid: String = name
+                .chars()
+                .map(|c| match c.is_alphanumeric() || "-_/.".contains(c) {
+                    true => c,
+                    false => '-',
+                })
+                .collect();
+            if valid != name {
+                self.notes.push(Note::approximated(
+                    None,
+                    format!("tag {} renamed to {}", tag, valid),
+                ));
+            }
+            header.push_str(&format!(" {}{}", prefix, valid));
+        }
+        self.out.push_str(&header);
+        self.out.push('\n');
+
+        let metadata = [
+            ("code", &t.code),
+            ("comment", &t.comment),
+            ("external_id", &t.external_id),
+        ];
+        for (key, value) in metadata {
+            if let Some(value) = value {
+                self.out
+                    .push_str(&format!("  {}: {}\n", key, quoted(value)));
+            }
+        }
+        let of = format!("transaction on {} {}", t.date, t.description);
+        for (key, value) in t.meta.iter().flatten() {
+            let key = self.metadata_key(key, &TRANSACTION_METADATA, &of);
+            self.out
+                .push_str(&format!("  {}: {}\n", key, quoted(value)));
+        }
+
+        let lines = self.lines(t);
+        let prices = self.prices(t, &lines);
+        let names: Vec<String> = lines
+            .iter()
+            .map(|l| match l.status {
+                Some(_) => format!("{} {}", Writer::status(&l.status), self.account(&l.account)),
+                None => self.account(&l.account),
+            })
+            .collect();
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+
+        for ((line, name), price) in lines.iter().zip(names).zip(prices) {
+            self.out.push_str(&format!(
+                "  {:<width$}  {}{}\n",
+                name,
+                self.amount(line.amount, &line.currency),
+                price.unwrap_or_default(),
+                width = width
+            ));
+            if let Some(comment) = &line.comment {
+                self.out
+                    .push_str(&format!("    comment: {}\n", quoted(comment)));
+            }
+            let of = format!("posting to {} on {}", line.account, t.date);
+            if !line.tags.is_empty() {
+                let tags: Vec<String> = line
+                    .tags
+                    .iter()
+                    .map(|tag| tag.split_whitespace().collect::<Vec<&str>>().join("-"))
+                    .collect();
+                self.out
+                    .push_str(&format!("    tags: {}\n", quoted(&tags.join(" "))));
+            }
+            for (key, value) in &line.meta {
+                let key = self.metadata_key(key, &POSTING_METADATA, &of);
+                self.out
+                    .push_str(&format!("    {}: {}\n", key, quoted(value)));
+            }
+        }
+    }
+}
+
+/// serialize a ledger file into a beancount file, along with notes on the
+/// features of the ledger file that beancount cannot represent. accounts
+/// are renamed to follow the naming rules of beancount, opening amounts of
+/// accounts become an `opening balances` transaction, budgets become
+/// `custom "budget"` directives of fava and balance assertions become
+/// `balance` directives on the following day, unless the account has later
+/// postings on the day of the assertion
+pub fn write(ledger_file: &LedgerFile) -> (String, Vec<Note>) {
+    let mut writer = Writer {
+        ledger_file,
+        commodities: ledger_file.commodities.clone().unwrap_or_default(),
+        accounts: BTreeMap::new(),
+        out: format!(
+            "option \"operating_currency\" {}\n",
+            quoted(&ledger_file.currency)
+        ),
+        notes: Vec::new(),
+    };
+
+    let opening = opening_balances(ledger_file, OPENING_BALANCES_ACCOUNT);
+    let mut transactions: Vec<Transaction> = opening
+        .clone()
+        .into_iter()
+        .chain(ledger_file.transactions.iter().cloned())
+        .collect();
+    transactions.sort_by_key(|t| t.date);
+
+    let prices = ledger_file.prices.clone().unwrap_or_default();
+    let first = transactions
+        .iter()
+        .map(|t| t.date)
+        .chain(prices.iter().map(|p| p.date))
+        .min()
+        .unwrap_or_else(|| chrono::Local::today().naive_local());
+
+    for c in writer.commodities.clone() {
+        writer.out.push_str(&format!(
+            "\n{} commodity {}\n  precision: {}\n",
+            first, c.commodity, c.precision
+        ));
+    }
+
+    // accounts are opened in the currency of their postings, as long as
+    // they only have postings in that currency
+    let postings = ledger_file.postings();
+    writer.out.push('\n');
+    let mut accounts: Vec<(String, Option<String>)> = ledger_file
+        .accounts
+        .iter()
+        .map(|a| (a.account.clone(), a.currency.clone()))
+        .collect();
+    if opening
+        .iter()
+        .flat_map(|t| t.transactions.iter().flatten())
+        .any(|p| p.account == OPENING_BALANCES_ACCOUNT)
+    {
+        accounts.push((OPENING_BALANCES_ACCOUNT.to_string(), None));
+    }
+    for (account, currency) in accounts {
+        let name = writer.account(&account);
+        let currency = currency.filter(|c| {
+            let single = postings
+                .iter()
+                .filter(|p| p.account == account)
+                .all(|p| p.currency == *c);
+            if !single {
+                writer.notes.push(Note::dropped(
+                    None,
+                    format!(
+                        "currency {} of {} dropped, the account has postings in other currencies",
+                        c, account
+                    ),
+                ));
+            }
+            single
+        });
+        match currency {
+            Some(c) => writer
+                .out
+                .push_str(&format!("{} open {} {}\n", first, name, c)),
+            None => writer.out.push_str(&format!("{} open {}\n", first, name)),
+        }
+    }
+
+    if !prices.is_empty() {
+        writer.out.push('\n');
+    }
+    for p in &prices {
+        writer.out.push_str(&format!(
+            "{} price {} {}\n",
+            p.date,
+            p.commodity,
+            writer.amount(p.price, &p.currency)
+        ));
+    }
+
+    let mut budgets = String::new();
+    for account in &ledger_file.accounts {
+        let currency = ledger_file.resolve_currency(&None, &account.account);
+        for (period, budget) in [
+            ("monthly", account.budget_month),
+            ("yearly", account.budget_year),
+        ] {
+            if let Some(amount) = budget {
+                budgets.push_str(&format!(
+                    "{} custom \"budget\" {} \"{}\" {}\n",
+                    first,
+                    writer.account(&account.account),
+                    period,
+                    writer.amount(amount, &currency)
+                ));
+            }
+        }
+    }
+    if !budgets.is_empty() {
+        writer.out.push('\n');
+        writer.out.push_str(&budgets);
+    }
+
+    // balances at the start of the following day, which would include
+    // the postings to the account later on the day of the assertion
+    let lines: Vec<(usize, NaiveDate, Line)> = transactions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, t)| writer.lines(t).into_iter().map(move |l| (i, t.date, l)))
+        .collect();
+    let mut balances: Vec<(usize, String)> = Vec::new();
+    for (i, (index, date, line)) in lines.iter().enumerate() {
+        let expected = match line.assert_balance {
+            Some(expected) => expected,
+            None => continue,
+        };
+        let later = lines[i + 1..]
+            .iter()
+            .take_while(|(_, d, _)| d == date)
+            .any(|(_, _, l)| l.account == line.account && l.currency == line.currency);
+        if later {
+            writer.notes.push(Note::dropped(
+                None,
+                format!(
+                    "balance assertion of {} on {} dropped, as the account has later postings that day",
+                    line.account, date
+                ),
+            ));
+            continue;
+        }
+
+        let balance = format!(
+            "{} balance {} {}\n",
+            *date + Duration::days(1),
+            writer.account(&line.account),
+            writer.amount(expected, &line.currency)
+        );
+        if !balances.iter().any(|(_, b)| *b == balance) {
+            balances.push((*index, balance));
+        }
+    }
+
+    for (i, t) in transactions.iter().enumerate() {
+        writer.transaction(t);
+        for (_, balance) in balances.iter().filter(|(index, _)| *index == i) {
+            writer.out.push_str(balance);
+        }
+    }
+
+    writer.notes.extend(rules_notes(ledger_file));
+    (writer.out, writer.notes)
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+const BEANCOUNT: &str = r#"option "title" "Personal"
+option "operating_currency" "USD"
+plugin "beancount.plugins.auto_accounts"
+
+2020-01-01 commodity VTI
+  precision: 4
+  name: "Vanguard Total Stock Market"
+
+2020-01-01 open Assets:Checking USD
+2020-01-01 open Assets:Brokerage VTI,USD "FIFO"
+2020-01-01 open Expenses:Food
+2020-01-01 open Equity:Opening-Balances
+2020-01-01 open Income:Gains
+
+2020-01-01 custom "budget" Expenses:Food "monthly" 400.00 USD
+
+2020-01-01 pad Assets:Checking Equity:Opening-Balances
+2020-01-02 balance Assets:Checking 1000.00 USD
+
+pushtag #personal
+2020-01-02 * "Whole Foods" "groceries" #food ^receipt-12
+  code: "1001"
+  external_id: "FITID-1"
+  location: "Berlin"
+  Expenses:Food  45.10 USD
+    comment: "weekly
+shopping"
+  ! Assets:Checking
+poptag #personal
+
+2020-01-05 price VTI 150.25 USD
+2020-01-05 event "location" "Berlin"
+
+2020-01-05 txn "buy"
+  Assets:Brokerage  2 VTI {150.00 USD, 2020-01-05}
+  Assets:Checking  -300.00 USD
+
+2020-01-10 txn "sell"
+  Assets:Brokerage  -1 VTI {} @ 160.00 USD
+  Assets:Checking  160.00 USD
+  Income:Gains
+
+2020-01-11 balance Assets:Checking 814.90 USD ~ 0.01
+2020-02-01 close Income:Gains
+"#;
+
+#[test]
+fn parse_beancount() {
+    let (file, notes) = parse(BEANCOUNT, None).unwrap();
+
+    assert_eq!(file.currency, "USD");
+    assert_eq!(
+        file.commodities,
+        Some(vec![Commodity {
+            commodity: "VTI".to_string(),
+            precision: 4
+        }])
+    );
+    assert_eq!(file.accounts[0].currency, None);
+    assert_eq!(file.accounts[2].budget_month, Some(dec!(400.00)));
+
+    let padding = &file.transactions[0];
+    assert_eq!(padding.description, "padding");
+    assert_eq!(padding.amount, Some(dec!(1000.00)));
+    assert_eq!(padding.assert_balance, Some(dec!(1000.00)));
+
+    let groceries = &file.transactions[1];
+    assert_eq!(groceries.status, Some(Status::Cleared));
+    assert_eq!(groceries.payee.as_deref(), Some("Whole Foods"));
+    assert_eq!(groceries.code.as_deref(), Some("1001"));
+    assert_eq!(groceries.external_id.as_deref(), Some("FITID-1"));
+    assert_eq!(
+        groceries.tags,
+        Some(vec![
+            "food".to_string(),
+            "^receipt-12".to_string(),
+            "personal".to_string()
+        ])
+    );
+    assert_eq!(
+        groceries.meta.as_ref().unwrap()["location"],
+        "Berlin".to_string()
+    );
+    let postings = groceries.transactions.clone().unwrap();
+    assert_eq!(postings[0].comment.as_deref(), Some("weekly\nshopping"));
+    assert_eq!(postings[1].status, Some(Status::Pending));
+    assert_eq!(postings[1].amount, dec!(-45.10));
+
+    let prices = file.prices.clone().unwrap();
+    let prices: Vec<(&str, Decimal)> = prices
+        .iter()
+        .map(|p| (p.commodity.as_str(), p.price))
+        .collect();
+    assert_eq!(
+        prices,
+        vec![
+            ("VTI", dec!(150.25)),
+            ("VTI", dec!(150.00)),
+            ("VTI", dec!(160.00))
+        ]
+    );
+
+    // the sale is weighed at its price, so the gains posting is empty
+    let sell = file.transactions[3].transactions.clone().unwrap();
+    assert_eq!(sell[2].amount, dec!(0));
+    assert_eq!(sell[1].assert_balance, Some(dec!(814.90)));
+
+    let notes: Vec<String> = notes.iter().map(|n| n.to_string()).collect();
+    assert_eq!(
+        notes,
+        vec![
+            "line 1: option title dropped",
+            "line 3: plugin beancount.plugins.auto_accounts dropped",
+            "line 7: metadata name of commodity VTI dropped",
+            "line 10: currencies VTI, USD of Assets:Brokerage dropped, accounts have a single default currency",
+            "line 10: booking method FIFO of Assets:Brokerage dropped",
+            "line 21: link ^receipt-12 of transaction on 2020-01-02 kept as tag",
+            "line 32: event on 2020-01-05 dropped",
+            "line 35: cost {150.00 USD, 2020-01-05} of 2 VTI in Assets:Brokerage kept as a price, lots are not tracked",
+            "line 39: lot {} of -1 VTI in Assets:Brokerage reduced at unknown cost, weighed at its price",
+            "line 43: tolerance of the balance of Assets:Checking on 2020-01-11 dropped",
+            "line 44: close of Income:Gains on 2020-02-01 dropped",
+            "line 17: pad of Assets:Checking from Equity:Opening-Balances on 2020-01-01 replaced by a transaction of 1000.00 USD",
+        ]
+    );
+}
+
+#[test]
+fn parse_reports_unsupported_beancount_features() {
+    let err = parse("include \"2019.beancount\"\n", None).unwrap_err();
+    assert!(err.to_string().contains("include is not supported"));
+
+    let source = "2020-01-01 txn \"x\"\n  Assets:Cash  (10/3) USD\n  Expenses:Food\n";
+    let err = parse(source, None).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("arithmetic expressions are not supported"));
+}
+
+#[test]
+fn write_beancount() {
+    let file = LedgerFile::parse(
+        "currency: USD
+commodities:
+  - commodity: VTI
+    precision: 4
+accounts:
+  - account: asset:cash_checking
+    amount: 100
+  - account: asset:brokerage
+    amount: 0
+    currency: VTI
+  - account: equity:opening
+    amount: -100
+transactions:
+  - date: 2020-01-02
+    description: buy
+    status: pending
+    code: \"1001\"
+    tags: [invest, trip 2020]
+    transactions:
+      - account: asset:brokerage
+        amount: 2
+        comment: first lot
+      - account: asset:cash_checking
+        amount: -300.5
+        assert_balance: -200.5
+  - date: 2020-01-02
+    description: fee
+    account: asset:cash_checking
+    amount: -1
+    offset_account: equity:opening
+    assert_balance: -201.5
+",
+    )
+    .unwrap();
+
+    let (beancount, notes) = write(&file);
+    assert_eq!(
+        beancount,
+        r#"option "operating_currency" "USD"
+
+2020-01-02 commodity VTI
+  precision: 4
+
+2020-01-02 open Assets:Cash-Checking
+2020-01-02 open Assets:Brokerage VTI
+2020-01-02 open Equity:Opening
+
+2020-01-02 txn "opening balances"
+  Assets:Cash-Checking  100.00 USD
+  Equity:Opening        -100.00 USD
+
+2020-01-02 ! "buy" #invest #trip-2020
+  code: "1001"
+  Assets:Brokerage      2.0000 VTI @@ 300.50 USD
+    comment: "first lot"
+  Assets:Cash-Checking  -300.50 USD
+
+2020-01-02 txn "fee"
+  Assets:Cash-Checking  -1.00 USD
+  Equity:Opening        1.00 USD
+2020-01-03 balance Assets:Cash-Checking -201.50 USD
+"#
+    );
+
+    let notes: Vec<String> = notes.iter().map(|n| n.to_string()).collect();
+    assert!(
+        notes.contains(&"account asset:cash_checking renamed to Assets:Cash-Checking".to_string())
+    );
+    assert!(notes.contains(&"tag trip 2020 renamed to trip-2020".to_string()));
+    assert!(notes.contains(
+        &"prices in USD added to transaction on 2020-01-02 buy to balance VTI".to_string()
+    ));
+    assert!(notes.contains(
+        &"balance assertion of asset:cash_checking on 2020-01-02 dropped, as the account has later postings that day"
+            .to_string()
+    ));
+}
+
+#[test]
+fn beancount_keeps_tags_and_metadata() {
+    let file = LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:cash_checking
+    amount: 0
+  - account: expense:home
+    amount: 0
+transactions:
+  - date: 2020-01-02
+    description: paint
+    meta:
+      project: house
+      Receipt No: 7
+    transactions:
+      - account: expense:home
+        amount: 20
+        tags: [reimbursable]
+        meta:
+          invoice: 14
+      - account: asset:cash_checking
+        amount: -20
+",
+    )
+    .unwrap();
+
+    let (beancount, notes) = write(&file);
+    assert!(beancount.contains(
+        r#"2020-01-02 txn "paint"
+  meta-Receipt-No: "7"
+  project: "house"
+  Expenses:Home         20.00 USD
+    tags: "reimbursable"
+    invoice: "14"
+"#
+    ));
+    let notes: Vec<String> = notes.iter().map(|n| n.to_string()).collect();
+    assert!(notes.contains(
+        &"metadata Receipt No of transaction on 2020-01-02 paint renamed to meta-Receipt-No"
+            .to_string()
+    ));
+
+    let (converted, _) = parse(&beancount, None).unwrap();
+    let paint = &converted.transactions[0];
+    assert_eq!(paint.meta.as_ref().unwrap()["project"], "house");
+    assert_eq!(paint.meta.as_ref().unwrap()["meta-Receipt-No"], "7");
+    let postings = paint.transactions.clone().unwrap();
+    assert_eq!(postings[0].tags, Some(vec!["reimbursable".to_string()]));
+    assert_eq!(postings[0].meta.as_ref().unwrap()["invoice"], "14");
+}
+
+#[test]
+fn beancount_round_trips() {
+    let file = LedgerFile::read("examples/example.yaml").unwrap();
+    let (converted, notes) = parse(&write(&file).0, None).unwrap();
+
+    assert!(notes.is_empty());
+    let accounts = |f: &LedgerFile| -> Vec<(Decimal, Option<Decimal>, Option<Decimal>)> {
+        f.accounts
+            .iter()
+            .map(|a| (a.amount, a.budget_month, a.budget_year))
+            .collect()
+    };
+    assert_eq!(accounts(&converted), accounts(&file));
+
+    let postings = |f: &LedgerFile| -> Vec<(NaiveDate, String, Decimal, Option<Decimal>)> {
+        f.postings()
+            .into_iter()
+            .map(|p| (p.date, account_name(&p.account), p.amount, p.assert_balance))
+            .collect()
+    };
+    let mut expected = postings(&file);
+    expected.sort_by_key(|p| p.0);
+    assert_eq!(postings(&converted), expected);
+}
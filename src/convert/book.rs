@@ -0,0 +1,564 @@
+use super::{Note, OPENING_BALANCES};
+use crate::commodity::{Commodity, Unit};
+use crate::error::{Diagnostic, Error, Location, Result};
+use crate::ledger::{Account, LedgerFile, Meta, Status, Transaction, TransactionList};
+use crate::price::Price;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// amount along with the commodity it is denominated in. amounts without
+/// a commodity have an empty one until the default currency is known
+pub(crate) type Amount = (Decimal, String);
+
+/// budget period of an account
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Budget {
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct EntryPosting {
+    pub line: usize,
+    pub account: String,
+    /// left out when the posting balances the transaction
+    pub amount: Option<Amount>,
+    /// price of one unit of `amount`, which is kept in the prices of the
+    /// ledger file
+    pub price: Option<Amount>,
+    /// cost of one unit of `amount`, which balances the transaction in
+    /// place of `price`
+    pub cost: Option<Amount>,
+    /// set for postings whose cost is not known, such as reductions of
+    /// lots held at cost. they are left out when balancing the transaction
+    pub unknown_cost: bool,
+    pub assertion: Option<Amount>,
+    pub status: Option<Status>,
+    pub comment: Vec<String>,
+    pub tags: Vec<String>,
+    pub meta: Meta,
+}
+
+/// transaction read from a plain text format
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    pub date: NaiveDate,
+    /// set for periodic transactions, whose postings are budgets
+    pub budget: Option<Budget>,
+    pub status: Option<Status>,
+    pub code: Option<String>,
+    pub description: String,
+    pub payee: Option<String>,
+    pub external_id: Option<String>,
+    pub tags: Vec<String>,
+    pub meta: Meta,
+    pub comment: Vec<String>,
+    pub postings: Vec<EntryPosting>,
+}
+
+/// balance of an account at the start of `date`
+pub(crate) struct Check {
+    pub line: usize,
+    pub date: NaiveDate,
+    pub account: String,
+    pub amount: Amount,
+}
+
+/// posting that makes up the difference between the balance of `account`
+/// and its next `Check`, offset against `source`
+pub(crate) struct Pad {
+    pub line: usize,
+    pub date: NaiveDate,
+    pub account: String,
+    pub source: String,
+    /// number of entries read before the pad
+    pub index: usize,
+}
+
+/// accounts, prices and transactions read from a plain text format, which
+/// are turned into a ledger file once the whole file has been read
+#[derive(Default)]
+pub(crate) struct Book {
+    /// currency set by the file itself
+    pub default_currency: Option<String>,
+    /// commodity of the first amount of the file
+    pub first_currency: Option<String>,
+    /// accounts in the order they were declared, with their currency
+    pub accounts: Vec<(String, Option<String>)>,
+    pub precisions: BTreeMap<String, u32>,
+    pub prices: Vec<(NaiveDate, String, Amount)>,
+    pub budgets: BTreeMap<String, (Option<Decimal>, Option<Decimal>)>,
+    pub entries: Vec<Entry>,
+    pub checks: Vec<Check>,
+    pub pads: Vec<Pad>,
+    pub notes: Vec<Note>,
+}
+
+/// error of the file being read, located at `line`
+pub(crate) fn error(line: usize, message: String) -> Error {
+    Error::Validation(vec![Diagnostic {
+        message,
+        path: String::new(),
+        location: Some(Location {
+            file: String::new(),
+            line,
+            column: 1,
+            source: String::new(),
+        }),
+    }])
+}
+
+pub(crate) fn joined(lines: &[String]) -> Option<String> {
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join("\n")),
+    }
+}
+
+impl Entry {
+    pub fn new(date: NaiveDate, description: &str) -> Entry {
+        Entry {
+            date,
+            budget: None,
+            status: None,
+            code: None,
+            description: description.to_string(),
+            payee: None,
+            external_id: None,
+            tags: Vec::new(),
+            meta: Meta::new(),
+            comment: Vec::new(),
+            postings: Vec::new(),
+        }
+    }
+}
+
+/// entry whose postings all have an amount in a known commodity
+type Resolved = (Entry, Vec<(EntryPosting, Amount)>);
+
+impl Book {
+    /// keep track of the commodity of the first amount of the file
+    pub fn seen(&mut self, (_, currency): &Amount) {
+        if self.first_currency.is_none() && !currency.is_empty() {
+            self.first_currency = Some(currency.clone());
+        }
+    }
+
+    pub fn declare(&mut self, account: &str) {
+        if !self.accounts.iter().any(|(a, _)| a == account) {
+            self.accounts.push((account.to_string(), None));
+        }
+    }
+
+    /// turn what was read into a ledger file. amounts without a commodity
+    /// are in the currency set by the file, or else `currency`, or else the
+    /// commodity of the first amount of the file. a transaction named
+    /// `opening balances` that precedes all other transactions becomes the
+    /// opening amounts of its accounts
+    pub fn build(mut self, currency: Option<&str>) -> Result<(LedgerFile, Vec<Note>)> {
+        let default_currency = self
+            .default_currency
+            .clone()
+            .or(currency.map(String::from))
+            .or(self.first_currency.clone())
+            .unwrap_or_else(|| "USD".to_string());
+        let resolve = |(value, code): Amount| match code.as_str() {
+            "" => (value, default_currency.clone()),
+            _ => (value, code),
+        };
+
+        let mut prices: Vec<Price> = Vec::new();
+        let mut add_price = |date: NaiveDate, commodity: String, (price, currency): Amount| {
+            let price = Price {
+                date,
+                commodity,
+                price,
+                currency,
+            };
+            if !prices.contains(&price) {
+                prices.push(price);
+            }
+        };
+        for (date, commodity, price) in std::mem::take(&mut self.prices) {
+            add_price(date, commodity, resolve(price));
+        }
+
+        // postings of every transaction with their amounts resolved, where a
+        // posting without amount receives one amount per commodity needed to
+        // balance the transaction
+        let mut entries: Vec<Resolved> = Vec::new();
+        for mut entry in std::mem::take(&mut self.entries) {
+            let postings = std::mem::take(&mut entry.postings);
+            if let Some(budget) = entry.budget {
+                for posting in postings {
+                    if let Some(amount) = posting.amount {
+                        self.declare(&posting.account);
+                        let (monthly, yearly) = self.budgets.entry(posting.account).or_default();
+                        let total = match budget {
+                            Budget::Monthly => monthly,
+                            Budget::Yearly => yearly,
+                        };
+                        *total = Some(total.unwrap_or_default() + amount.0);
+                    }
+                }
+                continue;
+            }
+
+            let mut totals: BTreeMap<String, Decimal> = BTreeMap::new();
+            let mut elided: Option<(usize, EntryPosting)> = None;
+            let mut resolved = Vec::new();
+            for mut posting in postings {
+                self.declare(&posting.account);
+                posting.assertion = posting.assertion.map(resolve);
+                match posting.amount.take().map(resolve) {
+                    Some((value, code)) => {
+                        let price = posting.price.take().or(posting.cost.clone()).map(resolve);
+                        if let Some(price) = price.clone() {
+                            add_price(entry.date, code.clone(), price);
+                        }
+                        let (weight, weight_code) = match posting.cost.take().map(resolve).or(price)
+                        {
+                            Some((price, currency)) => (value * price, currency),
+                            None => (value, code.clone()),
+                        };
+                        if !posting.unknown_cost {
+                            *totals.entry(weight_code).or_default() += weight;
+                        }
+                        resolved.push((posting, (value, code)));
+                    }
+                    None if elided.is_some() => {
+                        return Err(error(
+                            posting.line,
+                            "only one posting of a transaction may omit its amount".to_string(),
+                        ))
+                    }
+                    None => elided = Some((resolved.len(), posting)),
+                }
+            }
+
+            if let Some((index, posting)) = elided {
+                if let Some((p, _)) = resolved.iter().find(|(p, _)| p.unknown_cost) {
+                    return Err(error(
+                        p.line,
+                        "the amount of a posting cannot be inferred when the cost of another posting is unknown"
+                            .to_string(),
+                    ));
+                }
+
+                let mut missing: Vec<Amount> = totals
+                    .iter()
+                    .filter(|(_, total)| !total.is_zero())
+                    .map(|(code, total)| (-*total, code.clone()))
+                    .collect();
+                if missing.is_empty() {
+                    missing.push((Decimal::ZERO, default_currency.clone()));
+                }
+
+                // the elided posting keeps its place among the postings
+                for (i, amount) in missing.into_iter().enumerate() {
+                    let assertion = posting
+                        .assertion
+                        .clone()
+                        .filter(|(_, code)| *code == amount.1);
+                    let expanded = EntryPosting {
+                        line: posting.line,
+                        account: posting.account.clone(),
+                        status: posting.status,
+                        comment: posting.comment.clone(),
+                        assertion,
+                        ..EntryPosting::default()
+                    };
+                    resolved.insert(index + i, (expanded, amount));
+                }
+            }
+            entries.push((entry, resolved));
+        }
+
+        self.pad(&mut entries, &default_currency);
+        self.check(&mut entries, &default_currency);
+
+        // opening balances ahead of all other transactions are folded into
+        // the accounts
+        let mut openings: BTreeMap<String, Amount> = BTreeMap::new();
+        let first = entries.iter().map(|(e, _)| e.date).min();
+        if let Some(i) = entries.iter().position(|(e, postings)| {
+            e.description.eq_ignore_ascii_case(OPENING_BALANCES)
+                && Some(e.date) == first
+                && e.status.is_none()
+                && e.comment.is_empty()
+                && postings.iter().all(|(p, _)| {
+                    p.assertion.is_none()
+                        && p.status.is_none()
+                        && p.comment.is_empty()
+                        && postings
+                            .iter()
+                            .filter(|(o, _)| o.account == p.account)
+                            .count()
+                            == 1
+                })
+        }) {
+            let (_, postings) = entries.remove(i);
+            for (posting, amount) in postings {
+                openings.insert(posting.account, amount);
+            }
+        }
+
+        let mut ledger_file = LedgerFile {
+            include: None,
+            currency: default_currency.clone(),
+            commodities: None,
+            prices: None,
+            accounts: Vec::new(),
+            transactions: Vec::new(),
+            rules: None,
+            payee_aliases: None,
+        };
+
+        for (account, currency) in &self.accounts {
+            let opening = openings.get(account);
+            let currency = match (opening, currency) {
+                (Some((_, code)), _) if *code != default_currency => Some(code.clone()),
+                (_, currency) => currency.clone().filter(|c| *c != default_currency),
+            };
+            let (budget_month, budget_year) =
+                self.budgets.get(account).cloned().unwrap_or_default();
+
+            ledger_file.accounts.push(Account {
+                account: account.clone(),
+                amount: opening.map(|(value, _)| *value).unwrap_or_default(),
+                currency,
+                budget_month,
+                budget_year,
+            });
+        }
+
+        for (entry, postings) in entries {
+            let mut lists = Vec::new();
+            for (posting, (amount, code)) in postings {
+                let assert_balance = match posting.assertion {
+                    Some((value, c)) if c == code => Some(value),
+                    Some((_, c)) => {
+                        return Err(error(
+                            posting.line,
+                            format!("balance assertion in {} on posting in {}", c, code),
+                        ))
+                    }
+                    None => None,
+                };
+                let account_currency = ledger_file.resolve_currency(&None, &posting.account);
+
+                lists.push(TransactionList {
+                    account: posting.account,
+                    amount,
+                    currency: Some(code).filter(|c| *c != account_currency),
+                    assert_balance,
+                    status: posting.status,
+                    comment: joined(&posting.comment),
+                    tags: Some(posting.tags).filter(|t| !t.is_empty()),
+                    meta: Some(posting.meta).filter(|m| !m.is_empty()),
+                });
+            }
+
+            ledger_file.transactions.push(transaction(entry, lists));
+        }
+
+        // amounts of commodities that are not currencies may carry as many
+        // decimal places as they are written with
+        let mut amounts: Vec<Amount> = prices
+            .iter()
+            .map(|p| (p.price, p.currency.clone()))
+            .collect();
+        for posting in ledger_file.postings() {
+            amounts.push((posting.amount, posting.currency));
+        }
+        for (value, code) in amounts {
+            if Unit::find(&code, &[]).is_err() {
+                let precision = self.precisions.entry(code).or_default();
+                *precision = (*precision).max(value.scale());
+            }
+        }
+        let commodities: Vec<Commodity> = std::mem::take(&mut self.precisions)
+            .into_iter()
+            .filter(|(code, _)| Unit::find(code, &[]).is_err())
+            .map(|(commodity, precision)| Commodity {
+                commodity,
+                precision,
+            })
+            .collect();
+
+        ledger_file.commodities = Some(commodities).filter(|c| !c.is_empty());
+        ledger_file.prices = Some(prices).filter(|p| !p.is_empty());
+
+        Ok((ledger_file, self.notes))
+    }
+
+    /// replace pads by transactions of the amount needed to meet the next
+    /// check of their account
+    fn pad(&mut self, entries: &mut Vec<Resolved>, default_currency: &str) {
+        for (inserted, pad) in std::mem::take(&mut self.pads).into_iter().enumerate() {
+            let check = self
+                .checks
+                .iter()
+                .filter(|c| c.account == pad.account && c.date > pad.date)
+                .min_by_key(|c| c.date)
+                .map(|c| (c.date, c.amount.clone()));
+            let (date, (amount, mut code)) = match check {
+                Some(check) => check,
+                None => {
+                    self.notes.push(Note::dropped(
+                        Some(pad.line),
+                        format!(
+                            "pad of {} on {} dropped, no balance of the account follows it",
+                            pad.account, pad.date
+                        ),
+                    ));
+                    continue;
+                }
+            };
+
+            if code.is_empty() {
+                code = default_currency.to_string();
+            }
+            let balance: Decimal = entries
+                .iter()
+                .filter(|(e, _)| e.date < date)
+                .flat_map(|(_, postings)| postings)
+                .filter(|(p, (_, c))| p.account == pad.account && *c == code)
+                .map(|(_, (value, _))| *value)
+                .sum();
+            let difference = amount - balance;
+            if difference.is_zero() {
+                continue;
+            }
+
+            self.declare(&pad.source);
+            let posting = |account: &str, value: Decimal| {
+                let posting = EntryPosting {
+                    line: pad.line,
+                    account: account.to_string(),
+                    ..EntryPosting::default()
+                };
+                (posting, (value, code.clone()))
+            };
+            let mut entry = Entry::new(pad.date, "padding");
+            entry.comment.push(format!(
+                "inserted for the balance of {} on {}",
+                pad.account, date
+            ));
+            let postings = vec![
+                posting(&pad.account, difference),
+                posting(&pad.source, -difference),
+            ];
+            entries.insert((pad.index + inserted).min(entries.len()), (entry, postings));
+
+            self.notes.push(Note::approximated(
+                Some(pad.line),
+                format!(
+                    "pad of {} from {} on {} replaced by a transaction of {} {}",
+                    pad.account, pad.source, pad.date, difference, code
+                ),
+            ));
+        }
+    }
+
+    /// turn checks into balance assertions on the latest posting to their
+    /// account before their date
+    fn check(&mut self, entries: &mut [Resolved], default_currency: &str) {
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+        order.sort_by_key(|&i| entries[i].0.date);
+
+        let mut checks = std::mem::take(&mut self.checks);
+        checks.sort_by_key(|c| c.date);
+        for check in checks {
+            let amount = match check.amount.1.as_str() {
+                "" => (check.amount.0, default_currency.to_string()),
+                _ => check.amount.clone(),
+            };
+            let latest = order
+                .iter()
+                .rev()
+                .filter(|&&i| entries[i].0.date < check.date)
+                .find_map(|&i| {
+                    entries[i]
+                        .1
+                        .iter()
+                        .rposition(|(p, (_, c))| p.account == check.account && *c == amount.1)
+                        .map(|j| (i, j))
+                });
+
+            let dropped = match latest {
+                Some((i, j)) => {
+                    let posting = &mut entries[i].1[j].0;
+                    match &posting.assertion {
+                        None => {
+                            posting.assertion = Some(amount);
+                            continue;
+                        }
+                        Some(a) if *a == amount => continue,
+                        Some(_) => "the latest posting to the account asserts another balance",
+                    }
+                }
+                None => "there is no earlier posting to the account",
+            };
+            self.notes.push(Note::dropped(
+                Some(check.line),
+                format!(
+                    "balance of {} on {} dropped, {}",
+                    check.account, check.date, dropped
+                ),
+            ));
+        }
+    }
+}
+
+/// transaction of an entry. entries of two postings that offset each
+/// other without notes of their own become simplified transactions
+fn transaction(entry: Entry, mut lists: Vec<TransactionList>) -> Transaction {
+    let mut transaction = Transaction {
+        date: entry.date,
+        account: None,
+        amount: None,
+        currency: None,
+        description: entry.description,
+        offset_account: None,
+        assert_balance: None,
+        transactions: None,
+        external_id: entry.external_id,
+        payee: entry.payee,
+        tags: Some(entry.tags).filter(|t| !t.is_empty()),
+        meta: Some(entry.meta).filter(|m| !m.is_empty()),
+        status: entry.status,
+        code: entry.code,
+        comment: joined(&entry.comment),
+    };
+
+    let simple = match &lists[..] {
+        [first, second] => {
+            first.currency == second.currency
+                && first.amount == -second.amount
+                && second.assert_balance.is_none()
+                && [first, second].iter().all(|p| {
+                    p.status.is_none()
+                        && p.comment.is_none()
+                        && p.tags.is_none()
+                        && p.meta.is_none()
+                })
+        }
+        _ => false,
+    };
+
+    if simple {
+        let second = lists.pop().unwrap();
+        let first = lists.pop().unwrap();
+        transaction.account = Some(first.account);
+        transaction.amount = Some(first.amount);
+        transaction.currency = first.currency;
+        transaction.offset_account = Some(second.account);
+        transaction.assert_balance = first.assert_balance;
+    } else {
+        transaction.transactions = Some(lists);
+    }
+
+    transaction
+}
@@ -0,0 +1,1000 @@
+use super::book::{error, Amount, Book, Budget, Entry, EntryPosting};
+use super::{format_amount, opening_balances, rules_notes, Note};
+use crate::commodity::Commodity;
+use crate::error::Result;
+use crate::ledger::{Account, LedgerFile, Meta, Status, Transaction};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// currency symbols of journals and the ISO 4217 codes they stand for
+const SYMBOLS: [(&str, &str); 4] = [("$", "USD"), ("€", "EUR"), ("£", "GBP"), ("¥", "JPY")];
+
+/// account that opening balances which do not add up to zero are offset
+/// against
+const OPENING_BALANCES_ACCOUNT: &str = "equity:opening_balances";
+
+/// account that the periodic transactions of budgets are balanced against
+const BUDGET_ACCOUNT: &str = "equity:budget";
+
+/// directive that the indented lines following it belong to
+enum Block {
+    None,
+    Entry,
+    Account(String),
+    Commodity(String),
+    /// directives whose details are not kept, e.g. `payee`
+    Ignored,
+}
+
+#[derive(Default)]
+struct Parser {
+    book: Book,
+    year: Option<i32>,
+}
+
+/// text of a line up to its comment, along with the comment
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    match text.find(';') {
+        Some(i) => (text[..i].trim_end(), Some(text[i + 1..].trim())),
+        None => (text, None),
+    }
+}
+
+/// ISO 4217 code of a currency symbol, or the commodity itself
+fn commodity(symbol: &str) -> String {
+    let symbol = symbol.trim().trim_matches('"');
+    match SYMBOLS.iter().find(|(s, _)| *s == symbol) {
+        Some((_, code)) => code.to_string(),
+        None => symbol.to_string(),
+    }
+}
+
+/// amounts such as `$-1,000.00`, `-$5`, `10 VTI` or `EUR 20`
+fn parse_amount(text: &str, line: usize) -> Result<Amount> {
+    let invalid = || error(line, format!("invalid amount: {}", text.trim()));
+    let text = text.trim();
+    let (negative, rest) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, text),
+    };
+    let is_number = |c: char| c.is_ascii_digit() || c == '.' || c == ',';
+
+    let (number, symbol) = if rest.starts_with(is_number) {
+        let end = rest.find(|c: char| !is_number(c)).unwrap_or(rest.len());
+        (rest[..end].to_string(), rest[end..].trim())
+    } else {
+        let end = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.find('"').ok_or_else(invalid)? + 2,
+            None => rest
+                .find(|c: char| is_number(c) || c == '-' || c.is_whitespace())
+                .unwrap_or(rest.len()),
+        };
+        (rest[end..].trim().to_string(), &rest[..end])
+    };
+
+    let mut value = Decimal::from_str(&number.replace(',', "")).map_err(|_| invalid())?;
+    if negative {
+        value = -value;
+    }
+
+    Ok((value, commodity(symbol)))
+}
+
+/// dates are `YYYY-MM-DD`, `YYYY/MM/DD` or `YYYY.MM.DD`. the year may be
+/// left out when set by a `Y` or `year` directive
+fn parse_date(text: &str, year: Option<i32>, line: usize) -> Result<NaiveDate> {
+    let invalid = || error(line, format!("invalid date: {}", text));
+    if text.contains('=') {
+        return Err(error(line, "secondary dates are not supported".to_string()));
+    }
+
+    let parts: Vec<&str> = text.split(['-', '/', '.']).collect();
+    let (y, m, d) = match parts[..] {
+        [y, m, d] => (y.parse().map_err(|_| invalid())?, m, d),
+        [m, d] => (year.ok_or_else(invalid)?, m, d),
+        _ => return Err(invalid()),
+    };
+
+    NaiveDate::from_ymd_opt(
+        y,
+        m.parse().map_err(|_| invalid())?,
+        d.parse().map_err(|_| invalid())?,
+    )
+    .ok_or_else(invalid)
+}
+
+fn parse_status(text: &str) -> (Option<Status>, &str) {
+    match text.chars().next() {
+        Some('*') => (Some(Status::Cleared), text[1..].trim_start()),
+        Some('!') => (Some(Status::Pending), text[1..].trim_start()),
+        _ => (None, text),
+    }
+}
+
+fn parse_posting(text: &str, line: usize) -> Result<EntryPosting> {
+    let (text, comment) = split_comment(text);
+    let (status, text) = parse_status(text);
+
+    // the account name ends at two spaces or a tab
+    let end = [text.find("  "), text.find('\t')]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(text.len());
+    let account = text[..end].trim().to_string();
+    let rest = text[end..].trim();
+
+    if account.starts_with(['(', '[']) {
+        return Err(error(
+            line,
+            format!("virtual postings are not supported: {}", account),
+        ));
+    }
+    if rest.contains(['{', '(']) {
+        return Err(error(
+            line,
+            format!(
+                "lot annotations and value expressions are not supported: {}",
+                rest
+            ),
+        ));
+    }
+
+    let (rest, assertion) = match rest.split_once('=') {
+        Some((rest, assertion)) => (rest.trim(), Some(parse_amount(assertion, line)?)),
+        None => (rest, None),
+    };
+    let (amount, price) = match rest.split_once('@') {
+        Some((amount, price)) => (amount.trim(), Some(price)),
+        None => (rest, None),
+    };
+
+    let amount = match amount {
+        "" if price.is_some() || assertion.is_some() => {
+            return Err(error(
+                line,
+                "balance assignments and prices of postings without amount are not supported"
+                    .to_string(),
+            ))
+        }
+        "" => None,
+        a => Some(parse_amount(a, line)?),
+    };
+    let price = match (price, &amount) {
+        (Some(p), Some((quantity, _))) => Some(match p.strip_prefix('@') {
+            // `@@` gives the total price of the posting
+            Some(total) => {
+                let (total, currency) = parse_amount(total, line)?;
+                if quantity.is_zero() {
+                    return Err(error(line, "total price of zero amount".to_string()));
+                }
+                ((total / *quantity).abs(), currency)
+            }
+            None => parse_amount(p, line)?,
+        }),
+        _ => None,
+    };
+
+    let mut posting = EntryPosting {
+        line,
+        account,
+        amount,
+        price,
+        assertion,
+        status,
+        ..EntryPosting::default()
+    };
+    if let Some(c) = comment {
+        posting.note(c);
+    }
+
+    Ok(posting)
+}
+
+impl Parser {
+    fn header(&mut self, text: &str, line: usize) -> Result<()> {
+        let (text, comment) = split_comment(text);
+        let (date, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let date = parse_date(date, self.year, line)?;
+        let (status, rest) = parse_status(rest.trim());
+
+        let (code, description) = match rest.strip_prefix('(') {
+            Some(code) => match code.split_once(')') {
+                Some((code, description)) => (Some(code.to_string()), description.trim()),
+                None => (None, rest),
+            },
+            None => (None, rest),
+        };
+
+        let mut entry = Entry::new(date, description);
+        entry.status = status;
+        entry.code = code;
+        if let Some(c) = comment {
+            entry.note(c);
+        }
+        self.book.entries.push(entry);
+
+        Ok(())
+    }
+
+    fn periodic(&mut self, text: &str, line: usize) -> Result<()> {
+        let (period, _) = split_comment(text);
+        let budget = match period.trim().to_lowercase().as_str() {
+            "monthly" => Budget::Monthly,
+            "yearly" | "annually" => Budget::Yearly,
+            p => {
+                return Err(error(
+                    line,
+                    format!("periodic transactions other than monthly and yearly budgets are not supported: {}", p),
+                ))
+            }
+        };
+
+        let mut entry = Entry::new(NaiveDate::from_ymd(1970, 1, 1), "");
+        entry.budget = Some(budget);
+        self.book.entries.push(entry);
+
+        Ok(())
+    }
+
+    /// indented line following a transaction, which is either a comment or
+    /// a posting
+    fn entry_line(&mut self, text: &str, line: usize) -> Result<()> {
+        let posting = match text.strip_prefix(';') {
+            Some(_) => None,
+            None => Some(parse_posting(text, line)?),
+        };
+        if let Some(amount) = posting.as_ref().and_then(|p| p.amount.clone()) {
+            self.book.seen(&amount);
+        }
+
+        let entry = match self.book.entries.last_mut() {
+            Some(entry) => entry,
+            None => return Err(error(line, "posting outside of a transaction".to_string())),
+        };
+
+        match (posting, entry.postings.last_mut()) {
+            (Some(posting), _) => entry.postings.push(posting),
+            // comments after the first posting belong to the latest one
+            (None, Some(last)) => last.note(text[1..].trim()),
+            (None, None) => entry.note(text[1..].trim()),
+        }
+
+        Ok(())
+    }
+
+    fn directive(&mut self, text: &str, line: usize) -> Result<Block> {
+        let (text, _) = split_comment(text);
+        let (name, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args = args.trim();
+
+        match name {
+            "account" => {
+                self.book.declare(args);
+                Ok(Block::Account(args.to_string()))
+            }
+            "commodity" => {
+                // either `commodity VTI` or a sample amount such as
+                // `commodity 1,000.0000 VTI`
+                let code = match parse_amount(args, line) {
+                    Ok((value, code)) => {
+                        self.book.precisions.insert(code.clone(), value.scale());
+                        code
+                    }
+                    Err(_) => commodity(args),
+                };
+                Ok(Block::Commodity(code))
+            }
+            "P" => {
+                let mut parts = args.split_whitespace();
+                let date = parts.next().unwrap_or("");
+                let date = parse_date(date, self.year, line)?;
+                let mut rest: Vec<&str> = parts.collect();
+                // the time of day is optional
+                if rest.first().is_some_and(|t| t.contains(':')) {
+                    rest.remove(0);
+                }
+                let (symbol, price) = match rest.split_first() {
+                    Some((symbol, price)) => (commodity(symbol), price.join(" ")),
+                    None => return Err(error(line, format!("invalid price: {}", args))),
+                };
+                let price = parse_amount(&price, line)?;
+                self.book.seen(&price);
+                self.book.prices.push((date, symbol, price));
+                Ok(Block::None)
+            }
+            "D" => {
+                let (_, code) = parse_amount(args, line)?;
+                self.book.default_currency = Some(code);
+                Ok(Block::None)
+            }
+            "Y" | "year" => {
+                let year = args
+                    .parse()
+                    .map_err(|_| error(line, format!("invalid year: {}", args)))?;
+                self.year = Some(year);
+                Ok(Block::None)
+            }
+            "payee" | "tag" => Ok(Block::Ignored),
+            d => Err(error(line, format!("unsupported directive: {}", d))),
+        }
+    }
+
+    /// indented line following a directive
+    fn directive_line(&mut self, block: &Block, text: &str, line: usize) -> Result<()> {
+        match block {
+            Block::Account(account) => {
+                let currency = text
+                    .strip_prefix(';')
+                    .and_then(|c| c.trim().strip_prefix("currency:"));
+                if let Some(currency) = currency {
+                    if let Some(a) = self.book.accounts.iter_mut().find(|(a, _)| a == account) {
+                        a.1 = Some(commodity(currency));
+                    }
+                }
+                Ok(())
+            }
+            Block::Commodity(code) => {
+                if let Some(format) = text.strip_prefix("format") {
+                    let (value, _) = parse_amount(format, line)?;
+                    self.book.precisions.insert(code.clone(), value.scale());
+                }
+                Ok(())
+            }
+            Block::Ignored => Ok(()),
+            Block::None | Block::Entry => Err(error(line, "unexpected indented line".to_string())),
+        }
+    }
+
+    fn parse(&mut self, source: &str) -> Result<()> {
+        let mut block = Block::None;
+        let mut comment_block = false;
+
+        for (i, text) in source.lines().enumerate() {
+            let line = i + 1;
+            let text = text.trim_end();
+
+            if comment_block {
+                comment_block = !matches!(text.trim(), "end comment" | "end test");
+                continue;
+            }
+            if text.trim().is_empty() {
+                block = Block::None;
+                continue;
+            }
+
+            if text.starts_with([' ', '\t']) {
+                match block {
+                    Block::Entry => self.entry_line(text.trim(), line)?,
+                    ref b => self.directive_line(b, text.trim(), line)?,
+                }
+                continue;
+            }
+
+            block = match text.chars().next() {
+                Some(';' | '#' | '%' | '|' | '*') => Block::None,
+                Some(c) if c.is_ascii_digit() => {
+                    self.header(text, line)?;
+                    Block::Entry
+                }
+                Some('~') => {
+                    self.periodic(&text[1..], line)?;
+                    Block::Entry
+                }
+                Some('=') => {
+                    return Err(error(
+                        line,
+                        "automated transactions are not supported".to_string(),
+                    ))
+                }
+                _ if matches!(text, "comment" | "test") => {
+                    comment_block = true;
+                    Block::None
+                }
+                _ => self.directive(text, line)?,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// tags of a comment line, along with the rest of its text. ledger lists
+/// tags such as `:trip:food:`, while hledger tags are `name:` or
+/// `name: value`, separated by commas and possibly preceded by text, as in
+/// `paid in cash, trip:, project: alpha`
+fn parse_tags(text: &str) -> (Vec<String>, Option<String>) {
+    if text.len() > 2
+        && text.starts_with(':')
+        && text.ends_with(':')
+        && !text.contains(char::is_whitespace)
+    {
+        let tags = text
+            .trim_matches(':')
+            .split(':')
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect();
+        return (tags, None);
+    }
+
+    let mut tags = Vec::new();
+    let mut rest = Vec::new();
+    for part in text.split(',') {
+        // the name of a tag is the word right before its colon
+        let tag = part.split_once(':').and_then(|(before, value)| {
+            let name = before.rsplit(char::is_whitespace).next()?;
+            let prefix = before[..before.len() - name.len()].trim();
+            (!name.is_empty()).then(|| (prefix, name, value.trim()))
+        });
+
+        match tag {
+            Some((prefix, name, value)) => {
+                rest.extend(Some(prefix).filter(|p| !p.is_empty()));
+                tags.push(match value {
+                    "" => name.to_string(),
+                    value => format!("{}:{}", name, value),
+                });
+            }
+            None => rest.extend(Some(part.trim()).filter(|p| !p.is_empty())),
+        }
+    }
+
+    match (tags.is_empty(), rest.is_empty()) {
+        (true, _) => (tags, Some(text.to_string())),
+        (false, true) => (tags, None),
+        (false, false) => (tags, Some(rest.join(", "))),
+    }
+}
+
+impl Entry {
+    /// comment of a transaction. the `Payee` and `external_id` tags are
+    /// kept in their own fields
+    fn note(&mut self, text: &str) {
+        let metadata = text.split_once(':').map(|(k, v)| (k.trim(), v.trim()));
+
+        match metadata {
+            Some((key, value)) if key.eq_ignore_ascii_case("payee") => {
+                self.payee = Some(value.to_string())
+            }
+            Some(("external_id", value)) => self.external_id = Some(value.to_string()),
+            _ => {
+                let (tags, comment) = parse_tags(text);
+                self.tags.extend(tags);
+                self.comment.extend(comment);
+            }
+        }
+    }
+}
+
+impl EntryPosting {
+    /// comment of a posting
+    fn note(&mut self, text: &str) {
+        let (tags, comment) = parse_tags(text);
+        self.tags.extend(tags);
+        self.comment.extend(comment);
+    }
+}
+
+/// parse a ledger or hledger journal. amounts without a commodity are in
+/// the currency of the `D` directive, or else `currency`, or else the
+/// commodity of the first amount of the journal. transactions named
+/// `opening balances` that precede all other transactions become the
+/// opening amounts of their accounts, and monthly and yearly periodic
+/// transactions become budgets
+pub fn parse(source: &str, currency: Option<&str>) -> Result<LedgerFile> {
+    let mut parser = Parser::default();
+    parser.parse(source)?;
+
+    let (ledger_file, _) = parser.book.build(currency)?;
+    Ok(ledger_file)
+}
+
+fn status(status: &Option<Status>) -> &'static str {
+    match status {
+        Some(Status::Cleared) => "* ",
+        Some(Status::Pending) => "! ",
+        None => "",
+    }
+}
+
+/// comment lines of the tags and metadata of a transaction or posting,
+/// described by `of` in notes. journals have no metadata apart from tags,
+/// so metadata is written as `name: value` tags
+fn tag_lines(
+    tags: &Option<Vec<String>>,
+    meta: &Option<Meta>,
+    of: &str,
+    notes: &mut Vec<Note>,
+) -> Vec<String> {
+    let (valued, plain): (Vec<&str>, Vec<&str>) = tags
+        .iter()
+        .flatten()
+        .map(String::as_str)
+        .partition(|t| t.contains(':'));
+
+    let mut lines = Vec::new();
+    if !plain.is_empty() {
+        lines.push(format!(":{}:", plain.join(":")));
+    }
+    for (name, value) in valued.iter().filter_map(|t| t.split_once(':')) {
+        lines.push(format!("{}: {}", name, value));
+    }
+    for (key, value) in meta.iter().flatten() {
+        lines.push(format!("{}: {}", key, value));
+        notes.push(Note::approximated(
+            None,
+            format!("metadata {} of {} written as a tag", key, of),
+        ));
+    }
+
+    lines
+}
+
+/// posting lines of a transaction of the journal, aligning amounts
+struct Postings<'a> {
+    commodities: &'a [Commodity],
+    lines: Vec<(String, String, Option<String>)>,
+}
+
+impl<'a> Postings<'a> {
+    fn push(
+        &mut self,
+        account: String,
+        amount: Option<Amount>,
+        assertion: Option<Decimal>,
+        comment: &Option<String>,
+    ) {
+        let text = match amount {
+            Some((value, code)) => {
+                let mut text = format_amount(value, &code, self.commodities);
+                if let Some(a) = assertion {
+                    text = format!("{} = {}", text, format_amount(a, &code, self.commodities));
+                }
+                text
+            }
+            None => String::new(),
+        };
+        self.lines.push((account, text, comment.clone()));
+    }
+
+    fn write(self, journal: &mut String) {
+        let width = self
+            .lines
+            .iter()
+            .map(|(account, _, _)| account.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (account, amount, comment) in self.lines {
+            let line = format!("    {:<width$}  {}", account, amount, width = width);
+            journal.push_str(line.trim_end());
+            journal.push('\n');
+            for c in comment.iter().flat_map(|c| c.lines()) {
+                journal.push_str(&format!("    ; {}\n", c));
+            }
+        }
+    }
+}
+
+/// serialize a ledger file into a journal that ledger and hledger accept.
+/// opening amounts of accounts become an `opening balances` transaction
+/// and budgets become periodic transactions. categorization rules have no
+/// counterpart in journals and are left out, as the returned notes
+/// report
+pub fn write(ledger_file: &LedgerFile) -> (String, Vec<Note>) {
+    let commodities = ledger_file.commodities.clone().unwrap_or_default();
+    let postings = || Postings {
+        commodities: &commodities,
+        lines: Vec::new(),
+    };
+    let mut journal = format!(
+        "D {}\n",
+        format_amount(Decimal::ONE, &ledger_file.currency, &commodities)
+    );
+
+    for c in &commodities {
+        let mut sample = Decimal::from(1000);
+        sample.rescale(c.precision);
+        journal.push_str(&format!(
+            "\ncommodity {}\n    format {} {}\n",
+            c.commodity, sample, c.commodity
+        ));
+    }
+
+    journal.push('\n');
+    for account in &ledger_file.accounts {
+        journal.push_str(&format!("account {}\n", account.account));
+        if let Some(currency) = &account.currency {
+            journal.push_str(&format!("    ; currency: {}\n", currency));
+        }
+    }
+
+    let prices = ledger_file.prices.clone().unwrap_or_default();
+    if !prices.is_empty() {
+        journal.push('\n');
+    }
+    for p in &prices {
+        journal.push_str(&format!(
+            "P {} {} {}\n",
+            p.date,
+            p.commodity,
+            format_amount(p.price, &p.currency, &commodities)
+        ));
+    }
+
+    for period in [Budget::Monthly, Budget::Yearly] {
+        let budget = |a: &Account| match period {
+            Budget::Monthly => a.budget_month,
+            Budget::Yearly => a.budget_year,
+        };
+        let mut lines = postings();
+        for account in &ledger_file.accounts {
+            if let Some(amount) = budget(account) {
+                let currency = ledger_file.resolve_currency(&None, &account.account);
+                lines.push(
+                    account.account.clone(),
+                    Some((amount, currency)),
+                    None,
+                    &None,
+                );
+            }
+        }
+        if !lines.lines.is_empty() {
+            lines.push(BUDGET_ACCOUNT.to_string(), None, None, &None);
+            journal.push_str(&format!("\n~ {:?}\n", period));
+            lines.write(&mut journal);
+        }
+    }
+
+    let mut transactions: Vec<Transaction> =
+        opening_balances(ledger_file, OPENING_BALANCES_ACCOUNT)
+            .into_iter()
+            .chain(ledger_file.transactions.iter().cloned())
+            .collect();
+    transactions.sort_by_key(|t| t.date);
+
+    let mut changes = Vec::new();
+    for t in &transactions {
+        let code = match &t.code {
+            Some(code) => format!("({}) ", code),
+            None => String::new(),
+        };
+        journal.push_str(&format!(
+            "\n{} {}{}{}\n",
+            t.date,
+            status(&t.status),
+            code,
+            t.description
+        ));
+
+        let mut notes: Vec<String> = t
+            .comment
+            .iter()
+            .flat_map(|c| c.lines())
+            .map(String::from)
+            .collect();
+        if let Some(payee) = &t.payee {
+            notes.push(format!("Payee: {}", payee));
+        }
+        if let Some(id) = &t.external_id {
+            notes.push(format!("external_id: {}", id));
+        }
+        let of = format!("transaction on {} {}", t.date, t.description);
+        notes.extend(tag_lines(&t.tags, &t.meta, &of, &mut changes));
+        for note in notes {
+            journal.push_str(&format!("    ; {}\n", note));
+        }
+
+        let mut lines = postings();
+        match &t.transactions {
+            Some(list) => {
+                for p in list {
+                    let currency = p.currency.clone().or_else(|| t.currency.clone());
+                    let currency = ledger_file.resolve_currency(&currency, &p.account);
+                    let of = format!("posting to {} on {}", p.account, t.date);
+                    let comment: Vec<String> = p
+                        .comment
+                        .iter()
+                        .map(String::from)
+                        .chain(tag_lines(&p.tags, &p.meta, &of, &mut changes))
+                        .collect();
+                    lines.push(
+                        format!("{}{}", status(&p.status), p.account),
+                        Some((p.amount, currency)),
+                        p.assert_balance,
+                        &Some(comment.join("\n")).filter(|c| !c.is_empty()),
+                    );
+                }
+            }
+            None => {
+                let account = t.account.clone().unwrap_or_default();
+                let currency = ledger_file.resolve_currency(&t.currency, &account);
+                let amount = t.amount.unwrap_or_default();
+                lines.push(
+                    account,
+                    Some((amount, currency.clone())),
+                    t.assert_balance,
+                    &None,
+                );
+                lines.push(
+                    t.offset_account.clone().unwrap_or_default(),
+                    Some((-amount, currency)),
+                    None,
+                    &None,
+                );
+            }
+        }
+        lines.write(&mut journal);
+    }
+
+    changes.extend(rules_notes(ledger_file));
+    (journal, changes)
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+const JOURNAL: &str = "; personal finances
+D $1,000.00
+
+commodity VTI
+    format 1.0000 VTI
+
+account Assets:Checking
+account Expenses:Food
+    note groceries and restaurants
+
+P 2020/01/01 VTI $150.25
+
+~ Monthly
+    Expenses:Food  $500
+    Assets:Checking
+
+2020-01-01 opening balances
+    Assets:Checking  $1,000.00
+    Equity:Opening
+
+2020/01/02 * (1001) Grocery Store  ; weekly shopping
+    ; :food:
+    Expenses:Food        $45.10
+    Assets:Checking
+
+comment
+2020-01-03 not a transaction
+end comment
+
+2020-01-05 ! Broker
+    Assets:Brokerage     2 VTI @ $150.00
+    Assets:Checking     -$300 = $654.90
+    * Expenses:Fees       EUR 1  ; wire fee
+    Assets:Cash
+";
+
+#[test]
+fn parse_journal() {
+    let file = parse(JOURNAL, None).unwrap();
+
+    assert_eq!(file.currency, "USD");
+    assert_eq!(
+        file.commodities,
+        Some(vec![Commodity {
+            commodity: "VTI".to_string(),
+            precision: 4
+        }])
+    );
+    let accounts: Vec<(&str, Decimal)> = file
+        .accounts
+        .iter()
+        .map(|a| (a.account.as_str(), a.amount))
+        .collect();
+    assert_eq!(
+        accounts,
+        vec![
+            ("Assets:Checking", dec!(1000)),
+            ("Expenses:Food", dec!(0)),
+            ("Equity:Opening", dec!(-1000)),
+            ("Assets:Brokerage", dec!(0)),
+            ("Expenses:Fees", dec!(0)),
+            ("Assets:Cash", dec!(0)),
+        ]
+    );
+    assert_eq!(file.accounts[1].budget_month, Some(dec!(500)));
+
+    let prices = file.prices.clone().unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices[1].price, dec!(150.00));
+
+    let grocery = &file.transactions[0];
+    assert_eq!(grocery.status, Some(Status::Cleared));
+    assert_eq!(grocery.code.as_deref(), Some("1001"));
+    assert_eq!(grocery.description, "Grocery Store");
+    assert_eq!(grocery.comment.as_deref(), Some("weekly shopping"));
+    assert_eq!(grocery.tags, Some(vec!["food".to_string()]));
+    assert_eq!(grocery.account.as_deref(), Some("Expenses:Food"));
+    assert_eq!(grocery.amount, Some(dec!(45.10)));
+    assert_eq!(grocery.offset_account.as_deref(), Some("Assets:Checking"));
+
+    let broker = file.transactions[1].transactions.clone().unwrap();
+    assert_eq!(file.transactions[1].status, Some(Status::Pending));
+    assert_eq!(broker[0].currency.as_deref(), Some("VTI"));
+    assert_eq!(broker[1].assert_balance, Some(dec!(654.90)));
+    assert_eq!(broker[2].status, Some(Status::Cleared));
+    assert_eq!(broker[2].comment.as_deref(), Some("wire fee"));
+    // the elided posting balances both currencies
+    assert_eq!(
+        (broker[3].amount, broker[3].currency.as_deref()),
+        (dec!(-1), Some("EUR"))
+    );
+    assert_eq!(broker.len(), 4);
+}
+
+#[test]
+fn parse_reports_unsupported_features() {
+    let err = parse("2020-01-01 x\n    (Budget:Food)  $5\n", None).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("virtual postings are not supported"));
+
+    let err = parse("apply account Personal\n", None)
+        .unwrap_err()
+        .locate("ledger.journal", "apply account Personal\n");
+    assert!(err.to_string().contains("unsupported directive: apply"));
+    assert!(err.to_string().contains("ledger.journal:1:1"));
+}
+
+#[test]
+fn write_journal() {
+    let file = LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 100
+    budget_month: 5
+  - account: equity:opening
+    amount: -100
+  - account: expense:food
+    amount: 0
+transactions:
+  - date: 2020-01-02
+    description: grocery store
+    status: cleared
+    comment: weekly shopping
+    payee: Whole Foods
+    tags: [food]
+    account: expense:food
+    amount: 45.1
+    offset_account: asset:checking
+    assert_balance: 45.1
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        write(&file).0,
+        "D 1.00 USD
+
+account asset:checking
+account equity:opening
+account expense:food
+
+~ Monthly
+    asset:checking  5.00 USD
+    equity:budget
+
+2020-01-02 opening balances
+    asset:checking  100.00 USD
+    equity:opening  -100.00 USD
+
+2020-01-02 * grocery store
+    ; weekly shopping
+    ; Payee: Whole Foods
+    ; :food:
+    expense:food    45.10 USD = 45.10 USD
+    asset:checking  -45.10 USD
+"
+    );
+}
+
+#[test]
+fn journal_round_trips() {
+    let file = LedgerFile::read("examples/example.yaml").unwrap();
+    let converted = parse(&write(&file).0, None).unwrap();
+
+    assert_eq!(converted.accounts, file.accounts);
+    let postings = |f: &LedgerFile| -> Vec<(NaiveDate, String, Decimal, String)> {
+        f.postings()
+            .into_iter()
+            .map(|p| (p.date, p.account, p.amount, p.currency))
+            .collect()
+    };
+    let mut expected = postings(&file);
+    expected.sort_by_key(|p| p.0);
+    assert_eq!(postings(&converted), expected);
+}
+
+#[test]
+fn journal_keeps_tags_and_metadata() {
+    let file = LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:travel
+    amount: 0
+transactions:
+  - date: 2020-01-02
+    description: train
+    tags: [trip, project:berlin]
+    meta:
+      invoice: 14
+    transactions:
+      - account: expense:travel
+        amount: 80
+        comment: seat
+        tags: [reimbursable]
+      - account: asset:checking
+        amount: -80
+",
+    )
+    .unwrap();
+
+    let (journal, notes) = write(&file);
+    assert!(journal.contains(
+        "2020-01-02 train
+    ; :trip:
+    ; project: berlin
+    ; invoice: 14
+    expense:travel  80.00 USD
+    ; seat
+    ; :reimbursable:
+"
+    ));
+    let notes: Vec<String> = notes.iter().map(|n| n.to_string()).collect();
+    assert!(notes.contains(
+        &"metadata invoice of transaction on 2020-01-02 train written as a tag".to_string()
+    ));
+
+    let converted = parse(&journal, None).unwrap();
+    let train = &converted.transactions[0];
+    assert_eq!(
+        train.tags,
+        Some(vec![
+            "trip".to_string(),
+            "project:berlin".to_string(),
+            "invoice:14".to_string()
+        ])
+    );
+    let postings = train.transactions.clone().unwrap();
+    assert_eq!(postings[0].comment.as_deref(), Some("seat"));
+    assert_eq!(postings[0].tags, Some(vec!["reimbursable".to_string()]));
+}
+
+#[test]
+fn journal_parses_hledger_tags() {
+    let file = parse(
+        "2020-01-02 train  ; trip:, project: alpha
+    ; paid in cash, refund:
+    expenses:travel  80 USD  ; receipt: yes
+    assets:checking
+",
+        None,
+    )
+    .unwrap();
+
+    let train = &file.transactions[0];
+    assert_eq!(
+        train.tags,
+        Some(vec![
+            "trip".to_string(),
+            "project:alpha".to_string(),
+            "refund".to_string()
+        ])
+    );
+    assert_eq!(train.comment.as_deref(), Some("paid in cash"));
+    let postings = train.transactions.clone().unwrap();
+    assert_eq!(postings[0].tags, Some(vec!["receipt:yes".to_string()]));
+    assert_eq!(postings[0].comment, None);
+}
@@ -1,3 +1,4 @@
+use crate::source_map::SourceMap;
 use std::fmt;
 use std::io;
 use std::result;
@@ -11,18 +12,154 @@ pub enum Error {
     IO(io::Error),
     Csv(csv::Error),
     InvalidArg(String),
+    Yaml(Diagnostic),
+    Money(Diagnostic),
+    Validation(Vec<Diagnostic>),
+    MissingPrice(String),
+}
+
+/// position within a source file that a `Diagnostic` refers to, along
+/// with the text of that line for display
+#[derive(Debug, PartialEq, Clone)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub source: String,
+}
+
+/// error message that points to a node of a ledger file. `path` identifies
+/// the offending YAML node, e.g. `transactions[2].amount`, and is resolved
+/// into a `Location` once the source of the file is known
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub path: String,
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, path: &str) -> Diagnostic {
+        Diagnostic {
+            message,
+            path: path.to_string(),
+            location: None,
+        }
+    }
+
+    pub(crate) fn locate(&mut self, file: &str, source: &str, source_map: &SourceMap) {
+        let position = match &self.location {
+            Some(l) => Some((l.line, l.column)),
+            None => source_map.lookup(&self.path),
+        };
+
+        if let Some((line, column)) = position {
+            self.location = Some(Location {
+                file: file.to_string(),
+                line,
+                column,
+                source: source
+                    .lines()
+                    .nth(line.saturating_sub(1))
+                    .unwrap_or("")
+                    .to_string(),
+            });
+        }
+    }
+}
+
+impl Error {
+    /// point a diagnostic at the YAML node located at `path`
+    pub fn at(self, path: &str) -> Error {
+        match self {
+            Error::Money(d) => Error::Money(Diagnostic::new(d.message, path)),
+            Error::Yaml(d) => Error::Yaml(Diagnostic::new(d.message, path)),
+            err => err,
+        }
+    }
+
+    /// resolve the diagnostics of this error into locations within `file`
+    pub fn locate(self, file: &str, source: &str) -> Error {
+        let source_map = SourceMap::new(source);
+
+        self.map_diagnostics(|d| d.locate(file, source, &source_map))
+    }
+
+    /// apply `f` to every diagnostic of this error
+    pub(crate) fn map_diagnostics(self, mut f: impl FnMut(&mut Diagnostic)) -> Error {
+        match self {
+            Error::Yaml(mut d) => {
+                f(&mut d);
+                Error::Yaml(d)
+            }
+            Error::Money(mut d) => {
+                f(&mut d);
+                Error::Money(d)
+            }
+            Error::Validation(mut diagnostics) => {
+                for d in &mut diagnostics {
+                    f(d);
+                }
+                Error::Validation(diagnostics)
+            }
+            err => err,
+        }
+    }
+}
+
+/// renders the message followed by a snippet of the offending line, e.g.
+///
+/// ```text
+/// error: amount has more than 2 decimal places for USD: 2.505
+///   --> ledger.yaml:12:13
+///    |
+/// 12 |     amount: 2.505
+///    |             ^
+/// ```
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error: {}", self.message)?;
+
+        if let Some(l) = &self.location {
+            let gutter = " ".repeat(l.line.to_string().len());
+            write!(
+                f,
+                "\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}^",
+                gutter,
+                l.file,
+                l.line,
+                l.column,
+                gutter,
+                l.line,
+                l.source,
+                gutter,
+                " ".repeat(l.column.saturating_sub(1))
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::IO(ref err) => write!(f, "{}", err),
-            Error::Csv(ref err) => write!(f, "{}", err),
-            Error::InvalidArg(ref s) => write!(f, "{}", s),
+            Error::IO(ref err) => write!(f, "error: {}", err),
+            Error::Csv(ref err) => write!(f, "error: {}", err),
+            Error::InvalidArg(ref s) => write!(f, "error: {}", s),
+            Error::Yaml(ref d) => write!(f, "{}", d),
+            Error::Money(ref d) => write!(f, "{}", d),
+            Error::Validation(ref diagnostics) => {
+                let rendered: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+                write!(f, "{}", rendered.join("\n\n"))
+            }
+            Error::MissingPrice(ref s) => write!(f, "error: {}", s),
         }
     }
 }
 
+impl std::error::Error for Error {}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         Error::IO(err)
@@ -40,3 +177,60 @@ impl From<String> for Error {
         Error::InvalidArg(err)
     }
 }
+
+/// serde_yaml appends the position to its messages, which is
+/// shown separately in the rendered diagnostic instead
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Error {
+        let message = err.to_string();
+        let message = match (err.location(), message.rfind(" at line ")) {
+            (Some(_), Some(i)) => message[..i].to_string(),
+            _ => message,
+        };
+
+        Error::Yaml(Diagnostic {
+            message,
+            path: String::new(),
+            location: err.location().map(|l| Location {
+                file: String::new(),
+                line: l.line(),
+                column: l.column(),
+                source: String::new(),
+            }),
+        })
+    }
+}
+
+#[test]
+fn render_located_diagnostic() {
+    let source = "currency: USD\naccounts:\n  - account: asset:cash\n    amount: 2.505\n";
+    let err = Error::Money(Diagnostic::new(
+        "amount has more than 2 decimal places for USD: 2.505".to_string(),
+        "accounts[0].amount",
+    ))
+    .locate("ledger.yaml", source);
+
+    assert_eq!(
+        err.to_string(),
+        "error: amount has more than 2 decimal places for USD: 2.505\n \
+         --> ledger.yaml:4:13\n  \
+         |\n\
+         4 |     amount: 2.505\n  \
+         |             ^"
+    );
+}
+
+#[test]
+fn yaml_errors_carry_location() {
+    let source = "currency: USD\naccounts: []\ntransactions:\n  - date: 2020-13-01\n";
+    let err = serde_yaml::from_str::<crate::ledger::LedgerFile>(source).unwrap_err();
+
+    match Error::from(err).locate("ledger.yaml", source) {
+        Error::Yaml(d) => {
+            let location = d.location.unwrap();
+            assert_eq!((location.line, location.file.as_str()), (4, "ledger.yaml"));
+            assert!(!d.message.contains(" at line "));
+        }
+        _ => panic!("expected yaml error"),
+    }
+}
@@ -0,0 +1,231 @@
+pub mod categorize;
+pub mod classify;
+pub mod csv;
+pub mod duplicate;
+pub mod merge;
+pub mod ofx;
+
+use crate::import::categorize::Categorization;
+use crate::import::classify::Suggestion;
+use crate::import::duplicate::{Check, Duplicates};
+use crate::ledger::{LedgerFile, Transaction};
+use crate::payee::Payees;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+/// transactions converted from a bank statement or export. transactions
+/// that were imported before are skipped
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Import {
+    pub transactions: Vec<Transaction>,
+    /// transactions booked to the fallback account for lack of a rule or
+    /// a confident suggestion, which need to be reviewed
+    pub uncategorized: Vec<Uncategorized>,
+    pub skipped: Vec<Duplicate>,
+    /// transactions that were imported even though they resemble a
+    /// transaction of the ledger file, which need to be reviewed
+    pub ambiguous: Vec<Duplicate>,
+    /// statement balances that could not be asserted, because the last
+    /// transaction up to their date was not imported
+    pub unasserted: Vec<Balance>,
+}
+
+/// imported transaction along with the transaction of the ledger file it
+/// resembles
+#[derive(Debug, PartialEq, Clone)]
+pub struct Duplicate {
+    pub transaction: Transaction,
+    pub existing: Transaction,
+}
+
+/// balance of an account as of a date, as stated by a bank statement
+#[derive(Debug, PartialEq, Clone)]
+pub struct Balance {
+    pub account: String,
+    pub date: NaiveDate,
+    pub balance: Decimal,
+}
+
+/// imported transaction that could not be categorized, along with the
+/// account the ledger history suggests for it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Uncategorized {
+    pub date: NaiveDate,
+    pub description: String,
+    pub amount: Decimal,
+    pub suggestion: Option<Suggestion>,
+}
+
+impl Import {
+    /// add an imported transaction unless it was imported before, keeping
+    /// track of it when it needs to be reviewed
+    fn add(
+        &mut self,
+        transaction: Transaction,
+        categorization: Categorization,
+        duplicates: &mut Duplicates,
+    ) {
+        match duplicates.check(&transaction) {
+            Check::New => (),
+            Check::Duplicate(existing) => {
+                self.skipped.push(Duplicate {
+                    transaction,
+                    existing: existing.clone(),
+                });
+                return;
+            }
+            Check::Ambiguous(existing) => self.ambiguous.push(Duplicate {
+                transaction: transaction.clone(),
+                existing: existing.clone(),
+            }),
+        }
+
+        if categorization.uncategorized {
+            self.uncategorized.push(Uncategorized {
+                date: transaction.date,
+                description: transaction.description.clone(),
+                amount: transaction.amount.unwrap_or(Decimal::ZERO),
+                suggestion: categorization.suggestion,
+            });
+        }
+        self.transactions.push(transaction);
+    }
+
+    /// accounts the imported transactions post to that `ledger_file` does
+    /// not declare, which is usually the fallback account
+    pub fn undeclared_accounts(&self, ledger_file: &LedgerFile) -> Vec<String> {
+        let mut undeclared: Vec<String> = Vec::new();
+        let accounts = self.transactions.iter().flat_map(|t| {
+            let postings = t.transactions.iter().flatten().map(|p| &p.account);
+            t.account.iter().chain(&t.offset_account).chain(postings)
+        });
+
+        for account in accounts {
+            if !ledger_file.accounts.iter().any(|a| &a.account == account)
+                && !undeclared.contains(account)
+            {
+                undeclared.push(account.clone());
+            }
+        }
+
+        undeclared
+    }
+}
+
+/// account that the first transaction with the same description, or with
+/// the same payee once payee aliases are applied, books against `account`,
+/// the account being imported into. detailed transactions only lend an
+/// account when they have two postings, one of which is to `account`
+pub(crate) fn history_account(
+    description: &str,
+    account: &str,
+    ledger_file: &LedgerFile,
+    payees: &Payees,
+) -> Option<String> {
+    let payee = payees.alias(description);
+
+    ledger_file
+        .transactions
+        .iter()
+        .filter(|t| t.description == description || payee.is_some_and(|p| payees.payee(t) == p))
+        .find_map(|t| {
+            let sides: Vec<&String> = match &t.transactions {
+                Some(postings)
+                    if postings.len() == 2 && postings.iter().any(|p| p.account == account) =>
+                {
+                    postings.iter().map(|p| &p.account).collect()
+                }
+                Some(_) => Vec::new(),
+                None => t.account.iter().chain(&t.offset_account).collect(),
+            };
+            sides
+                .into_iter()
+                .find(|a| !a.is_empty() && a.as_str() != account)
+                .cloned()
+        })
+}
+
+#[cfg(test)]
+fn get_file() -> LedgerFile {
+    LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:cash
+    amount: 100.00
+  - account: expense:foo
+    amount: 0
+  - account: expense:bar
+    amount: 0
+  - account: expense:baz
+    amount: 0
+transactions:
+  - date: 2020-01-01
+    description: summary_transaction
+    account: asset:cash
+    amount: 10.00
+    offset_account: expense:foo
+  - date: 2020-01-01
+    description: summary_transaction
+    account: asset:cash
+    amount: -42.00
+    offset_account: expense:foo
+  - date: 2020-01-02
+    description: coffee shop
+    account: expense:foo
+    amount: 2.50
+    offset_account: asset:cash
+  - date: 2020-01-01
+    description: detailed_transaction
+    transactions:
+      - account: asset:cash
+        amount: -50.00
+      - account: expense:bar
+        amount: 20.00
+      - account: expense:baz
+        amount: 30.00
+",
+    )
+    .unwrap()
+}
+
+#[test]
+fn undeclared_accounts_are_reported_once() {
+    let file = get_file();
+    let mut transaction = file.transactions[0].clone();
+    transaction.offset_account = Some("expense:uncategorized".to_string());
+    let import = Import {
+        transactions: vec![
+            transaction.clone(),
+            transaction,
+            file.transactions[3].clone(),
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        import.undeclared_accounts(&file),
+        vec!["expense:uncategorized"]
+    );
+}
+
+#[test]
+fn history_accounts_are_the_other_side_of_the_import_account() {
+    let file = get_file();
+    let payees = Payees::new(&file);
+    let history = |description, account| history_account(description, account, &file, &payees);
+
+    assert_eq!(
+        history("summary_transaction", "asset:cash").as_deref(),
+        Some("expense:foo")
+    );
+    assert_eq!(
+        history("summary_transaction", "asset:checking").as_deref(),
+        Some("asset:cash")
+    );
+    assert_eq!(
+        history("coffee shop", "asset:cash").as_deref(),
+        Some("expense:foo")
+    );
+    assert_eq!(history("detailed_transaction", "asset:cash"), None);
+    assert_eq!(history("tea", "asset:cash"), None);
+}
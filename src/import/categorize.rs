@@ -0,0 +1,502 @@
+use crate::error::{Error, Result};
+use crate::import::classify::{Classifier, Suggestion};
+use crate::import::history_account;
+use crate::ledger::{LedgerFile, Transaction};
+use crate::payee::Payees;
+use crate::query::Matcher;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// categorization rule for imported transactions, declared under the
+/// `rules` key of a ledger file. rules with a higher `priority` are
+/// evaluated first, rules of equal priority in the order of the file
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(rename = "match", default)]
+    pub conditions: RuleMatch,
+    pub set: RuleActions,
+}
+
+/// conditions that must all hold for a rule to apply. `payee` matches
+/// like a query term, i.e. plain text matches any part of the payee
+/// regardless of case and `/regex/` or `/regex/i` a regular expression.
+/// amounts and dates are inclusive bounds
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleMatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_amount: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub begin: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+/// fields a rule assigns to the transactions it matches
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleActions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+/// transaction to be categorized. `payee` is the name the bank gave the
+/// transaction, `account` the account it is imported into and `amount`
+/// the amount booked to the categorized account, i.e. positive for
+/// expenses
+#[derive(Debug, PartialEq, Clone)]
+pub struct Candidate<'a> {
+    pub date: NaiveDate,
+    pub payee: &'a str,
+    pub amount: Decimal,
+    pub account: &'a str,
+}
+
+/// value of a categorized field, along with the rule that assigned it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Assigned {
+    pub value: String,
+    pub rule: String,
+}
+
+/// outcome of categorizing a transaction. accounts not assigned by a
+/// rule are taken from a transaction of the ledger file with the same
+/// description, or else suggested by the history of the ledger file.
+/// `suggestion` is the account the history suggests, even when it was
+/// not confident enough to be used
+#[derive(Debug, PartialEq, Clone)]
+pub struct Categorization {
+    pub account: Assigned,
+    pub payee: Option<Assigned>,
+    pub description: Option<Assigned>,
+    pub tags: Vec<Assigned>,
+    pub suggestion: Option<Suggestion>,
+    pub uncategorized: bool,
+}
+
+/// account of transactions that neither rules nor the ledger history
+/// categorize with a confidence of at least `min_confidence`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fallback {
+    pub account: String,
+    pub min_confidence: f64,
+}
+
+impl Default for Fallback {
+    fn default() -> Fallback {
+        Fallback {
+            account: "expense:uncategorized".to_string(),
+            min_confidence: 0.5,
+        }
+    }
+}
+
+impl Fallback {
+    /// build a `Fallback` from command line arguments, which default to
+    /// `expense:uncategorized` and a confidence of 0.5
+    pub fn from_args(account: &str, min_confidence: &str) -> Result<Fallback> {
+        let mut fallback = Fallback::default();
+
+        if !account.is_empty() {
+            fallback.account = account.to_string();
+        }
+        if !min_confidence.is_empty() {
+            fallback.min_confidence = match min_confidence.parse::<f64>() {
+                Ok(c) if (0.0..=1.0).contains(&c) => c,
+                _ => {
+                    return Err(Error::InvalidArg(format!(
+                        "invalid confidence {}: expected a number between 0 and 1",
+                        min_confidence
+                    )))
+                }
+            };
+        }
+
+        Ok(fallback)
+    }
+}
+
+struct CompiledRule<'a> {
+    label: String,
+    priority: i32,
+    payee: Option<Matcher>,
+    rule: &'a Rule,
+}
+
+/// evaluates the rules of a ledger file against imported transactions
+pub struct Categorizer<'a> {
+    ledger_file: &'a LedgerFile,
+    rules: Vec<CompiledRule<'a>>,
+    payees: Payees,
+    classifier: Classifier,
+    fallback: Fallback,
+}
+
+impl Rule {
+    /// name of the rule, or its position within the `rules` key
+    pub fn label(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("rules[{}]", index),
+        }
+    }
+
+    pub(crate) fn payee_matcher(&self) -> Result<Option<Matcher>> {
+        self.conditions
+            .payee
+            .as_deref()
+            .map(Matcher::parse)
+            .transpose()
+    }
+}
+
+impl RuleMatch {
+    fn matches(&self, payee: &Option<Matcher>, candidate: &Candidate) -> bool {
+        let in_account = |a: &String| {
+            candidate.account == a || candidate.account.starts_with(&format!("{}:", a))
+        };
+
+        payee.as_ref().is_none_or(|m| m.matches(candidate.payee))
+            && self.min_amount.is_none_or(|min| candidate.amount >= min)
+            && self.max_amount.is_none_or(|max| candidate.amount <= max)
+            && self.begin.is_none_or(|begin| candidate.date >= begin)
+            && self.end.is_none_or(|end| candidate.date <= end)
+            && self.account.as_ref().is_none_or(in_account)
+    }
+}
+
+impl<'a> Categorizer<'a> {
+    pub fn new(ledger_file: &'a LedgerFile, fallback: Fallback) -> Result<Categorizer<'a>> {
+        let mut rules = Vec::new();
+
+        for (i, rule) in ledger_file.rules.iter().flatten().enumerate() {
+            rules.push(CompiledRule {
+                label: rule.label(i),
+                priority: rule.priority.unwrap_or(0),
+                payee: rule.payee_matcher().map_err(|e| {
+                    Error::InvalidArg(format!("invalid rule {}: {}", rule.label(i), e))
+                })?,
+                rule,
+            });
+        }
+        // sorting is stable, so rules of equal priority keep their order
+        rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
+
+        Ok(Categorizer {
+            ledger_file,
+            rules,
+            payees: Payees::new(ledger_file),
+            classifier: Classifier::new(ledger_file),
+            fallback,
+        })
+    }
+
+    /// every matching rule applies in priority order. a field assigned by a
+    /// rule is not overwritten by rules of lower priority, while tags are
+    /// collected from all matching rules. payees not assigned by a rule
+    /// are taken from the payee aliases
+    pub fn categorize(&self, candidate: &Candidate) -> Categorization {
+        let mut account: Option<Assigned> = None;
+        let mut payee: Option<Assigned> = None;
+        let mut description: Option<Assigned> = None;
+        let mut tags: Vec<Assigned> = Vec::new();
+
+        for compiled in &self.rules {
+            if !compiled.rule.conditions.matches(&compiled.payee, candidate) {
+                continue;
+            }
+
+            let actions = &compiled.rule.set;
+            let assign = |field: &mut Option<Assigned>, value: &Option<String>| {
+                if let (None, Some(value)) = (&field, value) {
+                    *field = Some(Assigned {
+                        value: value.clone(),
+                        rule: compiled.label.clone(),
+                    });
+                }
+            };
+            assign(&mut account, &actions.account);
+            assign(&mut payee, &actions.payee);
+            assign(&mut description, &actions.description);
+
+            for tag in actions.tags.iter().flatten() {
+                if !tags.iter().any(|t| &t.value == tag) {
+                    tags.push(Assigned {
+                        value: tag.clone(),
+                        rule: compiled.label.clone(),
+                    });
+                }
+            }
+        }
+
+        if payee.is_none() {
+            payee = self.payees.alias(candidate.payee).map(|value| Assigned {
+                value: value.to_string(),
+                rule: "payee_aliases".to_string(),
+            });
+        }
+
+        let suggestion = match account {
+            Some(_) => None,
+            None => self.classifier.suggest(candidate.payee),
+        };
+        let confident = suggestion
+            .as_ref()
+            .filter(|s| s.confidence >= self.fallback.min_confidence);
+
+        let mut uncategorized = false;
+        let account = match account {
+            Some(assigned) => assigned,
+            None => match (
+                history_account(
+                    candidate.payee,
+                    candidate.account,
+                    self.ledger_file,
+                    &self.payees,
+                ),
+                confident,
+            ) {
+                (Some(value), _) => Assigned {
+                    value,
+                    rule: "history".to_string(),
+                },
+                (None, Some(s)) => Assigned {
+                    value: s.account.clone(),
+                    rule: "learned".to_string(),
+                },
+                (None, None) => {
+                    uncategorized = true;
+                    Assigned {
+                        value: self.fallback.account.clone(),
+                        rule: "uncategorized".to_string(),
+                    }
+                }
+            },
+        };
+
+        Categorization {
+            account,
+            payee,
+            description,
+            tags,
+            suggestion,
+            uncategorized,
+        }
+    }
+}
+
+impl Categorization {
+    /// assign the payee, description and tags to an imported transaction.
+    /// the account is left to the caller, as it is the primary account of
+    /// some imports and the offset account of others
+    pub fn annotate(&self, transaction: &mut Transaction) {
+        if let Some(payee) = &self.payee {
+            transaction.payee = Some(payee.value.clone());
+        }
+        if let Some(description) = &self.description {
+            transaction.description = description.value.clone();
+        }
+        if !self.tags.is_empty() {
+            transaction.tags = Some(self.tags.iter().map(|t| t.value.clone()).collect());
+        }
+    }
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+fn get_file() -> LedgerFile {
+    LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: liability:amex
+    amount: 0
+  - account: expense:fuel
+    amount: 0
+  - account: expense:car
+    amount: 0
+  - account: expense:grocery
+    amount: 0
+transactions:
+  - date: 2020-01-01
+    description: weekly groceries
+    account: expense:grocery
+    amount: 10
+    offset_account: liability:amex
+rules:
+  - name: car
+    match:
+      payee: shell
+    set:
+      account: expense:car
+      tags: [car]
+  - name: fuel
+    priority: 10
+    match:
+      payee: /^SHELL OIL/
+      max_amount: 100
+      account: liability
+    set:
+      account: expense:fuel
+      payee: Shell
+      tags: [fuel, car]
+  - match:
+      begin: 2020-06-01
+      end: 2020-06-30
+    set:
+      description: summer trip
+",
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+fn candidate<'a>(payee: &'a str, amount: Decimal, account: &'a str) -> Candidate<'a> {
+    Candidate {
+        date: NaiveDate::from_ymd(2020, 1, 3),
+        payee,
+        amount,
+        account,
+    }
+}
+
+#[test]
+fn rules_apply_in_priority_order() {
+    let file = get_file();
+    let categorizer = Categorizer::new(&file, Fallback::default()).unwrap();
+    let result = categorizer.categorize(&candidate("SHELL OIL 123", dec!(45.10), "liability:amex"));
+
+    assert_eq!(result.account.value, "expense:fuel");
+    assert_eq!(result.account.rule, "fuel");
+    assert_eq!(result.payee.unwrap().value, "Shell");
+    assert_eq!(result.description, None);
+    let tags: Vec<&str> = result.tags.iter().map(|t| t.value.as_str()).collect();
+    assert_eq!(tags, vec!["fuel", "car"]);
+}
+
+#[test]
+fn rules_check_amount_date_and_account() {
+    let file = get_file();
+    let categorizer = Categorizer::new(&file, Fallback::default()).unwrap();
+
+    // too expensive and paid from the wrong account for the fuel rule
+    let result =
+        categorizer.categorize(&candidate("SHELL OIL 123", dec!(145.10), "liability:amex"));
+    assert_eq!(result.account.rule, "car");
+    let result = categorizer.categorize(&candidate("SHELL OIL 123", dec!(45.10), "asset:checking"));
+    assert_eq!(result.account.rule, "car");
+
+    let mut june = candidate("weekly groceries", dec!(10), "liability:amex");
+    june.date = NaiveDate::from_ymd(2020, 6, 10);
+    let result = categorizer.categorize(&june);
+    assert_eq!(result.description.unwrap().rule, "rules[2]");
+    assert_eq!(result.account.value, "expense:grocery");
+    assert_eq!(result.account.rule, "history");
+
+    let result = categorizer.categorize(&candidate("refund", dec!(-10), "liability:amex"));
+    assert_eq!(result.account.value, "expense:uncategorized");
+    assert!(result.uncategorized);
+}
+
+#[test]
+fn annotate_sets_payee_description_and_tags() {
+    let file = get_file();
+    let categorizer = Categorizer::new(&file, Fallback::default()).unwrap();
+    let mut transaction = file.transactions[0].clone();
+
+    categorizer
+        .categorize(&candidate("SHELL OIL 123", dec!(45.10), "liability:amex"))
+        .annotate(&mut transaction);
+
+    assert_eq!(transaction.payee.as_deref(), Some("Shell"));
+    assert_eq!(transaction.description, "weekly groceries");
+    assert_eq!(
+        transaction.tags,
+        Some(vec!["fuel".to_string(), "car".to_string()])
+    );
+}
+
+#[test]
+fn unknown_payees_are_learned_or_uncategorized() {
+    let file = get_file();
+    let categorizer = Categorizer::new(&file, Fallback::default()).unwrap();
+
+    let result = categorizer.categorize(&candidate("groceries", dec!(10), "liability:amex"));
+    assert_eq!(result.account.value, "expense:grocery");
+    assert_eq!(result.account.rule, "learned");
+    assert!(!result.uncategorized);
+
+    let fallback = Fallback::from_args("expense:review", "0.9").unwrap();
+    let categorizer = Categorizer::new(&file, fallback).unwrap();
+    let result = categorizer.categorize(&candidate("refund", dec!(-10), "liability:amex"));
+    assert_eq!(result.account.value, "expense:review");
+    assert_eq!(result.suggestion, None);
+
+    assert!(Fallback::from_args("", "1.5").is_err());
+}
+
+#[test]
+fn payee_aliases_assign_payees_and_history() {
+    let file = LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:shopping
+    amount: 0
+transactions:
+  - date: 2020-01-01
+    description: household goods
+    payee: Amazon
+    account: expense:shopping
+    amount: 25
+    offset_account: asset:checking
+rules:
+  - match:
+      payee: amzn prime
+    set:
+      payee: Prime Video
+payee_aliases:
+  - payee: Amazon
+    match: [/^AMZN/, amazon.com]
+",
+    )
+    .unwrap();
+    let categorizer = Categorizer::new(&file, Fallback::default()).unwrap();
+
+    let result = categorizer.categorize(&candidate("AMZN MKTP US*2K3", dec!(12), "asset:checking"));
+    assert_eq!(
+        result.payee,
+        Some(Assigned {
+            value: "Amazon".to_string(),
+            rule: "payee_aliases".to_string()
+        })
+    );
+    assert_eq!(result.account.value, "expense:shopping");
+    assert_eq!(result.account.rule, "history");
+
+    // payees assigned by rules take precedence over aliases
+    let result = categorizer.categorize(&candidate("AMZN Prime", dec!(9), "asset:checking"));
+    assert_eq!(result.payee.unwrap().value, "Prime Video");
+}
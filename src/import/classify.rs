@@ -0,0 +1,174 @@
+use crate::account_tree::AccountType;
+use crate::ledger::LedgerFile;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// account suggested by the history of the ledger file, along with the
+/// probability that it is right
+#[derive(Debug, PartialEq, Clone)]
+pub struct Suggestion {
+    pub account: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Default)]
+struct AccountTokens {
+    postings: usize,
+    tokens: usize,
+    counts: HashMap<String, usize>,
+}
+
+/// naive Bayes classifier over the descriptions of the income and expense
+/// postings of a ledger file
+#[derive(Debug, Default)]
+pub struct Classifier {
+    postings: usize,
+    vocabulary: HashSet<String>,
+    accounts: BTreeMap<String, AccountTokens>,
+}
+
+/// lowercase words of a description. numbers such as store or reference
+/// numbers differ from one transaction to the next, so they are dropped
+pub(crate) fn tokens(description: &str) -> Vec<String> {
+    description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() > 1 && !t.chars().all(|c| c.is_numeric()))
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+impl Classifier {
+    pub fn new(ledger_file: &LedgerFile) -> Classifier {
+        let mut classifier = Classifier::default();
+
+        for posting in ledger_file.postings() {
+            if !matches!(
+                AccountType::of(&posting.account),
+                Some(AccountType::Income) | Some(AccountType::Expense)
+            ) {
+                continue;
+            }
+
+            let account = classifier.accounts.entry(posting.account).or_default();
+            account.postings += 1;
+            for token in tokens(&posting.description) {
+                account.tokens += 1;
+                *account.counts.entry(token.clone()).or_default() += 1;
+                classifier.vocabulary.insert(token);
+            }
+            classifier.postings += 1;
+        }
+
+        classifier
+    }
+
+    /// most probable account for `description`. words that never occurred
+    /// in the ledger file carry no evidence, so descriptions without any
+    /// known word have no suggestion
+    pub fn suggest(&self, description: &str) -> Option<Suggestion> {
+        let known: Vec<String> = tokens(description)
+            .into_iter()
+            .filter(|t| self.vocabulary.contains(t))
+            .collect();
+        if known.is_empty() {
+            return None;
+        }
+
+        // log probabilities with laplace smoothing
+        let vocabulary = self.vocabulary.len() as f64;
+        let scores: Vec<(&String, f64)> = self
+            .accounts
+            .iter()
+            .map(|(name, account)| {
+                let prior = (account.postings as f64 / self.postings as f64).ln();
+                let likelihood: f64 = known
+                    .iter()
+                    .map(|t| {
+                        let count = account.counts.get(t).copied().unwrap_or(0) as f64;
+                        ((count + 1.0) / (account.tokens as f64 + vocabulary)).ln()
+                    })
+                    .sum();
+                (name, prior + likelihood)
+            })
+            .collect();
+
+        let (account, best) = scores.iter().max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let total: f64 = scores.iter().map(|(_, s)| (s - *best).exp()).sum();
+
+        Some(Suggestion {
+            account: account.to_string(),
+            confidence: 1.0 / total,
+        })
+    }
+}
+
+#[cfg(test)]
+fn get_file() -> LedgerFile {
+    LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:grocery
+    amount: 0
+  - account: expense:fuel
+    amount: 0
+  - account: income:salary
+    amount: 0
+transactions:
+  - date: 2020-01-01
+    description: WHOLE FOODS MARKET 1021
+    account: expense:grocery
+    amount: 50
+    offset_account: asset:checking
+  - date: 2020-01-08
+    description: WHOLE FOODS MARKET 1187
+    account: expense:grocery
+    amount: 60
+    offset_account: asset:checking
+  - date: 2020-01-09
+    description: TRADER JOES MARKET
+    account: expense:grocery
+    amount: 30
+    offset_account: asset:checking
+  - date: 2020-01-10
+    description: SHELL OIL 5521
+    account: expense:fuel
+    amount: 40
+    offset_account: asset:checking
+  - date: 2020-01-15
+    description: ACME CORP PAYROLL
+    transactions:
+      - account: asset:checking
+        amount: 1000
+      - account: income:salary
+        amount: -1000
+",
+    )
+    .unwrap()
+}
+
+#[test]
+fn tokens_drop_numbers_and_punctuation() {
+    assert_eq!(
+        tokens("WHOLE FOODS #1021, Austin-TX"),
+        vec!["whole", "foods", "austin", "tx"]
+    );
+}
+
+#[test]
+fn suggest_learns_from_descriptions() {
+    let classifier = Classifier::new(&get_file());
+
+    let suggestion = classifier.suggest("WHOLE FOODS MARKET 2245").unwrap();
+    assert_eq!(suggestion.account, "expense:grocery");
+    assert!(suggestion.confidence > 0.9);
+
+    let suggestion = classifier.suggest("ACME CORP PAYROLL JAN").unwrap();
+    assert_eq!(suggestion.account, "income:salary");
+
+    // market points to groceries and oil to fuel
+    let suggestion = classifier.suggest("MARKET OIL").unwrap();
+    assert!(suggestion.confidence < 0.9);
+
+    assert_eq!(classifier.suggest("unknown merchant 42"), None);
+}
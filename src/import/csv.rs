@@ -0,0 +1,477 @@
+use crate::error::{Diagnostic, Error, Result};
+use crate::import::categorize::{Candidate, Categorizer, Fallback};
+use crate::import::duplicate::Duplicates;
+use crate::import::Import;
+use crate::ledger::{LedgerFile, Transaction};
+use chrono::NaiveDate;
+use encoding_rs::Encoding;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// column of a csv file, referenced by its header name or by its zero
+/// based index
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    fn name(name: &str) -> Column {
+        Column::Name(name.to_string())
+    }
+
+    fn index(&self, headers: Option<&::csv::StringRecord>) -> Option<usize> {
+        match (self, headers) {
+            (Column::Index(i), _) => Some(*i),
+            (Column::Name(name), Some(headers)) => headers.iter().position(|h| h.trim() == name),
+            (Column::Name(_), None) => None,
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Column::Index(i) => write!(f, "{}", i),
+            Column::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// columns holding the fields of a transaction. a row takes its amount
+/// from the first of `amount`, `debit` and `credit` that is not empty
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Columns {
+    pub date: Column,
+    pub description: Column,
+    pub amount: Option<Column>,
+    pub debit: Option<Column>,
+    pub credit: Option<Column>,
+}
+
+impl Default for Columns {
+    fn default() -> Columns {
+        Columns {
+            date: Column::name("date"),
+            description: Column::name("name"),
+            amount: Some(Column::name("amount")),
+            debit: Some(Column::name("debit")),
+            credit: Some(Column::name("credit")),
+        }
+    }
+}
+
+/// sign convention of the `amount` column
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sign {
+    /// money leaving the offset account is positive
+    #[default]
+    OutflowPositive,
+    /// money entering the offset account is positive, as on most bank
+    /// statements
+    InflowPositive,
+}
+
+/// rules describing the csv export of a bank, read from a yaml file
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvRules {
+    pub columns: Columns,
+    pub date_format: String,
+    pub decimal_separator: char,
+    pub thousands_separator: Option<char>,
+    pub skip_rows: usize,
+    pub header: bool,
+    pub delimiter: char,
+    pub encoding: String,
+    pub sign: Sign,
+    pub offset_account: String,
+}
+
+impl Default for CsvRules {
+    fn default() -> CsvRules {
+        CsvRules {
+            columns: Columns::default(),
+            date_format: "%Y-%m-%d".to_string(),
+            decimal_separator: '.',
+            thousands_separator: None,
+            skip_rows: 0,
+            header: true,
+            delimiter: ',',
+            encoding: "utf-8".to_string(),
+            sign: Sign::default(),
+            offset_account: String::new(),
+        }
+    }
+}
+
+/// transaction of a csv file. the amount is booked to the matched account
+/// and offset by the account of the rules
+#[derive(Debug, PartialEq, Clone)]
+pub struct CsvTransaction {
+    pub date: NaiveDate,
+    pub description: String,
+    pub amount: Decimal,
+}
+
+struct ColumnIndices {
+    date: usize,
+    description: usize,
+    amount: Option<usize>,
+    debit: Option<usize>,
+    credit: Option<usize>,
+}
+
+impl CsvRules {
+    /// deserialize the rules file located at `filename`
+    pub fn read(filename: &str) -> Result<CsvRules> {
+        let source = std::fs::read_to_string(filename)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+
+        CsvRules::parse(&source).map_err(|e| e.locate(filename, &source))
+    }
+
+    /// deserialize and validate the contents of a rules file
+    pub fn parse(source: &str) -> Result<CsvRules> {
+        let rules: CsvRules = serde_yaml::from_str(source)?;
+
+        let invalid = |message: String, path: &str| Error::Yaml(Diagnostic::new(message, path));
+        if rules.offset_account.is_empty() {
+            return Err(invalid(
+                "csv rules must set an offset_account".to_string(),
+                "offset_account",
+            ));
+        }
+        if !rules.delimiter.is_ascii() {
+            return Err(invalid(
+                format!("delimiter must be an ascii character: {}", rules.delimiter),
+                "delimiter",
+            ));
+        }
+        if Encoding::for_label(rules.encoding.as_bytes()).is_none() {
+            return Err(invalid(
+                format!("unknown encoding: {}", rules.encoding),
+                "encoding",
+            ));
+        }
+
+        Ok(rules)
+    }
+
+    fn resolve(&self, headers: Option<&::csv::StringRecord>) -> Result<ColumnIndices> {
+        let required = |column: &Column| {
+            column
+                .index(headers)
+                .ok_or_else(|| Error::InvalidArg(format!("csv column not found: {}", column)))
+        };
+        let optional = |column: &Option<Column>| column.as_ref().and_then(|c| c.index(headers));
+
+        let columns = ColumnIndices {
+            date: required(&self.columns.date)?,
+            description: required(&self.columns.description)?,
+            amount: optional(&self.columns.amount),
+            debit: optional(&self.columns.debit),
+            credit: optional(&self.columns.credit),
+        };
+
+        if columns.amount.is_none() && columns.debit.is_none() && columns.credit.is_none() {
+            return Err(Error::InvalidArg(
+                "csv file must have an amount, debit or credit column".to_string(),
+            ));
+        }
+
+        Ok(columns)
+    }
+
+    /// amounts may use a decimal comma and separate thousands, e.g.
+    /// `1.234,56`. empty fields have no amount
+    fn parse_amount(&self, raw: &str) -> Option<std::result::Result<Decimal, ()>> {
+        let normalized: String = raw
+            .chars()
+            .filter(|c| !c.is_whitespace() && Some(*c) != self.thousands_separator)
+            .map(|c| if c == self.decimal_separator { '.' } else { c })
+            .collect();
+
+        match normalized.trim_start_matches('+') {
+            "" => None,
+            n => Some(Decimal::from_str(n).map_err(|_| ())),
+        }
+    }
+}
+
+/// parse the transactions of a csv file according to `rules`
+pub fn parse(bytes: &[u8], rules: &CsvRules) -> Result<Vec<CsvTransaction>> {
+    let encoding = Encoding::for_label(rules.encoding.as_bytes())
+        .ok_or_else(|| Error::InvalidArg(format!("unknown encoding: {}", rules.encoding)))?;
+    let (source, _, _) = encoding.decode(bytes);
+
+    // rows before the header often hold account details rather than csv
+    let body: String = source.split_inclusive('\n').skip(rules.skip_rows).collect();
+    let mut reader = ::csv::ReaderBuilder::new()
+        .has_headers(rules.header)
+        .delimiter(rules.delimiter as u8)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+
+    let headers = if rules.header {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let columns = rules.resolve(headers.as_ref())?;
+    let mut transactions = Vec::new();
+
+    for record in reader.records() {
+        let record = record?;
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+
+        let line = record.position().map_or(0, |p| p.line() as usize) + rules.skip_rows;
+        let field = |index: usize| record.get(index).unwrap_or("").trim();
+        let invalid =
+            |message: String| Error::InvalidArg(format!("csv line {}: {}", line, message));
+        let amount = |index: Option<usize>| match index.and_then(|i| rules.parse_amount(field(i))) {
+            Some(Ok(amount)) => Ok(Some(amount)),
+            Some(Err(())) => Err(invalid(format!("invalid amount {}", field(index.unwrap())))),
+            None => Ok(None),
+        };
+
+        let date = NaiveDate::parse_from_str(field(columns.date), &rules.date_format)
+            .map_err(|_| invalid(format!("invalid date {}", field(columns.date))))?;
+
+        let amount = if let Some(a) = amount(columns.amount)? {
+            match rules.sign {
+                Sign::OutflowPositive => a,
+                Sign::InflowPositive => -a,
+            }
+        } else if let Some(a) = amount(columns.debit)? {
+            a
+        } else if let Some(a) = amount(columns.credit)? {
+            -a
+        } else {
+            return Err(invalid("no amount, debit or credit".to_string()));
+        };
+
+        transactions.push(CsvTransaction {
+            date,
+            description: field(columns.description).to_string(),
+            amount,
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// convert the rows of a csv file into transactions of the account
+/// categorized for their description, offset by the account of the rules
+pub fn import(
+    bytes: &[u8],
+    rules: &CsvRules,
+    ledger_file: &LedgerFile,
+    fallback: Fallback,
+) -> Result<Import> {
+    if !ledger_file
+        .accounts
+        .iter()
+        .any(|a| a.account == rules.offset_account)
+    {
+        return Err(Error::InvalidArg(format!(
+            "unknown account: {}",
+            rules.offset_account
+        )));
+    }
+
+    let categorizer = Categorizer::new(ledger_file, fallback)?;
+    let mut duplicates = Duplicates::new(ledger_file);
+    let mut import = Import::default();
+
+    for t in parse(bytes, rules)? {
+        let categorization = categorizer.categorize(&Candidate {
+            date: t.date,
+            payee: &t.description,
+            amount: t.amount,
+            account: &rules.offset_account,
+        });
+
+        let mut transaction = Transaction {
+            date: t.date,
+            account: Some(categorization.account.value.clone()),
+            amount: Some(t.amount),
+            currency: None,
+            description: t.description,
+            offset_account: Some(rules.offset_account.clone()),
+            assert_balance: None,
+            transactions: None,
+            external_id: None,
+            payee: None,
+            tags: None,
+            meta: None,
+            status: None,
+            code: None,
+            comment: None,
+        };
+        categorization.annotate(&mut transaction);
+        import.add(transaction, categorization, &mut duplicates);
+    }
+
+    Ok(import)
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+fn get_rules(source: &str) -> CsvRules {
+    CsvRules::parse(source).unwrap()
+}
+
+/// negative `amount`s should be expressed as debits of the categorized account
+/// when inflows are positive
+#[test]
+fn negative_csv_amount_should_be_debit() {
+    let file = super::get_file();
+    let rules = get_rules("offset_account: asset:cash\nsign: inflow_positive\n");
+    let import = import(
+        b"date,name,amount\n2020-01-01, tea ,-2.50\n",
+        &rules,
+        &file,
+        Fallback::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        import.transactions[0],
+        Transaction {
+            date: NaiveDate::from_ymd(2020, 1, 1),
+            account: Some("expense:uncategorized".to_string()),
+            amount: Some(dec!(2.50)),
+            currency: None,
+            description: "tea".to_string(),
+            offset_account: Some("asset:cash".to_string()),
+            assert_balance: None,
+            transactions: None,
+            external_id: None,
+            payee: None,
+            tags: None,
+            meta: None,
+            status: None,
+            code: None,
+            comment: None,
+        }
+    );
+}
+
+/// positive `amount`s should be expressed as credits of the categorized account
+/// when inflows are positive
+#[test]
+fn positive_csv_amount_should_be_credit() {
+    let file = super::get_file();
+    let rules = get_rules("offset_account: asset:cash\nsign: inflow_positive\n");
+    let import = import(
+        b"date,name,amount\n2020-01-01,coffee,2.50\n2020-01-02,tea,2.50\n",
+        &rules,
+        &file,
+        Fallback::default(),
+    )
+    .unwrap();
+
+    // coffee is learned from the coffee shop of the ledger file
+    assert_eq!(
+        import.transactions[0].account.as_deref(),
+        Some("expense:foo")
+    );
+    assert_eq!(import.transactions[0].amount, Some(dec!(-2.50)));
+    assert_eq!(import.uncategorized.len(), 1);
+    assert_eq!(import.uncategorized[0].description, "tea");
+}
+
+/// optional debits and credits should be handled correctly
+#[test]
+fn should_handle_debits_and_credits() {
+    let rules = get_rules("offset_account: asset:cash\n");
+    let source =
+        b"date,name,amount,debit,credit\n2020-01-01,coffee,,2.50,\n2020-01-02,refund,,,2.50\n";
+    let transactions = parse(source, &rules).unwrap();
+
+    assert_eq!(transactions[0].amount, dec!(2.50));
+    assert_eq!(transactions[1].amount, dec!(-2.50));
+}
+
+/// columns may be referenced by index, and amounts and dates follow the
+/// conventions of the bank
+#[test]
+fn should_apply_bank_conventions() {
+    let rules = get_rules(
+        "columns:
+  date: 0
+  description: 2
+  amount: 3
+date_format: \"%d.%m.%Y\"
+decimal_separator: \",\"
+thousands_separator: \".\"
+delimiter: \";\"
+skip_rows: 2
+header: false
+sign: inflow_positive
+offset_account: asset:cash
+",
+    );
+    let source = "Konto;DE00 1234\n\n03.01.2020;02.01.2020;Miete;-1.234,50\n\n05.01.2020;05.01.2020;Gehalt;+2.000\n";
+    let transactions = parse(source.as_bytes(), &rules).unwrap();
+
+    assert_eq!(
+        transactions,
+        vec![
+            CsvTransaction {
+                date: NaiveDate::from_ymd(2020, 1, 3),
+                description: "Miete".to_string(),
+                amount: dec!(1234.50),
+            },
+            CsvTransaction {
+                date: NaiveDate::from_ymd(2020, 1, 5),
+                description: "Gehalt".to_string(),
+                amount: dec!(-2000),
+            },
+        ]
+    );
+}
+
+#[test]
+fn should_decode_legacy_encodings() {
+    let rules = get_rules("offset_account: asset:cash\nencoding: windows-1252\n");
+    let transactions = parse(b"date,name,amount\n2020-01-01,caf\xe9,2.50\n", &rules).unwrap();
+
+    assert_eq!(transactions[0].description, "café");
+}
+
+#[test]
+fn should_report_invalid_rows_and_rules() {
+    let rules = get_rules("offset_account: asset:cash\n");
+
+    let err = parse(
+        b"date,name,amount\n2020-01-01,coffee,2.50\n01/02/2020,tea,1\n",
+        &rules,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error: csv line 3: invalid date 01/02/2020"
+    );
+
+    let err = parse(b"date,name\n2020-01-01,coffee\n", &rules).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error: csv file must have an amount, debit or credit column"
+    );
+
+    assert!(CsvRules::parse("sign: inflow_positive\n").is_err());
+    assert!(CsvRules::parse("offset_account: asset:cash\nencoding: foo\n").is_err());
+    assert!(CsvRules::parse("offset_account: asset:cash\ninvert: true\n").is_err());
+}
@@ -0,0 +1,243 @@
+use crate::import::classify::tokens;
+use crate::ledger::{LedgerFile, Transaction};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+
+/// number of days a bank may post a transaction before or after the date
+/// it was recorded with in the ledger file
+const DATE_WINDOW: i64 = 3;
+
+/// share of description words two transactions have in common for them
+/// to be considered the same
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// outcome of comparing an imported transaction with the ledger file
+#[derive(Debug, PartialEq, Clone)]
+pub enum Check<'a> {
+    New,
+    /// the transaction was imported before
+    Duplicate(&'a Transaction),
+    /// a transaction of the ledger file has the same amount around the
+    /// same date, but a different description
+    Ambiguous(&'a Transaction),
+}
+
+struct Existing<'a> {
+    transaction: &'a Transaction,
+    postings: Vec<(String, Decimal)>,
+    words: HashSet<String>,
+    /// whether an imported transaction was found to duplicate it
+    used: bool,
+}
+
+/// index of the transactions of a ledger file that imports are checked
+/// against. each transaction is the duplicate of at most one imported
+/// transaction, so that repeated transactions are all imported
+pub struct Duplicates<'a> {
+    ledger_file: &'a LedgerFile,
+    existing: Vec<Existing<'a>>,
+}
+
+/// postings of a transaction as account and amount pairs
+fn postings(ledger_file: &LedgerFile, transaction: &Transaction) -> Vec<(String, Decimal)> {
+    ledger_file
+        .flatten_transaction(transaction.clone())
+        .into_iter()
+        .map(|p| (p.account.unwrap_or_default(), p.amount.unwrap_or_default()))
+        .collect()
+}
+
+/// share of words two descriptions have in common, ignoring case,
+/// punctuation and numbers
+fn similarity(words: &HashSet<String>, other: &HashSet<String>) -> f64 {
+    let union = words.union(other).count();
+    match union {
+        0 => 1.0,
+        _ => words.intersection(other).count() as f64 / union as f64,
+    }
+}
+
+impl<'a> Duplicates<'a> {
+    pub fn new(ledger_file: &'a LedgerFile) -> Duplicates<'a> {
+        let existing = ledger_file
+            .transactions
+            .iter()
+            .map(|transaction| Existing {
+                transaction,
+                postings: postings(ledger_file, transaction),
+                words: tokens(&transaction.description).into_iter().collect(),
+                used: false,
+            })
+            .collect();
+
+        Duplicates {
+            ledger_file,
+            existing,
+        }
+    }
+
+    /// transactions with the same external id are duplicates, while those
+    /// with different ones are not. without external ids, transactions
+    /// within a few days of each other that post the same amount to the
+    /// same account are duplicates when their descriptions are similar and
+    /// ambiguous otherwise. transactions that were already found to be
+    /// duplicated are only matched by their external id
+    pub fn check(&mut self, transaction: &Transaction) -> Check<'a> {
+        let imported = postings(self.ledger_file, transaction);
+        let words: HashSet<String> = tokens(&transaction.description).into_iter().collect();
+        let mut ambiguous = None;
+
+        for e in &mut self.existing {
+            match (&transaction.external_id, &e.transaction.external_id) {
+                (Some(id), Some(other)) if id == other => {
+                    e.used = true;
+                    return Check::Duplicate(e.transaction);
+                }
+                (Some(_), Some(_)) => continue,
+                _ => (),
+            }
+
+            let days = (transaction.date - e.transaction.date).num_days().abs();
+            if e.used || days > DATE_WINDOW || !imported.iter().any(|p| e.postings.contains(p)) {
+                continue;
+            }
+
+            if similarity(&words, &e.words) >= MIN_SIMILARITY {
+                e.used = true;
+                return Check::Duplicate(e.transaction);
+            }
+            ambiguous.get_or_insert(e.transaction);
+        }
+
+        match ambiguous {
+            Some(t) => Check::Ambiguous(t),
+            None => Check::New,
+        }
+    }
+}
+
+#[cfg(test)]
+fn get_file() -> LedgerFile {
+    LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:grocery
+    amount: 0
+  - account: expense:general
+    amount: 0
+transactions:
+  - date: 2020-01-02
+    description: WHOLE FOODS MARKET 1021
+    account: expense:grocery
+    amount: 50
+    offset_account: asset:checking
+  - date: 2020-01-05
+    description: corner store
+    transactions:
+      - account: expense:general
+        amount: 20
+      - account: asset:checking
+        amount: -20
+  - date: 2020-01-06
+    description: ATM WITHDRAWAL
+    account: asset:checking
+    amount: -100
+    offset_account: expense:general
+    external_id: A-1
+",
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+fn imported(date: &str, description: &str, amount: Decimal, id: Option<&str>) -> Transaction {
+    Transaction {
+        date: chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+        account: Some("expense:uncategorized".to_string()),
+        amount: Some(amount),
+        currency: None,
+        description: description.to_string(),
+        offset_account: Some("asset:checking".to_string()),
+        assert_balance: None,
+        transactions: None,
+        external_id: id.map(String::from),
+        payee: None,
+        tags: None,
+        meta: None,
+        status: None,
+        code: None,
+        comment: None,
+    }
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[test]
+fn check_compares_date_amount_and_description() {
+    let file = get_file();
+    let mut duplicates = Duplicates::new(&file);
+
+    let check = duplicates.check(&imported("2020-01-03", "TRADER JOES", dec!(50), None));
+    assert_eq!(check, Check::Ambiguous(&file.transactions[0]));
+
+    // posted a day later by the bank, with another store number
+    let check = duplicates.check(&imported(
+        "2020-01-03",
+        "Whole Foods Market #2245",
+        dec!(50),
+        None,
+    ));
+    assert_eq!(check, Check::Duplicate(&file.transactions[0]));
+
+    let check = duplicates.check(&imported("2020-01-04", "CORNER STORE", dec!(20), None));
+    assert_eq!(check, Check::Duplicate(&file.transactions[1]));
+
+    let check = duplicates.check(&imported(
+        "2020-01-09",
+        "WHOLE FOODS MARKET",
+        dec!(50),
+        None,
+    ));
+    assert_eq!(check, Check::New);
+    let check = duplicates.check(&imported(
+        "2020-01-02",
+        "WHOLE FOODS MARKET",
+        dec!(51),
+        None,
+    ));
+    assert_eq!(check, Check::New);
+}
+
+#[test]
+fn check_prefers_external_ids() {
+    let file = get_file();
+    let mut duplicates = Duplicates::new(&file);
+
+    let check = duplicates.check(&imported("2020-02-01", "withdrawal", dec!(1), Some("A-1")));
+    assert_eq!(check, Check::Duplicate(&file.transactions[2]));
+
+    let check = duplicates.check(&imported(
+        "2020-01-06",
+        "ATM WITHDRAWAL",
+        dec!(100),
+        Some("A-2"),
+    ));
+    assert_eq!(check, Check::New);
+}
+
+#[test]
+fn check_matches_each_transaction_once() {
+    let file = get_file();
+    let mut duplicates = Duplicates::new(&file);
+    let fare = imported("2020-01-05", "corner store", dec!(20), None);
+
+    // the same fare twice on one day, once in the ledger file
+    assert_eq!(
+        duplicates.check(&fare),
+        Check::Duplicate(&file.transactions[1])
+    );
+    assert_eq!(duplicates.check(&fare), Check::New);
+}
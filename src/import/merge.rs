@@ -0,0 +1,593 @@
+use crate::error::{Error, Result};
+use crate::include;
+use crate::ledger::{Account, LedgerFile, Transaction};
+use crate::source_map::SourceMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// lines of unchanged text shown around the changes of a diff
+const CONTEXT: usize = 3;
+
+/// replacement of `remove` lines of the source, starting at `line`, with
+/// the `insert` lines
+#[derive(Debug, PartialEq, Clone)]
+struct Edit {
+    line: usize,
+    remove: usize,
+    insert: Vec<String>,
+}
+
+/// imported transactions merged into the source of a ledger file. they are
+/// inserted as yaml text into the `transactions` list in date order, so the
+/// comments and formatting of the rest of the file are kept as they are
+#[derive(Debug, PartialEq, Clone)]
+pub struct Merge {
+    lines: Vec<String>,
+    edits: Vec<Edit>,
+}
+
+fn is_trivia(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// line of the `- ` that starts the `i`th item of the `list` of the source
+fn item_start(lines: &[String], source_map: &SourceMap, list: &str, i: usize) -> Result<usize> {
+    let unsupported = || {
+        Error::InvalidArg(format!(
+            "cannot merge into ledger file: {}[{}] is not an item of a block sequence",
+            list, i
+        ))
+    };
+
+    let (line, column) = source_map
+        .lookup(&format!("{}[{}]", list, i))
+        .ok_or_else(unsupported)?;
+    let text = lines.get(line - 1).ok_or_else(unsupported)?;
+
+    match text.get(..column - 1) {
+        Some(prefix) if prefix.trim() == "-" => Ok(line - 1),
+        _ => Err(unsupported()),
+    }
+}
+
+/// line after the item starting at `start`. an item runs up to the first
+/// line that is indented no further than its `- `, without trailing
+/// comments
+fn item_end(lines: &[String], start: usize) -> usize {
+    let dash = indent(&lines[start]);
+    let mut end = start + 1;
+    let mut line = start + 1;
+
+    while line < lines.len() && (is_trivia(&lines[line]) || indent(&lines[line]) > dash) {
+        line += 1;
+        if !is_trivia(&lines[line - 1]) {
+            end = line;
+        }
+    }
+
+    end
+}
+
+/// first line of the comments directly above `line`, which describe the
+/// transaction starting at it
+fn block_start(lines: &[String], line: usize) -> usize {
+    let mut start = line;
+    while start > 0 && lines[start - 1].trim().starts_with('#') {
+        start -= 1;
+    }
+    start
+}
+
+/// yaml lines of `value` as an item of a sequence indented by `indent`
+fn item<T: Serialize>(value: &T, indent: usize, newline: &str) -> Result<Vec<String>> {
+    let yaml = crate::yaml::to_string(&[value])?;
+
+    Ok(yaml
+        .trim_start_matches("---\n")
+        .lines()
+        .map(|l| format!("{}{}{}", " ".repeat(indent), l, newline))
+        .collect())
+}
+
+/// edit that inserts `insert` before `line`, which may be the end of the
+/// source
+fn insert(lines: &mut [String], line: usize, insert: Vec<String>, newline: &str) -> Edit {
+    // text appended to a last line without line break would be joined
+    // with it
+    if line == lines.len() && line > 0 && !lines[line - 1].ends_with('\n') {
+        lines[line - 1].push_str(newline);
+    }
+
+    Edit {
+        line,
+        remove: 0,
+        insert,
+    }
+}
+
+/// edit that writes `items` as the block sequence of the top level `key`
+/// of a source without items under it. an empty list is written as
+/// `key: []`, which is replaced. ledger files that include their lists may
+/// not have the key at all, so it is added to the end of the source
+fn new_list(lines: &mut [String], key: &str, items: Vec<String>, newline: &str) -> Edit {
+    let key_line = lines
+        .iter()
+        .position(|l| l.starts_with(&format!("{}:", key)));
+
+    let mut edit = insert(
+        lines,
+        key_line.unwrap_or(lines.len()),
+        vec![format!("{}:{}", key, newline)],
+        newline,
+    );
+    edit.insert.extend(items);
+    if key_line.is_some() {
+        edit.remove = 1;
+    }
+    edit
+}
+
+impl Merge {
+    /// merge `transactions` into `source`, the text of `ledger_file`. each
+    /// transaction is inserted before the first transaction of the file
+    /// with a later date, or after the last one. `accounts` that the
+    /// transactions post to but the ledger file does not declare are added
+    /// to the end of its `accounts` list
+    pub fn new(
+        source: &str,
+        ledger_file: &LedgerFile,
+        transactions: &[Transaction],
+        accounts: &[String],
+    ) -> Result<Merge> {
+        let mut lines: Vec<String> = source.split_inclusive('\n').map(String::from).collect();
+        let newline = if source.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut transactions = transactions.to_vec();
+        transactions.sort_by_key(|t| t.date);
+
+        let source_map = SourceMap::new(source);
+        let starts = (0..ledger_file.transactions.len())
+            .map(|i| item_start(&lines, &source_map, "transactions", i))
+            .collect::<Result<Vec<usize>>>()?;
+
+        let mut edits = Vec::new();
+        if !accounts.is_empty() {
+            let last = match ledger_file.accounts.len() {
+                0 => None,
+                len => Some(item_start(&lines, &source_map, "accounts", len - 1)?),
+            };
+            let dash = last.map_or(2, |last| indent(&lines[last]));
+
+            let mut items = Vec::new();
+            for account in accounts {
+                let account = Account {
+                    account: account.clone(),
+                    amount: Decimal::ZERO,
+                    currency: None,
+                    budget_month: None,
+                    budget_year: None,
+                };
+                items.extend(item(&account, dash, newline)?);
+            }
+
+            edits.push(match last {
+                Some(last) => {
+                    let end = item_end(&lines, last);
+                    insert(&mut lines, end, items, newline)
+                }
+                None => new_list(&mut lines, "accounts", items, newline),
+            });
+        }
+
+        if transactions.is_empty() {
+            return Ok(Merge { lines, edits });
+        }
+
+        let last = match starts.iter().max() {
+            Some(last) => *last,
+            None => {
+                let mut items = Vec::new();
+                for t in &transactions {
+                    items.extend(item(t, 2, newline)?);
+                }
+                edits.push(new_list(&mut lines, "transactions", items, newline));
+                edits.sort_by_key(|e| e.line);
+                return Ok(Merge { lines, edits });
+            }
+        };
+
+        let dash = indent(&lines[last]);
+        // transactions separated by blank lines keep being so
+        let separated = starts.len() > 1 && {
+            let start = block_start(&lines, starts[1]);
+            start > 0 && lines[start - 1].trim().is_empty()
+        };
+        let end = item_end(&lines, last);
+
+        let mut inserts: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for t in &transactions {
+            let before = ledger_file
+                .transactions
+                .iter()
+                .zip(&starts)
+                .find(|(existing, _)| existing.date > t.date);
+            let item = item(t, dash, newline)?;
+
+            match before {
+                Some((_, start)) => {
+                    let insert = inserts.entry(block_start(&lines, *start)).or_default();
+                    insert.extend(item);
+                    if separated {
+                        insert.push(newline.to_string());
+                    }
+                }
+                None => {
+                    let insert = inserts.entry(end).or_default();
+                    if separated {
+                        insert.push(newline.to_string());
+                    }
+                    insert.extend(item);
+                }
+            }
+        }
+
+        for (line, items) in inserts {
+            edits.push(insert(&mut lines, line, items, newline));
+        }
+        // the accounts may come before or after the transactions
+        edits.sort_by_key(|e| e.line);
+
+        Ok(Merge { lines, edits })
+    }
+
+    /// merge `transactions` and the `accounts` they need into the ledger
+    /// file located at `filename`, rather than into the files it includes.
+    /// errors point to their position within the file they came from, or
+    /// within the merged text when the merged file is invalid
+    pub fn read(
+        filename: &str,
+        transactions: &[Transaction],
+        accounts: &[String],
+    ) -> Result<Merge> {
+        let source = fs::read_to_string(filename)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+        include::load(filename, filename, &source, false)?;
+        let ledger_file: LedgerFile =
+            serde_yaml::from_str(&source).map_err(|e| Error::from(e).locate(filename, &source))?;
+
+        let merge = Merge::new(&source, &ledger_file, transactions, accounts)?;
+        let merged = merge.source();
+        include::load(filename, &format!("{} (merged)", filename), &merged, false)?;
+
+        Ok(merge)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// text of the merged ledger file
+    pub fn source(&self) -> String {
+        let mut source = String::new();
+        let mut line = 0;
+
+        for edit in &self.edits {
+            source.extend(self.lines[line..edit.line].iter().map(String::as_str));
+            source.extend(edit.insert.iter().map(String::as_str));
+            line = edit.line + edit.remove;
+        }
+        source.extend(self.lines[line..].iter().map(String::as_str));
+
+        source
+    }
+
+    /// unified diff of the changes to the ledger file located at `filename`
+    pub fn diff(&self, filename: &str) -> String {
+        if self.edits.is_empty() {
+            return String::new();
+        }
+
+        // edits whose context overlaps are shown in the same hunk
+        let mut hunks: Vec<(usize, usize, Vec<&Edit>)> = Vec::new();
+        for edit in &self.edits {
+            let start = edit.line.saturating_sub(CONTEXT);
+            let end = (edit.line + edit.remove + CONTEXT).min(self.lines.len());
+            match hunks.last_mut() {
+                Some(hunk) if start <= hunk.1 => {
+                    hunk.1 = end;
+                    hunk.2.push(edit);
+                }
+                _ => hunks.push((start, end, vec![edit])),
+            }
+        }
+
+        let mut diff = format!("--- {}\n+++ {}\n", filename, filename);
+        let mut added = 0;
+        for (start, end, edits) in hunks {
+            let mut body = String::new();
+            let (mut old, mut new) = (0, 0);
+            let push = |body: &mut String, prefix: &str, line: &str| {
+                body.push_str(prefix);
+                body.push_str(line.trim_end_matches(['\r', '\n']));
+                body.push('\n');
+            };
+
+            let mut line = start;
+            for edit in edits {
+                for l in &self.lines[line..edit.line] {
+                    push(&mut body, " ", l);
+                }
+                for l in &self.lines[edit.line..edit.line + edit.remove] {
+                    push(&mut body, "-", l);
+                }
+                for l in &edit.insert {
+                    push(&mut body, "+", l);
+                }
+                old += edit.line - line + edit.remove;
+                new += edit.line - line + edit.insert.len();
+                line = edit.line + edit.remove;
+            }
+            for l in &self.lines[line..end] {
+                push(&mut body, " ", l);
+            }
+            old += end - line;
+            new += end - line;
+
+            // empty ranges are numbered after the line they follow
+            let first = |count: usize, start: usize| if count == 0 { start } else { start + 1 };
+            diff.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n{}",
+                first(old, start),
+                old,
+                first(new, start + added),
+                new,
+                body
+            ));
+            added += new;
+            added -= old;
+        }
+
+        diff
+    }
+
+    /// replace the ledger file located at `filename` with the merged text.
+    /// the text is written to a temporary file that is renamed over the
+    /// ledger file, so an interrupted write leaves the file intact. the
+    /// previous version of the file is kept as `<filename>.bak`, whose
+    /// name is returned
+    pub fn write(&self, filename: &str) -> Result<String> {
+        let error = |name: &str, e: io::Error| io::Error::new(e.kind(), format!("{}: {}", name, e));
+        let path = Path::new(filename);
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::InvalidArg(format!("not a file: {}", filename)))?;
+        let temp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+        let temp_name = temp.to_string_lossy().to_string();
+        let backup = format!("{}.bak", filename);
+
+        let permissions = fs::metadata(path)
+            .map_err(|e| error(filename, e))?
+            .permissions();
+        let mut file = File::create(&temp).map_err(|e| error(&temp_name, e))?;
+        file.write_all(self.source().as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::set_permissions(&temp, permissions))
+            .map_err(|e| error(&temp_name, e))?;
+
+        fs::copy(path, &backup).map_err(|e| error(&backup, e))?;
+        fs::rename(&temp, path).map_err(|e| error(filename, e))?;
+
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+const SOURCE: &str = "# household ledger
+currency: USD
+accounts:
+  - account: asset:checking
+    amount: 100
+  - account: expense:grocery
+    amount: 0
+transactions:
+  # first shopping of the year
+  - date: 2020-01-02
+    description: grocery store
+    account: expense:grocery
+    amount: 10
+    offset_account: asset:checking
+
+  - date: 2020-01-09
+    description: grocery store
+    transactions:
+      - account: expense:grocery
+        amount: 20
+      - account: asset:checking
+        amount: -20
+
+# end of file
+";
+
+#[cfg(test)]
+fn transaction(date: &str, description: &str) -> Transaction {
+    Transaction {
+        date: chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+        account: Some("expense:grocery".to_string()),
+        amount: Some(rust_decimal::Decimal::new(550, 2)),
+        currency: None,
+        description: description.to_string(),
+        offset_account: Some("asset:checking".to_string()),
+        assert_balance: None,
+        transactions: None,
+        external_id: None,
+        payee: None,
+        tags: None,
+        meta: None,
+        status: None,
+        code: None,
+        comment: None,
+    }
+}
+
+#[test]
+fn merge_inserts_transactions_in_date_order() {
+    let file = LedgerFile::parse(SOURCE).unwrap();
+    let merge = Merge::new(
+        SOURCE,
+        &file,
+        &[
+            transaction("2020-01-20", "bakery"),
+            transaction("2020-01-01", "market"),
+        ],
+        &[],
+    )
+    .unwrap();
+    let merged = merge.source();
+
+    assert!(merged.starts_with(
+        "# household ledger
+currency: USD
+accounts:
+  - account: asset:checking
+    amount: 100
+  - account: expense:grocery
+    amount: 0
+transactions:
+  - date: 2020-01-01
+    account: \"expense:grocery\"
+    amount: 5.50
+    description: market
+    offset_account: \"asset:checking\"
+
+  # first shopping of the year
+  - date: 2020-01-02"
+    ));
+    assert!(merged.ends_with(
+        "        amount: -20
+
+  - date: 2020-01-20
+    account: \"expense:grocery\"
+    amount: 5.50
+    description: bakery
+    offset_account: \"asset:checking\"
+
+# end of file
+"
+    ));
+
+    let merged = LedgerFile::parse(&merged).unwrap();
+    let dates: Vec<String> = merged
+        .transactions
+        .iter()
+        .map(|t| t.date.to_string())
+        .collect();
+    assert_eq!(
+        dates,
+        vec!["2020-01-01", "2020-01-02", "2020-01-09", "2020-01-20"]
+    );
+}
+
+#[test]
+fn merge_into_empty_list() {
+    let source = "currency: USD
+accounts:
+  - account: expense:grocery
+    amount: 0
+  - account: asset:checking
+    amount: 0
+transactions: []";
+    let file = LedgerFile::parse(source).unwrap();
+    let merge = Merge::new(source, &file, &[transaction("2020-01-01", "market")], &[]).unwrap();
+
+    let merged = LedgerFile::parse(&merge.source()).unwrap();
+    assert_eq!(merged.transactions[0].description, "market");
+    assert!(Merge::new(source, &file, &[], &[]).unwrap().is_empty());
+}
+
+#[test]
+fn merge_adds_missing_transactions() {
+    let source = "currency: USD
+accounts:
+  - account: expense:grocery
+    amount: 0
+  - account: asset:checking
+    amount: 0";
+    let file = LedgerFile::parse(source).unwrap();
+    let merge = Merge::new(source, &file, &[transaction("2020-01-01", "market")], &[]).unwrap();
+
+    let merged = LedgerFile::parse(&merge.source()).unwrap();
+    assert_eq!(merged.transactions[0].description, "market");
+}
+
+#[test]
+fn diff_shows_inserted_lines() {
+    let file = LedgerFile::parse(SOURCE).unwrap();
+    let merge = Merge::new(SOURCE, &file, &[transaction("2020-01-05", "market")], &[]).unwrap();
+
+    assert_eq!(
+        merge.diff("ledger.yaml"),
+        "--- ledger.yaml
++++ ledger.yaml
+@@ -13,6 +13,12 @@
+     amount: 10
+     offset_account: asset:checking
+\x20
++  - date: 2020-01-05
++    account: \"expense:grocery\"
++    amount: 5.50
++    description: market
++    offset_account: \"asset:checking\"
++
+   - date: 2020-01-09
+     description: grocery store
+     transactions:
+"
+    );
+}
+
+#[test]
+fn merge_declares_accounts() {
+    let file = LedgerFile::parse(SOURCE).unwrap();
+    let mut market = transaction("2020-01-05", "market");
+    market.account = Some("expense:uncategorized".to_string());
+    let accounts = ["expense:uncategorized".to_string()];
+    let merge = Merge::new(SOURCE, &file, &[market.clone()], &accounts).unwrap();
+
+    assert!(merge.source().contains(
+        "  - account: expense:grocery
+    amount: 0
+  - account: \"expense:uncategorized\"
+    amount: 0
+transactions:
+"
+    ));
+    assert!(LedgerFile::parse(&merge.source()).is_ok());
+
+    // included files are not read, like when merging into the ledger file
+    let source = "currency: USD\ninclude: [\"*.yaml\"]";
+    let file: LedgerFile = serde_yaml::from_str(source).unwrap();
+    let merge = Merge::new(source, &file, &[market], &accounts).unwrap();
+
+    assert!(merge.source().starts_with(
+        "currency: USD
+include: [\"*.yaml\"]
+accounts:
+  - account: \"expense:uncategorized\"
+    amount: 0
+transactions:
+  - date: 2020-01-05
+"
+    ));
+}
@@ -0,0 +1,457 @@
+use crate::error::{Error, Result};
+use crate::import::categorize::{Candidate, Categorizer, Fallback};
+use crate::import::duplicate::Duplicates;
+use crate::import::{Balance, Import};
+use crate::ledger::{LedgerFile, Transaction};
+use chrono::NaiveDate;
+use encoding_rs::Encoding;
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// transaction of a bank or credit card statement. `id` is the FITID the
+/// bank assigned to it, which stays the same across downloads
+#[derive(Debug, PartialEq, Clone)]
+pub struct StatementTransaction {
+    pub id: String,
+    pub date: NaiveDate,
+    pub amount: Decimal,
+    pub name: Option<String>,
+    pub memo: Option<String>,
+}
+
+/// bank or credit card statement of an OFX file, along with the ledger
+/// balance of the account as of a date
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    pub currency: Option<String>,
+    pub transactions: Vec<StatementTransaction>,
+    pub ledger_balance: Option<(NaiveDate, Decimal)>,
+}
+
+/// parse the statements of an OFX or QFX file. both the SGML based OFX 1.x
+/// format, where elements holding a value are not closed, and the XML
+/// based OFX 2.x format are supported
+pub fn parse(source: &str) -> Result<Vec<Statement>> {
+    let mut statements = Vec::new();
+
+    for block in blocks(source, "STMTRS")
+        .into_iter()
+        .chain(blocks(source, "CCSTMTRS"))
+    {
+        let mut transactions = Vec::new();
+        for t in blocks(block, "STMTTRN") {
+            let id = value(t, "FITID").ok_or_else(|| invalid("transaction without FITID", t))?;
+            transactions.push(StatementTransaction {
+                date: date(t, "DTPOSTED")?,
+                amount: amount(t, "TRNAMT")?,
+                name: value(t, "NAME"),
+                memo: value(t, "MEMO"),
+                id,
+            });
+        }
+
+        let ledger_balance = match blocks(block, "LEDGERBAL").first() {
+            Some(b) => Some((date(b, "DTASOF")?, amount(b, "BALAMT")?)),
+            None => None,
+        };
+
+        statements.push(Statement {
+            currency: value(block, "CURDEF"),
+            transactions,
+            ledger_balance,
+        });
+    }
+
+    if statements.is_empty() {
+        return Err(Error::InvalidArg(
+            "no bank or credit card statement found in OFX file".to_string(),
+        ));
+    }
+
+    Ok(statements)
+}
+
+/// decode the bytes of an OFX file. OFX 1.x files declare their charset in
+/// the `CHARSET` header, e.g. `1252` for windows-1252, unless the
+/// `ENCODING` header is `UTF-8`. OFX 2.x files declare it in their xml
+/// declaration. files without a charset are read as UTF-8
+pub fn decode(bytes: &[u8]) -> Result<String> {
+    // headers and xml declarations are ASCII in every charset
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]);
+    let header = |name: &str| {
+        head.lines()
+            .find_map(|l| l.trim().strip_prefix(name))
+            .map(|v| v.trim().to_string())
+    };
+    let declaration = head
+        .split_once("<?xml")
+        .and_then(|(_, rest)| rest.split_once("?>"))
+        .and_then(|(xml, _)| xml.split_once("encoding="))
+        .and_then(|(_, rest)| rest.split(['"', '\'']).nth(1))
+        .map(String::from);
+
+    let label = match (header("ENCODING:"), header("CHARSET:"), declaration) {
+        (_, _, Some(encoding)) => encoding,
+        (Some(encoding), _, _) if encoding.eq_ignore_ascii_case("UTF-8") => encoding,
+        // windows code pages are given by their number
+        (_, Some(charset), _) if charset.chars().all(|c| c.is_ascii_digit()) => {
+            format!("windows-{}", charset)
+        }
+        (_, Some(charset), _) if !charset.eq_ignore_ascii_case("NONE") => charset,
+        _ => "UTF-8".to_string(),
+    };
+
+    let encoding = Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| Error::InvalidArg(format!("unknown OFX charset: {}", label)))?;
+    let (source, _, _) = encoding.decode(bytes);
+
+    Ok(source.into_owned())
+}
+
+/// convert the statements of an OFX file into transactions of `account`,
+/// offset by the account categorized for their description. the FITID of a
+/// transaction becomes its external id, which recognizes transactions
+/// imported before. the ledger balance of a statement becomes a balance
+/// assertion on its latest transaction up to the date of the balance. when
+/// that transaction was imported before, the balance is left unasserted
+pub fn import(
+    bytes: &[u8],
+    account: &str,
+    ledger_file: &LedgerFile,
+    fallback: Fallback,
+) -> Result<Import> {
+    if !ledger_file.accounts.iter().any(|a| a.account == account) {
+        return Err(Error::InvalidArg(format!("unknown account: {}", account)));
+    }
+
+    let account_currency = ledger_file.resolve_currency(&None, account);
+    let categorizer = Categorizer::new(ledger_file, fallback)?;
+    let mut duplicates = Duplicates::new(ledger_file);
+    // statements may overlap within a file as well
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut import = Import::default();
+
+    for statement in parse(&decode(bytes)?)? {
+        let currency = statement.currency.filter(|c| *c != account_currency);
+        let mut imported = Vec::new();
+
+        for t in statement.transactions {
+            if !seen.insert(t.id.clone()) {
+                continue;
+            }
+
+            let description = t.name.or(t.memo).unwrap_or_default();
+            // amounts are seen from the statement account, so the
+            // categorized account receives the opposite amount
+            let categorization = categorizer.categorize(&Candidate {
+                date: t.date,
+                payee: &description,
+                amount: -t.amount,
+                account,
+            });
+
+            let mut transaction = Transaction {
+                date: t.date,
+                account: Some(account.to_string()),
+                amount: Some(t.amount),
+                currency: currency.clone(),
+                description,
+                offset_account: Some(categorization.account.value.clone()),
+                assert_balance: None,
+                transactions: None,
+                external_id: Some(t.id),
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
+            };
+            categorization.annotate(&mut transaction);
+            imported.push((transaction, categorization));
+        }
+
+        imported.sort_by_key(|(t, _)| t.date);
+        // a balance asserted on an earlier transaction would not hold
+        // once the later ones are in the ledger file
+        let last_id = statement.ledger_balance.and_then(|(date, _)| {
+            imported
+                .iter()
+                .rev()
+                .find(|(t, _)| t.date <= date)
+                .and_then(|(t, _)| t.external_id.clone())
+        });
+        let start = import.transactions.len();
+        for (transaction, categorization) in imported {
+            import.add(transaction, categorization, &mut duplicates);
+        }
+
+        if let Some((date, balance)) = statement.ledger_balance {
+            let last = last_id.and_then(|id| {
+                import.transactions[start..]
+                    .iter_mut()
+                    .find(|t| t.external_id.as_ref() == Some(&id))
+            });
+            match last {
+                Some(last) => last.assert_balance = Some(balance),
+                None => import.unasserted.push(Balance {
+                    account: account.to_string(),
+                    date,
+                    balance,
+                }),
+            }
+        }
+    }
+
+    Ok(import)
+}
+
+fn invalid(message: &str, block: &str) -> Error {
+    Error::InvalidArg(format!("invalid OFX file, {}: {}", message, block.trim()))
+}
+
+/// contents of every `<tag>` aggregate. aggregates are closed in both OFX
+/// versions
+fn blocks<'a>(source: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut blocks = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find(&open) {
+        let contents = &rest[start + open.len()..];
+        let end = contents.find(&close).unwrap_or(contents.len());
+        blocks.push(&contents[..end]);
+        rest = &contents[end..];
+    }
+
+    blocks
+}
+
+/// value of the `<tag>` element of `block`, which runs up to the next tag
+fn value(block: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = block.find(&open)? + open.len();
+    let raw = block[start..].split('<').next().unwrap_or("").trim();
+
+    match raw {
+        "" => None,
+        v => Some(
+            v.replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&"),
+        ),
+    }
+}
+
+/// dates are formatted as `YYYYMMDD`, optionally followed by a time and
+/// a time zone, e.g. `20200131120000.000[-5:EST]`
+fn date(block: &str, tag: &str) -> Result<NaiveDate> {
+    let raw = value(block, tag).ok_or_else(|| invalid(&format!("missing {}", tag), block))?;
+
+    raw.get(..8)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+        .ok_or_else(|| invalid(&format!("invalid date {}", raw), block))
+}
+
+fn amount(block: &str, tag: &str) -> Result<Decimal> {
+    let raw = value(block, tag).ok_or_else(|| invalid(&format!("missing {}", tag), block))?;
+
+    Decimal::from_str(raw.trim_start_matches('+'))
+        .map_err(|_| invalid(&format!("invalid amount {}", raw), block))
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+const SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>USD
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20200103120000.000[-5:EST]
+<TRNAMT>-45.10
+<FITID>2020010301
+<NAME>SHELL OIL &amp; GAS
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20200102
+<TRNAMT>300.00
+<FITID>2020010201
+<MEMO>donut sale to dale
+</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL><BALAMT>1754.90<DTASOF>20200105</LEDGERBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+
+#[cfg(test)]
+const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX>
+  <CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+    <CURDEF>USD</CURDEF>
+    <BANKTRANLIST>
+      <STMTTRN>
+        <TRNTYPE>DEBIT</TRNTYPE>
+        <DTPOSTED>20191231</DTPOSTED>
+        <TRNAMT>-455.00</TRNAMT>
+        <FITID>A-1</FITID>
+        <NAME>weekly groceries</NAME>
+      </STMTTRN>
+    </BANKTRANLIST>
+    <LEDGERBAL><BALAMT>-455.00</BALAMT><DTASOF>20191231</DTASOF></LEDGERBAL>
+  </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>
+"#;
+
+#[cfg(test)]
+fn get_file() -> LedgerFile {
+    LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 1500
+  - account: liability:amex
+    amount: 0
+  - account: income:general
+    amount: 0
+  - account: expense:grocery
+    amount: 0
+transactions:
+  - date: 2019-12-01
+    description: weekly groceries
+    account: expense:grocery
+    amount: 10
+    offset_account: liability:amex
+  - date: 2020-01-02
+    description: donut sale
+    account: asset:checking
+    amount: 1
+    offset_account: income:general
+    external_id: \"2020010201\"
+",
+    )
+    .unwrap()
+}
+
+#[test]
+fn parse_sgml_statement() {
+    let statements = parse(SGML).unwrap();
+    let transactions = &statements[0].transactions;
+
+    assert_eq!(statements[0].currency.as_deref(), Some("USD"));
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0].id, "2020010301");
+    assert_eq!(transactions[0].date, NaiveDate::from_ymd(2020, 1, 3));
+    assert_eq!(transactions[0].amount, dec!(-45.10));
+    assert_eq!(transactions[0].name.as_deref(), Some("SHELL OIL & GAS"));
+    assert_eq!(transactions[1].name, None);
+    assert_eq!(
+        statements[0].ledger_balance,
+        Some((NaiveDate::from_ymd(2020, 1, 5), dec!(1754.90)))
+    );
+}
+
+#[test]
+fn parse_xml_statement() {
+    let statements = parse(XML).unwrap();
+
+    assert_eq!(statements.len(), 1);
+    assert_eq!(statements[0].transactions[0].id, "A-1");
+    assert_eq!(statements[0].transactions[0].amount, dec!(-455.00));
+    assert!(parse("<OFX></OFX>").is_err());
+}
+
+#[test]
+fn import_skips_known_ids_and_asserts_ledger_balance() {
+    let file = get_file();
+    let transactions = import(
+        SGML.as_bytes(),
+        "asset:checking",
+        &file,
+        Fallback::default(),
+    )
+    .unwrap()
+    .transactions;
+
+    // the donut sale was imported before
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].external_id.as_deref(), Some("2020010301"));
+    assert_eq!(transactions[0].account.as_deref(), Some("asset:checking"));
+    assert_eq!(transactions[0].assert_balance, Some(dec!(1754.90)));
+    assert_eq!(transactions[0].currency, None);
+
+    let transactions = import(XML.as_bytes(), "liability:amex", &file, Fallback::default())
+        .unwrap()
+        .transactions;
+    assert_eq!(
+        transactions[0].offset_account.as_deref(),
+        Some("expense:grocery")
+    );
+
+    assert!(import(XML.as_bytes(), "liability:visa", &file, Fallback::default()).is_err());
+}
+
+#[test]
+fn decode_uses_declared_charset() {
+    let sgml = SGML
+        .replace(
+            "VERSION:102\n",
+            "VERSION:102\nENCODING:USASCII\nCHARSET:1252\n",
+        )
+        .replace("SHELL OIL &amp; GAS", "CAFÉ DU MONDE");
+    let (bytes, _, _) = encoding_rs::WINDOWS_1252.encode(&sgml);
+    assert!(std::str::from_utf8(&bytes).is_err());
+
+    let statements = parse(&decode(&bytes).unwrap()).unwrap();
+    assert_eq!(
+        statements[0].transactions[0].name.as_deref(),
+        Some("CAFÉ DU MONDE")
+    );
+
+    let latin = XML
+        .replace("UTF-8", "ISO-8859-1")
+        .replace("weekly groceries", "épicerie");
+    let (bytes, _, _) = encoding_rs::WINDOWS_1252.encode(&latin);
+    assert!(decode(&bytes).unwrap().contains("<NAME>épicerie</NAME>"));
+
+    let unknown = SGML.replace("VERSION:102\n", "VERSION:102\nCHARSET:KLINGON\n");
+    assert!(decode(unknown.as_bytes()).is_err());
+}
+
+#[test]
+fn import_does_not_assert_balance_before_skipped_transaction() {
+    // the donut sale, which was imported before, is now the last
+    // transaction up to the date of the balance
+    let sgml = SGML.replace("<DTPOSTED>20200102", "<DTPOSTED>20200104");
+    let import = import(
+        sgml.as_bytes(),
+        "asset:checking",
+        &get_file(),
+        Fallback::default(),
+    )
+    .unwrap();
+
+    assert_eq!(import.transactions.len(), 1);
+    assert_eq!(import.transactions[0].assert_balance, None);
+    assert_eq!(
+        import.unasserted,
+        vec![Balance {
+            account: "asset:checking".to_string(),
+            date: NaiveDate::from_ymd_opt(2020, 1, 5).unwrap(),
+            balance: dec!(1754.90),
+        }]
+    );
+}
@@ -0,0 +1,438 @@
+use crate::error::{Diagnostic, Error, Result};
+use crate::ledger::LedgerFile;
+use crate::source_map::SourceMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// ledger file read while following includes
+struct File {
+    name: String,
+    source: String,
+    /// directory that includes of the file are relative to
+    dir: PathBuf,
+    /// canonical path, which identifies the file across includes
+    path: PathBuf,
+}
+
+/// file an item of a merged list came from, along with its index within
+/// the list of that file
+type Origin = (usize, usize);
+
+/// ledger files read by following the `include` lists of a ledger file
+/// depth first. the items of each file come before those of the files it
+/// includes. the origin of every item of the merged lists is kept, so
+/// diagnostics of the merged ledger file point into the file the item
+/// came from
+#[derive(Default)]
+struct Includes {
+    files: Vec<File>,
+    /// files being read, from the ledger file down to the current one
+    stack: Vec<usize>,
+    currency: usize,
+    commodities: Vec<Origin>,
+    prices: Vec<Origin>,
+    accounts: Vec<Origin>,
+    transactions: Vec<Origin>,
+    rules: Vec<Origin>,
+    payee_aliases: Vec<Origin>,
+}
+
+fn has_wildcard(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// append `items` of the file at `file` to `merged`, remembering where they came from
+fn extend<T>(merged: &mut Vec<T>, origins: &mut Vec<Origin>, file: usize, items: Vec<T>) {
+    origins.extend((0..items.len()).map(|i| (file, i)));
+    merged.extend(items);
+}
+
+fn extend_option<T>(
+    merged: &mut Option<Vec<T>>,
+    origins: &mut Vec<Origin>,
+    file: usize,
+    items: Option<Vec<T>>,
+) {
+    if let Some(items) = items {
+        extend(merged.get_or_insert_with(Vec::new), origins, file, items);
+    }
+}
+
+impl Includes {
+    /// error at the node located at `path` of the file at `file`
+    fn error(&self, file: usize, message: String, path: &str) -> Error {
+        let file = &self.files[file];
+        Error::Validation(vec![Diagnostic::new(message, path)]).locate(&file.name, &file.source)
+    }
+
+    /// read the ledger file named `name` with contents `source`, located
+    /// at `path`, into `merged`, followed by the files it includes
+    fn add(
+        &mut self,
+        merged: &mut LedgerFile,
+        name: &str,
+        path: &Path,
+        source: String,
+    ) -> Result<()> {
+        let document: LedgerFile =
+            serde_yaml::from_str(&source).map_err(|e| Error::from(e).locate(name, &source))?;
+        let index = self.files.len();
+        self.files.push(File {
+            name: name.to_string(),
+            source,
+            dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            path: fs::canonicalize(path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", name, e)))?,
+        });
+
+        if !document.currency.is_empty() {
+            if merged.currency.is_empty() {
+                merged.currency = document.currency;
+                self.currency = index;
+            } else if merged.currency != document.currency {
+                let message = format!(
+                    "currency {} differs from currency {} of {}",
+                    document.currency, merged.currency, self.files[self.currency].name
+                );
+                return Err(self.error(index, message, "currency"));
+            }
+        }
+
+        extend_option(
+            &mut merged.commodities,
+            &mut self.commodities,
+            index,
+            document.commodities,
+        );
+        extend_option(&mut merged.prices, &mut self.prices, index, document.prices);
+        extend(
+            &mut merged.accounts,
+            &mut self.accounts,
+            index,
+            document.accounts,
+        );
+        extend(
+            &mut merged.transactions,
+            &mut self.transactions,
+            index,
+            document.transactions,
+        );
+        extend_option(&mut merged.rules, &mut self.rules, index, document.rules);
+        extend_option(
+            &mut merged.payee_aliases,
+            &mut self.payee_aliases,
+            index,
+            document.payee_aliases,
+        );
+
+        self.stack.push(index);
+        for (i, pattern) in document.include.iter().flatten().enumerate() {
+            let node = format!("include[{}]", i);
+            for included in self.expand(index, pattern, &node)? {
+                self.include(merged, index, &included, &node)?;
+            }
+        }
+        self.stack.pop();
+
+        Ok(())
+    }
+
+    /// paths of the files matched by `pattern`, relative to the file at
+    /// `file`. patterns without wildcards name a single file, which has to
+    /// exist, while glob patterns may match none
+    fn expand(&self, file: usize, pattern: &str, node: &str) -> Result<Vec<PathBuf>> {
+        let dir = &self.files[file].dir;
+
+        if !has_wildcard(pattern) {
+            return Ok(vec![dir.join(pattern)]);
+        }
+
+        let pattern = match dir.to_str() {
+            Some(d) if !d.is_empty() && !Path::new(pattern).is_absolute() => {
+                format!("{}/{}", glob::Pattern::escape(d), pattern)
+            }
+            _ => pattern.to_string(),
+        };
+        let paths = glob::glob(&pattern).map_err(|e| {
+            self.error(
+                file,
+                format!("invalid include pattern {}: {}", pattern, e),
+                node,
+            )
+        })?;
+
+        paths
+            .map(|p| p.map_err(|e| self.error(file, format!("cannot read {}", e), node)))
+            .collect()
+    }
+
+    /// read the file at `path`, included by the file at `file`
+    fn include(
+        &mut self,
+        merged: &mut LedgerFile,
+        file: usize,
+        path: &Path,
+        node: &str,
+    ) -> Result<()> {
+        let name = path.to_string_lossy().to_string();
+        let read =
+            |e: std::io::Error| self.error(file, format!("cannot read {}: {}", name, e), node);
+        let canonical = fs::canonicalize(path).map_err(read)?;
+
+        if let Some(position) = self
+            .stack
+            .iter()
+            .position(|f| self.files[*f].path == canonical)
+        {
+            let mut cycle: Vec<&str> = self.stack[position..]
+                .iter()
+                .map(|f| self.files[*f].name.as_str())
+                .collect();
+            cycle.push(&name);
+            let message = format!("include cycle: {}", cycle.join(" -> "));
+            return Err(self.error(file, message, node));
+        }
+        if let Some(f) = self.files.iter().find(|f| f.path == canonical) {
+            let message = format!("{} is included more than once, as {} before", name, f.name);
+            return Err(self.error(file, message, node));
+        }
+
+        let source = fs::read_to_string(path).map_err(read)?;
+        self.add(merged, &name, path, source)
+    }
+
+    /// file that the node at `path` of the merged ledger file came from,
+    /// along with the path of the node within that file
+    fn origin(&self, path: &str) -> (usize, String) {
+        let lists = [
+            ("commodities", &self.commodities),
+            ("prices", &self.prices),
+            ("accounts", &self.accounts),
+            ("transactions", &self.transactions),
+            ("rules", &self.rules),
+            ("payee_aliases", &self.payee_aliases),
+        ];
+
+        for (list, origins) in lists {
+            let item = path
+                .strip_prefix(list)
+                .and_then(|p| p.strip_prefix('['))
+                .and_then(|p| p.split_once(']'));
+            if let Some((i, rest)) = item {
+                if let Some((file, i)) = i.parse().ok().and_then(|i: usize| origins.get(i)) {
+                    return (*file, format!("{}[{}]{}", list, i, rest));
+                }
+            }
+        }
+
+        match path {
+            "currency" => (self.currency, path.to_string()),
+            _ => (0, path.to_string()),
+        }
+    }
+
+    /// resolve the diagnostics of an error of the merged ledger file into
+    /// locations within the files they came from
+    fn locate(&self, err: Error) -> Error {
+        let source_maps: Vec<SourceMap> = self
+            .files
+            .iter()
+            .map(|f| SourceMap::new(&f.source))
+            .collect();
+
+        err.map_diagnostics(|d| {
+            let (file, path) = self.origin(&d.path);
+            d.path = path;
+            d.locate(
+                &self.files[file].name,
+                &self.files[file].source,
+                &source_maps[file],
+            );
+        })
+    }
+}
+
+/// deserialize `source`, the contents of the ledger file located at
+/// `filename`, merged with the files it includes, and validate the merged
+/// ledger file. errors point into the file they came from, where the
+/// ledger file itself is called `name`. with `strict_prices`, every
+/// exchange must balance at the prices of its date
+pub(crate) fn load(
+    filename: &str,
+    name: &str,
+    source: &str,
+    strict_prices: bool,
+) -> Result<LedgerFile> {
+    let mut includes = Includes::default();
+    let mut merged = LedgerFile::default();
+
+    includes.add(&mut merged, name, Path::new(filename), source.to_string())?;
+    merged
+        .validate(strict_prices)
+        .map_err(|e| includes.locate(e))?;
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+/// directory of ledger files named `dir` within the temp directory
+#[cfg(test)]
+fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(dir);
+    let _ = fs::remove_dir_all(&dir);
+    for (name, source) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+#[cfg(test)]
+const ACCOUNTS: &str = "accounts:
+  - account: asset:cash
+    amount: 100
+  - account: expense:food
+    amount: 0
+  - account: equity:opening
+    amount: -100
+";
+
+#[cfg(test)]
+fn read(dir: &Path) -> Result<LedgerFile> {
+    LedgerFile::read(dir.join("ledger.yaml").to_str().unwrap())
+}
+
+#[test]
+fn read_merges_included_files() {
+    let dir = write_files(
+        "rust_ledger_include_merge",
+        &[
+            (
+                "ledger.yaml",
+                "currency: USD\ninclude: [accounts.yaml, \"2020/*.yaml\", \"2021/*.yaml\"]\n",
+            ),
+            ("accounts.yaml", ACCOUNTS),
+            (
+                "2020/02.yaml",
+                "transactions:
+  - date: 2020-02-01
+    description: lunch
+    account: expense:food
+    offset_account: asset:cash
+    amount: 10
+",
+            ),
+            (
+                "2020/01.yaml",
+                "include: [../prices.yaml]
+transactions:
+  - date: 2020-01-01
+    description: dinner
+    account: expense:food
+    offset_account: asset:cash
+    amount: 20
+",
+            ),
+            (
+                "prices.yaml",
+                "prices:\n  - date: 2020-01-01\n    commodity: EUR\n    price: 1.1\n    currency: USD\n",
+            ),
+        ],
+    );
+
+    let file = read(&dir).unwrap();
+    let descriptions: Vec<&str> = file
+        .transactions
+        .iter()
+        .map(|t| t.description.as_str())
+        .collect();
+
+    assert_eq!(file.currency, "USD");
+    assert_eq!(file.include, None);
+    assert_eq!(file.accounts.len(), 3);
+    assert_eq!(descriptions, vec!["dinner", "lunch"]);
+    assert_eq!(file.prices.unwrap()[0].price, dec!(1.1));
+}
+
+#[test]
+fn errors_point_into_included_file() {
+    let dir = write_files(
+        "rust_ledger_include_errors",
+        &[
+            (
+                "ledger.yaml",
+                "currency: USD\ninclude: [accounts.yaml, 2020.yaml]\n",
+            ),
+            ("accounts.yaml", ACCOUNTS),
+            (
+                "2020.yaml",
+                "transactions:
+  - date: 2020-01-01
+    description: dinner
+    account: expense:dining
+    offset_account: asset:cash
+    amount: 20
+",
+            ),
+        ],
+    );
+
+    let err = read(&dir).unwrap_err().to_string();
+    assert!(err.contains("expense:dining"));
+    assert!(err.contains(&format!("--> {}:4:14", dir.join("2020.yaml").display())));
+
+    fs::write(dir.join("2020.yaml"), "currency: EUR\n").unwrap();
+    let err = read(&dir).unwrap_err().to_string();
+    assert!(err.contains("currency EUR differs from currency USD of"));
+    assert!(err.contains(&format!("--> {}:1:11", dir.join("2020.yaml").display())));
+
+    fs::write(dir.join("2020.yaml"), "transactions: [\n").unwrap();
+    let err = read(&dir).unwrap_err().to_string();
+    assert!(err.contains(&format!("--> {}:2:1", dir.join("2020.yaml").display())));
+
+    fs::remove_file(dir.join("2020.yaml")).unwrap();
+    let err = read(&dir).unwrap_err().to_string();
+    assert!(err.contains(&format!("cannot read {}", dir.join("2020.yaml").display())));
+    assert!(err.contains(&format!("--> {}:2:26", dir.join("ledger.yaml").display())));
+}
+
+#[test]
+fn include_cycles_are_detected() {
+    let dir = write_files(
+        "rust_ledger_include_cycle",
+        &[
+            ("ledger.yaml", "currency: USD\ninclude: [accounts.yaml]\n"),
+            ("accounts.yaml", "include: [\"led*.yaml\"]\n"),
+        ],
+    );
+
+    let err = read(&dir).unwrap_err().to_string();
+    assert!(err.contains(&format!(
+        "include cycle: {} -> {} -> {}",
+        dir.join("ledger.yaml").display(),
+        dir.join("accounts.yaml").display(),
+        dir.join("ledger.yaml").display()
+    )));
+    assert!(err.contains(&format!("--> {}:1:11", dir.join("accounts.yaml").display())));
+
+    fs::write(dir.join("accounts.yaml"), ACCOUNTS).unwrap();
+    fs::write(
+        dir.join("ledger.yaml"),
+        "currency: USD\ninclude: [accounts.yaml, \"*s.yaml\"]\n",
+    )
+    .unwrap();
+    let err = read(&dir).unwrap_err().to_string();
+    assert!(err.contains("accounts.yaml is included more than once"));
+}
+
+#[test]
+fn parse_rejects_includes() {
+    let err = LedgerFile::parse("currency: USD\ninclude: [accounts.yaml]\n").unwrap_err();
+    assert!(err.to_string().contains("include is only supported"));
+
+    let err = LedgerFile::parse("accounts: []\ntransactions: []\n").unwrap_err();
+    assert!(err.to_string().contains("missing field `currency`"));
+}
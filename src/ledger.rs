@@ -1,42 +1,198 @@
-use chrono::NaiveDate;
-use prettytable::{format, Table};
-use rusty_money::{iso, Money};
+use crate::account_tree::{AccountTree, AccountType, Layout};
+use crate::commodity::{Commodity, Totals, Unit};
+use crate::error::{Diagnostic, Error, Result};
+use crate::import::categorize::{Assigned, Candidate, Categorizer, Fallback, Rule};
+use crate::include;
+use crate::payee::{PayeeAlias, Payees};
+use crate::period::{DateRange, Period};
+use crate::price::{Price, Valuation};
+use crate::query::{Matcher, Pivot, Query};
+use crate::report::{
+    AccountList, AccountPosting, AccountRegister, BalanceReport, BalanceRow, BudgetReport,
+    BudgetRow, ColumnReport, ColumnRow, PayeeReport, PayeeRow, PeriodReport, PeriodRow,
+    RegisterReport, RegisterRow, RuleTestReport, RuleTestRow, Statement, StatementSection,
+};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
 use serde::{de, Deserialize, Deserializer, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 use std::str::FromStr;
 
 /// root data structure that contains the deserialized `LedgerFile` data
 /// and associated structs
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct LedgerFile {
+    /// files merged into this one, as paths or glob patterns relative to
+    /// the file. only set on ledger files that have not been read yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// default currency. files included by another one may leave it out
+    #[serde(default)]
     pub currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commodities: Option<Vec<Commodity>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prices: Option<Vec<Price>>,
+    #[serde(default)]
     pub accounts: Vec<Account>,
+    #[serde(default)]
     pub transactions: Vec<Transaction>,
+    /// categorization rules applied to imported transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<Rule>>,
+    /// canonical payees of the names banks give them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee_aliases: Option<Vec<PayeeAlias>>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub account: String,
-    pub amount: f64,
-    pub budget_month: Option<f64>,
-    pub budget_year: Option<f64>,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_month: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_year: Option<Decimal>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     #[serde(deserialize_with = "deserialize_date_from_str")]
     pub date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub account: Option<String>,
-    pub amount: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
     pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub offset_account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assert_balance: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transactions: Option<Vec<TransactionList>>,
+    /// identifier assigned by the bank or statement the transaction was
+    /// imported from, used to recognize transactions imported before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    /// merchant or person on the other side of the transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+    /// labels such as `reimbursable`, or `name:value` pairs such as
+    /// `trip:japan-2024`. the postings of the transaction inherit them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// key/value pairs such as `receipt: scans/123.pdf`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    /// check number or other reference of the transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// clearing state of a transaction or posting, marked by `*` and `!` in
+/// ledger journals
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Cleared,
+    Pending,
+}
+
+/// key/value metadata of transactions and postings. values are read as the
+/// text of any yaml scalar, so e.g. `invoice: 123` does not have to be quoted
+/// and `rate: 1.50` keeps its trailing zero
+pub type Meta = BTreeMap<String, String>;
+
+/// account that postings without a value of the pivot are grouped under
+const UNPIVOTED: &str = "(none)";
+
+/// flattened posting as a detailed transaction with a single posting
+fn single_posting(p: Transaction) -> Transaction {
+    Transaction {
+        transactions: Some(vec![TransactionList {
+            account: p.account.clone().unwrap_or_default(),
+            amount: p.amount.unwrap_or(Decimal::ZERO),
+            currency: p.currency.clone(),
+            assert_balance: None,
+            status: None,
+            comment: None,
+            tags: None,
+            meta: None,
+        }]),
+        account: None,
+        amount: None,
+        currency: None,
+        offset_account: None,
+        assert_balance: None,
+        ..p
+    }
+}
+
+/// tags of a posting, following the tags of its transaction
+fn inherit_tags(
+    transaction: &Option<Vec<String>>,
+    posting: Option<Vec<String>>,
+) -> Option<Vec<String>> {
+    let mut tags = transaction.clone();
+    for tag in posting.into_iter().flatten() {
+        let tags = tags.get_or_insert_with(Vec::new);
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// metadata of a posting, merged into the metadata of its transaction
+fn inherit_meta(transaction: &Option<Meta>, posting: Option<Meta>) -> Option<Meta> {
+    match (transaction.clone(), posting) {
+        (Some(mut meta), Some(posting)) => {
+            meta.extend(posting);
+            Some(meta)
+        }
+        (meta, None) => meta,
+        (None, posting) => posting,
+    }
+}
+
+/// problem of an exchange between two currencies at the rate its postings
+/// imply, i.e. one currency must be given for the other
+fn unbalanced_implied_exchange(totals: &Totals, context: &str) -> Option<String> {
+    let amounts: Vec<&Decimal> = totals.values().collect();
+    let balances = match amounts[..] {
+        [a, b] if a.is_zero() || b.is_zero() => a.is_zero() && b.is_zero(),
+        [a, b] => a.is_sign_positive() != b.is_sign_positive(),
+        _ => true,
+    };
+    if balances {
+        return None;
+    }
+
+    let sums: Vec<String> = totals
+        .iter()
+        .map(|(code, total)| format!("{} {}", total, code))
+        .collect();
+    Some(format!(
+        "{} does not balance: postings sum to {}",
+        context,
+        sums.join(" and ")
+    ))
 }
 
 /// chrono::NaiveDate implements std::str::FromStr, so this is a generic
 /// deserializer fn that can deserialize YAML strings into the NaiveDate struct
-fn deserialize_date_from_str<'de, S, D>(deserializer: D) -> Result<S, D::Error>
+pub(crate) fn deserialize_date_from_str<'de, S, D>(
+    deserializer: D,
+) -> std::result::Result<S, D::Error>
 where
     S: FromStr,
     S::Err: Display,
@@ -49,15 +205,46 @@ where
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct TransactionList {
     pub account: String,
-    pub amount: f64,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assert_balance: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// tags of the posting, in addition to those of its transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// key/value pairs of the posting, which take precedence over those of
+    /// its transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+}
+
+/// single posting of a transaction. abbreviated transactions consist of a
+/// posting to `account` and an offsetting posting to `offset_account`,
+/// detailed transactions of one posting per entry of `transactions`
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Posting {
+    pub date: NaiveDate,
+    pub description: String,
+    pub account: String,
+    pub amount: Decimal,
+    pub currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assert_balance: Option<Decimal>,
 }
 
 /// enumerates all possible `group` values for pattern matching
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum Group {
     Monthly,
     Yearly,
     Daily,
+    Weekly,
+    Quarterly,
     None,
 }
 
@@ -67,7 +254,8 @@ pub enum Group {
 struct OptionalKeys {
     account: String,
     offset_account: String,
-    amount: f64,
+    amount: Decimal,
+    currency: String,
     transactions: Vec<TransactionList>,
 }
 
@@ -83,384 +271,1483 @@ impl OptionalKeys {
             Some(name) => name.to_string(),
         };
 
-        let amount = transaction.amount.unwrap_or(0.00);
+        let amount = transaction.amount.unwrap_or(Decimal::ZERO);
 
-        let transactions = match transaction.transactions.clone() {
-            None => vec![],
-            Some(list) => list,
-        };
+        let currency = transaction.currency.clone().unwrap_or_default();
+
+        let transactions = transaction.transactions.clone().unwrap_or_default();
 
         Self {
             account,
             offset_account,
             amount,
+            currency,
             transactions,
         }
     }
 }
 
 /// data structure for maintaining summarized register data
-/// via 2D HashMap. The HashMap is keyed by date and the related
-/// values are a HashMap keyed by Account and the related
-/// values are cumulative totals.
-/// e.g. Key: "2020-01-01" -> <Key: "expense:general", Value: 100.00>
+/// via 2D BTreeMap. The BTreeMap is keyed by period and the related
+/// values are a BTreeMap keyed by Account and the related
+/// values are cumulative totals per currency.
+/// e.g. Key: 2020-01 -> <Key: "expense:general", Value: <"USD", 100.00>>
 #[derive(Debug, PartialEq)]
 struct GroupMap {
-    group_map: HashMap<String, HashMap<String, f64>>,
+    group_map: BTreeMap<Period, BTreeMap<String, Totals>>,
 }
 
 impl GroupMap {
     fn new() -> GroupMap {
         GroupMap {
-            group_map: HashMap::new(),
+            group_map: BTreeMap::new(),
+        }
+    }
+
+    /// totals of `postings` per period of `group`
+    fn from_postings(postings: Vec<Transaction>, group: Group) -> GroupMap {
+        let mut group_map = GroupMap::new();
+
+        for posting in postings {
+            let OptionalKeys {
+                amount,
+                account,
+                currency,
+                transactions,
+                ..
+            } = OptionalKeys::match_optional_keys(&posting);
+
+            if let Some(period) = Period::containing(posting.date, group) {
+                group_map.populate_group_map(period, account, amount, currency, transactions);
+            }
         }
+
+        group_map
+    }
+
+    fn add_amount(&mut self, period: Period, account: String, currency: String, amount: Decimal) {
+        *self
+            .group_map
+            .entry(period)
+            .or_default()
+            .entry(account)
+            .or_default()
+            .entry(currency)
+            .or_default() += amount;
     }
 
     fn populate_group_map(
         &mut self,
-        date_string: String,
+        period: Period,
         account: String,
-        amount: f64,
+        amount: Decimal,
+        currency: String,
         transactions: Vec<TransactionList>,
     ) {
-        if amount != 0.00 && !account.is_empty() {
-            let account_ref = self.group_map.get_mut(&date_string);
-            if let Some(r) = account_ref {
-                *r.entry(account).or_insert(0.00) += amount;
-            } else {
-                let mut account_map = HashMap::new();
-                account_map.insert(account, amount);
-                self.group_map.insert(date_string.clone(), account_map);
-            }
+        if !amount.is_zero() && !account.is_empty() {
+            self.add_amount(period, account, currency, amount);
         } else {
             for t in transactions {
-                let account_ref = self.group_map.get_mut(&date_string);
-                if let Some(r) = account_ref {
-                    *r.entry(t.account).or_insert(0.00) += amount;
-                } else {
-                    let mut account_map = HashMap::new();
-                    account_map.insert(t.account, t.amount);
-                    self.group_map.insert(date_string.clone(), account_map);
+                let currency = t.currency.unwrap_or_else(|| currency.clone());
+                self.add_amount(period, t.account, currency, t.amount);
+            }
+        }
+    }
+
+    /// every period from the first through the last period that holds
+    /// amounts, including the periods in between that do not. bounds of
+    /// `range` extend the periods to cover the whole range
+    fn periods(&self, range: &DateRange) -> Vec<Period> {
+        match (self.group_map.keys().next(), self.group_map.keys().last()) {
+            (Some(first), Some(last)) => Period::range(
+                range.begin.unwrap_or(first.start),
+                range.end.unwrap_or(last.end),
+                first.group,
+            ),
+            _ => Vec::new(),
+        }
+    }
+
+    /// amounts of each account and currency for each of `periods`, with
+    /// zero for periods without any
+    fn columns(&self, periods: &[Period]) -> BTreeMap<(String, String), Vec<Decimal>> {
+        let mut columns: BTreeMap<(String, String), Vec<Decimal>> = BTreeMap::new();
+
+        for (i, period) in periods.iter().enumerate() {
+            for (account, totals) in self.group_map.get(period).into_iter().flatten() {
+                for (code, amount) in totals {
+                    columns
+                        .entry((account.clone(), code.clone()))
+                        .or_insert_with(|| vec![Decimal::ZERO; periods.len()])[i] += *amount;
                 }
             }
         }
+
+        columns
     }
 }
 
 impl LedgerFile {
-    /// obtain ISO 4217 currency for reference
-    fn get_currency(&self) -> &'static rusty_money::iso::Currency {
-        let c = &self.currency;
+    /// deserialize the ledger file located at `filename`, merged with the
+    /// files it includes, and ensure that its transactions, amounts and
+    /// balance assertions are valid. errors point to their position within
+    /// the file they came from
+    pub fn read(filename: &str) -> Result<LedgerFile> {
+        LedgerFile::load(filename, false)
+    }
+
+    /// read a ledger file like `read`, but also require exchanges between
+    /// two currencies to balance at the prices of their date rather than
+    /// at the rate their postings imply
+    pub fn read_strict(filename: &str) -> Result<LedgerFile> {
+        LedgerFile::load(filename, true)
+    }
+
+    fn load(filename: &str, strict_prices: bool) -> Result<LedgerFile> {
+        let source = std::fs::read_to_string(filename)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+
+        include::load(filename, filename, &source, strict_prices)
+    }
+
+    /// serialize into the yaml format of ledger files
+    pub fn to_yaml(&self) -> Result<String> {
+        crate::yaml::to_string(self)
+    }
+
+    /// write the ledger file to `filename` in yaml format
+    pub fn write(&self, filename: &str) -> Result<()> {
+        std::fs::write(filename, self.to_yaml()?)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+
+        Ok(())
+    }
+
+    /// deserialize and validate the contents of a ledger file. includes
+    /// are resolved relative to the file, so they can only be read with
+    /// `LedgerFile::read`
+    pub fn parse(source: &str) -> Result<LedgerFile> {
+        let ledger_file: LedgerFile = serde_yaml::from_str(source)?;
+
+        if ledger_file.include.is_some() {
+            return Err(Error::Validation(vec![Diagnostic::new(
+                "include is only supported in ledger files read from disk".to_string(),
+                "include",
+            )]));
+        }
+        ledger_file.validate(false)?;
+
+        Ok(ledger_file)
+    }
+
+    /// ensure that the ledger file sets a currency and that its
+    /// transactions, amounts and balance assertions are valid. with
+    /// `strict_prices`, every exchange balances at the prices of its date
+    pub(crate) fn validate(&self, strict_prices: bool) -> Result<()> {
+        if self.currency.is_empty() {
+            return Err(Error::Validation(vec![Diagnostic::new(
+                "missing field `currency`".to_string(),
+                "",
+            )]));
+        }
+
+        self.validate_transactions(strict_prices)?;
+        self.validate_rules()?;
+        self.validate_payee_aliases()?;
+        self.validate_amounts()?;
+        self.validate_assertions()?;
+
+        Ok(())
+    }
+
+    /// verify that the payee patterns of categorization rules are valid
+    /// and that rules assign declared accounts
+    fn validate_rules(&self) -> Result<()> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+
+        for (i, rule) in self.rules.iter().flatten().enumerate() {
+            if let Err(e) = rule.payee_matcher() {
+                let message = match e {
+                    Error::InvalidArg(message) => message,
+                    e => e.to_string(),
+                };
+                errors.push(Diagnostic::new(
+                    format!("rule {}: {}", rule.label(i), message),
+                    &format!("rules[{}].match.payee", i),
+                ));
+            }
+
+            if let Some(account) = &rule.set.account {
+                if !self.accounts.iter().any(|a| &a.account == account) {
+                    errors.push(Diagnostic::new(
+                        format!(
+                            "rule {} assigns undeclared account {}",
+                            rule.label(i),
+                            account
+                        ),
+                        &format!("rules[{}].set.account", i),
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+
+    /// verify that the patterns of payee aliases are valid
+    fn validate_payee_aliases(&self) -> Result<()> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+
+        for (i, alias) in self.payee_aliases.iter().flatten().enumerate() {
+            for (j, pattern) in alias.patterns.iter().enumerate() {
+                if let Err(e) = Matcher::parse(pattern) {
+                    let message = match e {
+                        Error::InvalidArg(message) => message,
+                        e => e.to_string(),
+                    };
+                    errors.push(Diagnostic::new(
+                        format!("payee alias {}: {}", alias.payee, message),
+                        &format!("payee_aliases[{}].match[{}]", i, j),
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+
+    /// verify that account declarations are unique, that every posting
+    /// references a declared account, that simplified transactions are
+    /// complete and that detailed transactions balance. all problems are
+    /// reported at once, each pointing to the offending transaction
+    fn validate_transactions(&self, strict_prices: bool) -> Result<()> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+        let mut declared: HashSet<&str> = HashSet::new();
+
+        for (i, account) in self.accounts.iter().enumerate() {
+            if !declared.insert(&account.account) {
+                errors.push(Diagnostic::new(
+                    format!("account {} is declared more than once", account.account),
+                    &format!("accounts[{}].account", i),
+                ));
+            }
+        }
+
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            let path = format!("transactions[{}]", i);
+            let context = format!(
+                "transaction {} ({} {})",
+                i + 1,
+                transaction.date,
+                transaction.description
+            );
+            let undeclared = |account: &str, path: String| {
+                if declared.contains(account) {
+                    None
+                } else {
+                    Some(Diagnostic::new(
+                        format!("{} posts to undeclared account {}", context, account),
+                        &path,
+                    ))
+                }
+            };
+
+            match &transaction.transactions {
+                Some(list) => {
+                    let mut totals = Totals::new();
+                    for (j, t) in list.iter().enumerate() {
+                        errors.extend(undeclared(
+                            &t.account,
+                            format!("{}.transactions[{}].account", path, j),
+                        ));
+                        let currency = t.currency.clone().or_else(|| transaction.currency.clone());
+                        *totals
+                            .entry(self.resolve_currency(&currency, &t.account))
+                            .or_default() += t.amount;
+                    }
+
+                    // postings in more than one currency are an exchange.
+                    // exchanges between two currencies balance at the rate
+                    // their postings imply, others at the prices of their date
+                    let unbalanced = match totals.len() {
+                        1 => totals.iter().find(|(_, total)| !total.is_zero()).map(
+                            |(code, total)| {
+                                format!(
+                                    "{} does not balance: postings sum to {} {}",
+                                    context, total, code
+                                )
+                            },
+                        ),
+                        2 if !strict_prices => unbalanced_implied_exchange(&totals, &context),
+                        _ => self.unbalanced_exchange(&totals, transaction.date, &context),
+                    };
+                    if let Some(message) = unbalanced {
+                        errors.push(Diagnostic::new(message, &format!("{}.transactions", path)));
+                    }
+                }
+                None => {
+                    let fields = [
+                        ("account", &transaction.account),
+                        ("offset_account", &transaction.offset_account),
+                    ];
+                    for (field, value) in fields {
+                        let field_path = format!("{}.{}", path, field);
+                        match value {
+                            Some(account) => errors.extend(undeclared(account, field_path)),
+                            None => errors.push(Diagnostic::new(
+                                format!("{} is missing {}", context, field),
+                                &field_path,
+                            )),
+                        }
+                    }
+                    if transaction.amount.is_none() {
+                        errors.push(Diagnostic::new(
+                            format!("{} is missing amount", context),
+                            &path,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+
+    /// problem of an exchange, i.e. a transaction with postings in more
+    /// than one currency. its totals are converted into the ledger
+    /// currency, or else the first of its currencies, at the latest prices
+    /// on or before its date and must sum to zero within the minor unit of
+    /// that currency
+    fn unbalanced_exchange(
+        &self,
+        totals: &Totals,
+        date: NaiveDate,
+        context: &str,
+    ) -> Option<String> {
+        let currency = if totals.contains_key(&self.currency) {
+            self.currency.clone()
+        } else {
+            totals.keys().next()?.clone()
+        };
+        // unknown currencies are reported by `validate_amounts`
+        let exponent = self.get_unit(&currency).ok()?.exponent();
+        let valuation = Valuation {
+            currency,
+            date,
+            prices: Vec::new(),
+        };
+
+        let mut sum = Decimal::ZERO;
+        for (code, total) in totals {
+            match valuation.rate(code, self.prices()) {
+                Ok(rate) => sum += total * rate,
+                Err(_) => {
+                    let codes: Vec<&str> = totals.keys().map(String::as_str).collect();
+                    return Some(format!(
+                        "{} exchanges {} without a price on or before {}",
+                        context,
+                        codes.join(" and "),
+                        date
+                    ));
+                }
+            }
+        }
+
+        let sum = sum.round_dp(exponent);
+        if sum.is_zero() {
+            None
+        } else {
+            Some(format!(
+                "{} does not balance: postings sum to {} {} at the prices of {}",
+                context, sum, valuation.currency, date
+            ))
+        }
+    }
+
+    /// reject unknown currencies and amounts with more decimal places
+    /// than the minor unit of their currency allows, e.g. `2.505` USD
+    fn validate_amounts(&self) -> Result<()> {
+        self.get_unit(&self.currency)
+            .map_err(|e| e.at("currency"))?;
+
+        for (i, price) in self.prices().iter().enumerate() {
+            let path = format!("prices[{}]", i);
+            self.get_unit(&price.commodity)
+                .map_err(|e| e.at(&format!("{}.commodity", path)))?;
+            self.get_unit(&price.currency)
+                .map_err(|e| e.at(&format!("{}.currency", path)))?;
+        }
+
+        let check = |amount: Decimal, code: &str, context: &str, path: String| {
+            let unit = self.get_unit(code).map_err(|e| e.at(&path))?;
+            if amount.normalize().scale() > unit.exponent() {
+                Err(Error::Money(Diagnostic::new(
+                    format!(
+                        "{} has more than {} decimal places for {}: {}",
+                        context,
+                        unit.exponent(),
+                        unit.code(),
+                        amount
+                    ),
+                    &path,
+                )))
+            } else {
+                Ok(())
+            }
+        };
+
+        for (i, account) in self.accounts.iter().enumerate() {
+            let path = format!("accounts[{}]", i);
+            let code = self.resolve_currency(&account.currency, &account.account);
+            let fields = [
+                ("amount", Some(account.amount)),
+                ("budget_month", account.budget_month),
+                ("budget_year", account.budget_year),
+            ];
+            for (field, value) in fields {
+                if let Some(amount) = value {
+                    check(
+                        amount,
+                        &code,
+                        &account.account,
+                        format!("{}.{}", path, field),
+                    )?;
+                }
+            }
+        }
+
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            let path = format!("transactions[{}]", i);
+            let OptionalKeys { account, .. } = OptionalKeys::match_optional_keys(transaction);
+            let code = self.resolve_currency(&transaction.currency, &account);
+            let fields = [
+                ("amount", transaction.amount),
+                ("assert_balance", transaction.assert_balance),
+            ];
+            for (field, value) in fields {
+                if let Some(amount) = value {
+                    let field_path = format!("{}.{}", path, field);
+                    check(amount, &code, &transaction.description, field_path)?;
+                }
+            }
+
+            for (j, t) in transaction.transactions.iter().flatten().enumerate() {
+                let posting_path = format!("{}.transactions[{}]", path, j);
+                let currency = t.currency.clone().or_else(|| transaction.currency.clone());
+                let code = self.resolve_currency(&currency, &t.account);
+                let fields = [
+                    ("amount", Some(t.amount)),
+                    ("assert_balance", t.assert_balance),
+                ];
+                for (field, value) in fields {
+                    if let Some(amount) = value {
+                        let field_path = format!("{}.{}", posting_path, field);
+                        check(amount, &code, &transaction.description, field_path)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// verify `assert_balance` values against the running balance of each
+    /// account. transactions are applied in date order, and the running
+    /// balance starts from the opening `amount` of the account
+    fn validate_assertions(&self) -> Result<()> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+        let mut balances: HashMap<(String, String), Decimal> = HashMap::new();
+
+        for account in &self.accounts {
+            let code = self.resolve_currency(&account.currency, &account.account);
+            balances.insert((account.account.clone(), code), account.amount);
+        }
+
+        let mut order: Vec<usize> = (0..self.transactions.len()).collect();
+        order.sort_by_key(|&i| self.transactions[i].date);
+
+        for i in order {
+            let transaction = &self.transactions[i];
+            let flattened_transactions = self.flatten_transaction(transaction.clone());
+
+            for (j, posting) in flattened_transactions.into_iter().enumerate() {
+                let OptionalKeys {
+                    account,
+                    amount,
+                    currency,
+                    ..
+                } = OptionalKeys::match_optional_keys(&posting);
+
+                let balance = balances
+                    .entry((account.clone(), currency.clone()))
+                    .or_default();
+                *balance += amount;
+
+                let expected = match posting.assert_balance {
+                    Some(e) if e != *balance => e,
+                    _ => continue,
+                };
+
+                let path = match transaction.transactions {
+                    Some(_) => format!("transactions[{}].transactions[{}].assert_balance", i, j),
+                    None => format!("transactions[{}].assert_balance", i),
+                };
+
+                errors.push(Diagnostic::new(
+                    format!(
+                        "balance assertion failed on {} {}: expected {} {} to be {} but was {}",
+                        posting.date, posting.description, account, currency, expected, balance
+                    ),
+                    &path,
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+
+    /// non-ISO 4217 commodities declared in the ledger file
+    fn commodities(&self) -> &[Commodity] {
+        self.commodities.as_deref().unwrap_or(&[])
+    }
 
-        match iso::find(c) {
-            Some(n) => n,
-            None => rusty_money::iso::USD,
+    /// prices declared in the ledger file
+    fn prices(&self) -> &[Price] {
+        self.prices.as_deref().unwrap_or(&[])
+    }
+
+    /// obtain ISO 4217 currency or declared commodity for reference
+    fn get_unit(&self, code: &str) -> Result<Unit> {
+        Unit::find(code, self.commodities())
+    }
+
+    /// determine the currency of an amount posted to `account`. an explicit
+    /// `currency` takes precedence, followed by the currency declared on the
+    /// account in `accounts` and finally the ledger `currency`
+    pub(crate) fn resolve_currency(&self, currency: &Option<String>, account: &str) -> String {
+        if let Some(c) = currency {
+            return c.to_string();
+        }
+
+        self.accounts
+            .iter()
+            .find(|a| a.account == account)
+            .and_then(|a| a.currency.clone())
+            .unwrap_or_else(|| self.currency.clone())
+    }
+
+    /// convert every amount into the valuation currency using the latest
+    /// price on or before the valuation date. the returned `LedgerFile` is
+    /// denominated entirely in the valuation currency
+    pub fn value(self, valuation: &Valuation) -> Result<LedgerFile> {
+        let unit = self.get_unit(&valuation.currency)?;
+        let prices: Vec<Price> = self
+            .prices()
+            .iter()
+            .chain(&valuation.prices)
+            .cloned()
+            .collect();
+        let mut rates: HashMap<String, Decimal> = HashMap::new();
+
+        // converted amounts are rounded to the minor unit of the valuation
+        // currency so that totals match the amounts that are displayed
+        let mut convert = |amount: Decimal, code: String| -> Result<Decimal> {
+            let rate = match rates.get(&code) {
+                Some(r) => *r,
+                None => {
+                    let r = valuation.rate(&code, &prices)?;
+                    rates.insert(code, r);
+                    r
+                }
+            };
+            Ok((amount * rate).round_dp(unit.exponent()))
+        };
+
+        let mut accounts = Vec::new();
+        for account in &self.accounts {
+            let code = self.resolve_currency(&account.currency, &account.account);
+            accounts.push(Account {
+                account: account.account.clone(),
+                amount: convert(account.amount, code.clone())?,
+                currency: Some(valuation.currency.clone()),
+                budget_month: match account.budget_month {
+                    Some(b) => Some(convert(b, code.clone())?),
+                    None => None,
+                },
+                budget_year: match account.budget_year {
+                    Some(b) => Some(convert(b, code)?),
+                    None => None,
+                },
+            });
+        }
+
+        let mut transactions = Vec::new();
+        for transaction in &self.transactions {
+            let OptionalKeys { account, .. } = OptionalKeys::match_optional_keys(transaction);
+            let code = self.resolve_currency(&transaction.currency, &account);
+
+            let amount = match transaction.amount {
+                Some(a) => Some(convert(a, code.clone())?),
+                None => None,
+            };
+
+            let assert_balance = match transaction.assert_balance {
+                Some(a) => Some(convert(a, code)?),
+                None => None,
+            };
+
+            let list = match &transaction.transactions {
+                Some(list) => {
+                    let mut converted = Vec::new();
+                    for t in list {
+                        let currency = t.currency.clone().or_else(|| transaction.currency.clone());
+                        let code = self.resolve_currency(&currency, &t.account);
+                        converted.push(TransactionList {
+                            account: t.account.clone(),
+                            amount: convert(t.amount, code.clone())?,
+                            currency: Some(valuation.currency.clone()),
+                            assert_balance: match t.assert_balance {
+                                Some(a) => Some(convert(a, code)?),
+                                None => None,
+                            },
+                            status: t.status,
+                            comment: t.comment.clone(),
+                            tags: t.tags.clone(),
+                            meta: t.meta.clone(),
+                        });
+                    }
+                    Some(converted)
+                }
+                None => None,
+            };
+
+            transactions.push(Transaction {
+                amount,
+                assert_balance,
+                currency: Some(valuation.currency.clone()),
+                transactions: list,
+                ..transaction.clone()
+            });
         }
+
+        Ok(LedgerFile {
+            currency: valuation.currency.clone(),
+            accounts,
+            transactions,
+            ..self
+        })
+    }
+
+    /// every posting of every transaction, in the order of the file.
+    /// postings without a currency of their own are resolved to the
+    /// currency of their account or the ledger file
+    pub fn postings(&self) -> Vec<Posting> {
+        self.transactions
+            .iter()
+            .flat_map(|t| self.flatten_transaction(t.clone()))
+            .map(|p| {
+                let OptionalKeys {
+                    account,
+                    amount,
+                    currency,
+                    ..
+                } = OptionalKeys::match_optional_keys(&p);
+
+                Posting {
+                    date: p.date,
+                    description: p.description,
+                    account,
+                    amount,
+                    currency,
+                    assert_balance: p.assert_balance,
+                }
+            })
+            .collect()
     }
 
     /// flatten abbreviated and detailed `LedgerFile` transactions into
     /// a Vec containing individual detailed transactions.
     /// all downstream logic expects this data structure.
-    fn flatten_transactions(self) -> Vec<Transaction> {
+    fn flatten_transactions(mut self) -> Vec<Transaction> {
         let mut flattened_transactions: Vec<Transaction> = Vec::new();
 
-        for t in self.transactions {
-            let OptionalKeys { amount, .. } = OptionalKeys::match_optional_keys(&t);
-            match t.transactions {
-                Some(subt) => {
-                    for s in subt {
-                        flattened_transactions.push(Transaction {
-                            date: t.date,
-                            account: Some(s.account),
-                            amount: Some(s.amount),
-                            transactions: None,
-                            description: t.description.clone(),
-                            offset_account: None,
-                        });
-                    }
-                }
-                None => {
-                    // push entry
-                    flattened_transactions.push(Transaction {
-                        account: t.account.clone(),
-                        offset_account: None,
-                        amount: t.amount,
-                        ..t.clone()
-                    });
+        for t in std::mem::take(&mut self.transactions) {
+            flattened_transactions.extend(self.flatten_transaction(t));
+        }
+        flattened_transactions
+    }
 
-                    // push offset entry
+    /// flatten a single abbreviated or detailed transaction into
+    /// its individual postings
+    pub(crate) fn flatten_transaction(&self, t: Transaction) -> Vec<Transaction> {
+        let mut flattened_transactions: Vec<Transaction> = Vec::new();
+        let OptionalKeys {
+            amount, account, ..
+        } = OptionalKeys::match_optional_keys(&t);
+
+        match t.transactions {
+            Some(subt) => {
+                for s in subt {
+                    let currency = s.currency.or_else(|| t.currency.clone());
                     flattened_transactions.push(Transaction {
-                        account: t.offset_account,
+                        date: t.date,
+                        currency: Some(self.resolve_currency(&currency, &s.account)),
+                        account: Some(s.account),
+                        amount: Some(s.amount),
+                        transactions: None,
+                        external_id: t.external_id.clone(),
+                        payee: t.payee.clone(),
+                        tags: inherit_tags(&t.tags, s.tags),
+                        meta: inherit_meta(&t.meta, s.meta),
+                        status: s.status.or(t.status),
+                        code: t.code.clone(),
+                        comment: s.comment.or_else(|| t.comment.clone()),
+                        description: t.description.clone(),
                         offset_account: None,
-                        amount: Some(amount * -1.00),
-                        ..t
+                        assert_balance: s.assert_balance,
                     });
                 }
             }
+            None => {
+                // both entries share the currency of the primary account
+                let currency = Some(self.resolve_currency(&t.currency, &account));
+
+                // push entry
+                flattened_transactions.push(Transaction {
+                    account: t.account.clone(),
+                    offset_account: None,
+                    amount: t.amount,
+                    currency: currency.clone(),
+                    ..t.clone()
+                });
+
+                // push offset entry. balance assertions on simplified
+                // transactions apply to the primary account only
+                flattened_transactions.push(Transaction {
+                    account: t.offset_account,
+                    offset_account: None,
+                    amount: Some(-amount),
+                    currency,
+                    assert_balance: None,
+                    ..t
+                });
+            }
         }
         flattened_transactions
     }
 
-    /// filter transactions by option. Downstream logic pairs this with
-    /// the "group" argument for more extensive filtering
-    fn filter_transactions_by_option(self, option: &str) -> Vec<Transaction> {
+    /// keep only the postings that match `query`, each as a transaction
+    /// of its own. opening balances count as postings dated before any
+    /// transaction, described as "opening balance". accounts without any
+    /// matching posting are dropped. all commands other than `payees`,
+    /// which counts whole transactions, filter by query through this fn
+    pub fn filter_by_query(self, query: &Query) -> LedgerFile {
+        if query.is_any() {
+            return self;
+        }
+
+        let payees = Payees::new(&self);
+        let mut accounts = self.accounts.clone();
+        let mut matched = HashSet::new();
+        for account in &mut accounts {
+            let opening = Transaction {
+                date: chrono::naive::MIN_DATE,
+                account: Some(account.account.clone()),
+                amount: Some(account.amount),
+                currency: Some(self.resolve_currency(&account.currency, &account.account)),
+                description: "opening balance".to_string(),
+                offset_account: None,
+                assert_balance: None,
+                transactions: None,
+                external_id: None,
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
+            };
+            if query.matches(&opening, &payees) {
+                matched.insert(account.account.clone());
+            } else {
+                account.amount = Decimal::ZERO;
+            }
+        }
+
+        let transactions: Vec<Transaction> = LedgerFile::flatten_transactions(self.clone())
+            .into_iter()
+            .filter(|p| query.matches(p, &payees))
+            .map(single_posting)
+            .collect();
+
+        for t in &transactions {
+            for posting in t.transactions.iter().flatten() {
+                matched.insert(posting.account.clone());
+            }
+        }
+        accounts.retain(|a| matched.contains(&a.account));
+
+        LedgerFile {
+            accounts,
+            transactions,
+            ..self
+        }
+    }
+
+    /// replace the account of every posting with its value of `pivot`, so
+    /// that reports group postings by e.g. the values of a tag rather than
+    /// by account. postings without a value, along with the opening
+    /// balances of accounts, are grouped under `(none)`
+    pub fn pivot(self, pivot: &Pivot) -> LedgerFile {
+        let mut accounts: Vec<Account> = Vec::new();
+        for account in self.accounts.iter().filter(|a| !a.amount.is_zero()) {
+            let currency = self.resolve_currency(&account.currency, &account.account);
+            match accounts
+                .iter_mut()
+                .find(|a| a.currency.as_ref() == Some(&currency))
+            {
+                Some(a) => a.amount += account.amount,
+                None => accounts.push(Account {
+                    account: UNPIVOTED.to_string(),
+                    amount: account.amount,
+                    currency: Some(currency),
+                    budget_month: None,
+                    budget_year: None,
+                }),
+            }
+        }
+
+        let payees = Payees::new(&self);
+        let transactions: Vec<Transaction> = LedgerFile::flatten_transactions(self.clone())
+            .into_iter()
+            .map(|p| Transaction {
+                account: Some(
+                    pivot
+                        .value(&p, &payees)
+                        .unwrap_or_else(|| UNPIVOTED.to_string()),
+                ),
+                ..p
+            })
+            .map(single_posting)
+            .collect();
+
+        for t in &transactions {
+            for posting in t.transactions.iter().flatten() {
+                if !accounts.iter().any(|a| a.account == posting.account) {
+                    accounts.push(Account {
+                        account: posting.account.clone(),
+                        amount: Decimal::ZERO,
+                        currency: None,
+                        budget_month: None,
+                        budget_year: None,
+                    });
+                }
+            }
+        }
+
+        LedgerFile {
+            accounts,
+            transactions,
+            ..self
+        }
+    }
+
+    /// postings of income statement accounts. budgets are limited to a
+    /// date range by `filter_by_range` beforehand
+    fn filter_income_expense_transactions(self) -> Vec<Transaction> {
         let flattened_transactions = LedgerFile::flatten_transactions(self);
 
         flattened_transactions
             .into_iter()
-            .filter(|x| match option {
-                "" => true,
-                _ => {
-                    let OptionalKeys {
-                        account,
-                        offset_account,
-                        amount,
-                        ..
-                    } = OptionalKeys::match_optional_keys(x);
-
-                    x.date.to_string().contains(option)
-                        || amount.to_string().contains(option)
-                        || account.contains(option)
-                        || offset_account.contains(option)
-                        || x.description.contains(option)
-                }
+            .filter(|x| {
+                let OptionalKeys { account, .. } = OptionalKeys::match_optional_keys(x);
+                matches!(
+                    AccountType::of(&account),
+                    Some(AccountType::Income | AccountType::Expense)
+                )
             })
             .collect()
     }
-    /// filters all income statement transactions by option
-    fn filter_income_expense_transactions(self, option: &str, group: &Group) -> Vec<Transaction> {
-        let flattened_transactions = LedgerFile::flatten_transactions(self);
 
-        flattened_transactions
-            .into_iter()
-            .filter(|x| {
-                let filter_period = match group {
-                    Group::Yearly => "%Y",
-                    Group::Monthly => "%m",
-                    Group::Daily => "%d",
-                    Group::None => "%Y",
-                };
-                x.date.format(filter_period).to_string() == option
+    pub fn accounts_report(self) -> AccountList {
+        AccountList {
+            accounts: self.accounts.into_iter().map(|a| a.account).collect(),
+        }
+    }
+
+    /// keep only the transactions dated within `range`. opening balances
+    /// precede every transaction, so they are dropped once the range has
+    /// a `begin` date. all commands filter by date through this fn
+    pub fn filter_by_range(mut self, range: &DateRange) -> LedgerFile {
+        self.transactions.retain(|t| range.contains(t.date));
+
+        if range.begin.is_some() {
+            for account in &mut self.accounts {
+                account.amount = Decimal::ZERO;
+            }
+        }
+
+        self
+    }
+
+    /// totals of every account, including opening balances
+    pub fn account_tree(&self) -> AccountTree {
+        let mut tree = AccountTree::new();
+
+        for account in &self.accounts {
+            tree.add(
+                &account.account,
+                &self.resolve_currency(&account.currency, &account.account),
+                account.amount,
+            );
+        }
+
+        // loop over postings and increment(+)/decrement(-) totals
+        // of each account in the currency of the posting
+        for posting in self.postings() {
+            tree.add(&posting.account, &posting.currency, posting.amount);
+        }
+
+        tree
+    }
+
+    /// balance of every account. in the `Tree` layout parent accounts are
+    /// listed with the totals of their sub-accounts, in the `Flat` layout
+    /// only the accounts themselves are listed. accounts deeper than
+    /// `depth` are collapsed into their parent
+    pub fn balance_report(self, layout: Layout, depth: Option<usize>) -> BalanceReport {
+        let commodities = self.commodities().to_vec();
+        let tree = self.account_tree();
+
+        let rows = match layout {
+            Layout::Tree => tree.tree_rows(depth),
+            Layout::Flat => tree.flat_rows(depth),
+        };
+
+        BalanceReport {
+            layout,
+            commodities,
+            rows: rows
+                .into_iter()
+                .flat_map(|r| {
+                    BalanceRow::from_totals(&r.account, &r.name, r.depth, r.totals, false)
+                })
+                .collect(),
+            check: tree.totals(),
+        }
+    }
+
+    /// assets, liabilities and equity as of `date` along with net worth.
+    /// income and expenses are rolled into equity as retained earnings of
+    /// prior years and earnings of the current year
+    pub fn balance_sheet_report(self, date: NaiveDate, depth: Option<usize>) -> Statement {
+        let commodities = self.commodities().to_vec();
+        let year_start = NaiveDate::from_ymd(date.year(), 1, 1);
+        let prior_tree = self
+            .clone()
+            .filter_by_range(&DateRange {
+                begin: None,
+                end: year_start.pred_opt(),
             })
-            .filter(|x| {
-                let OptionalKeys { account, .. } = OptionalKeys::match_optional_keys(x);
-                account.contains("income") || account.contains("expense")
+            .account_tree();
+        let tree = self
+            .filter_by_range(&DateRange {
+                begin: None,
+                end: Some(date),
             })
-            .collect()
-    }
+            .account_tree();
 
-    pub fn print_accounts(self) {
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row!["Account"]);
+        let earnings = |tree: &AccountTree| {
+            let mut totals = tree.section(AccountType::Income).totals();
+            for (code, amount) in tree.section(AccountType::Expense).totals() {
+                *totals.entry(code).or_default() += amount;
+            }
+            totals
+        };
+        let retained_earnings = earnings(&prior_tree);
+        let mut current_earnings = earnings(&tree);
+        for (code, amount) in &retained_earnings {
+            *current_earnings.entry(code.clone()).or_default() -= *amount;
+        }
 
-        for account in self.accounts {
-            table.add_row(row![account.account]);
+        let mut equity = tree.section(AccountType::Equity);
+        for (code, amount) in &retained_earnings {
+            if !amount.is_zero() {
+                equity.add("equity:retained earnings", code, *amount);
+            }
+        }
+        for (code, amount) in &current_earnings {
+            if !amount.is_zero() {
+                equity.add("equity:current year earnings", code, *amount);
+            }
         }
-        table.printstd();
-    }
 
-    pub fn print_balances(self) {
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row!["Account", "Balance"]);
+        let assets = tree.section(AccountType::Asset);
+        let liabilities = tree.section(AccountType::Liability);
 
-        let currency_code = self.get_currency();
-        let mut accounts_vec: Vec<Account> = Vec::new();
-        let mut transactions_vec: Vec<Account> = Vec::new();
+        let mut net_worth = assets.totals();
+        for (code, amount) in liabilities.totals() {
+            *net_worth.entry(code).or_default() += amount;
+        }
 
-        // push opening balances into Vec
-        for account in &self.accounts {
-            accounts_vec.push(Account {
-                account: account.account.to_owned(),
-                amount: account.amount.to_owned(),
-                budget_month: None,
-                budget_year: None,
-            });
+        Statement {
+            heading: "Balance".to_string(),
+            commodities,
+            sections: vec![
+                LedgerFile::statement_section("assets", &assets, AccountType::Asset, depth),
+                LedgerFile::statement_section(
+                    "liabilities",
+                    &liabilities,
+                    AccountType::Liability,
+                    depth,
+                ),
+                LedgerFile::statement_section("equity", &equity, AccountType::Equity, depth),
+            ],
+            summary: "net worth".to_string(),
+            total: net_worth,
         }
+    }
 
-        let flattened_transactions = LedgerFile::flatten_transactions(self);
+    /// income and expenses along with the resulting net income
+    pub fn income_statement_report(self, depth: Option<usize>) -> Statement {
+        let commodities = self.commodities().to_vec();
+        let tree = self.account_tree();
+        let income = tree.section(AccountType::Income);
+        let expenses = tree.section(AccountType::Expense);
+
+        // income is stored as negative amounts, so net income is the
+        // negated sum of both sections
+        let mut net_income = Totals::new();
+        for (code, amount) in income.totals().into_iter().chain(expenses.totals()) {
+            *net_income.entry(code).or_default() -= amount;
+        }
 
-        // push transactions into Vec
-        for transaction in flattened_transactions {
-            let OptionalKeys {
-                account, amount, ..
-            } = OptionalKeys::match_optional_keys(&transaction);
+        Statement {
+            heading: "Amount".to_string(),
+            commodities,
+            sections: vec![
+                LedgerFile::statement_section("income", &income, AccountType::Income, depth),
+                LedgerFile::statement_section("expenses", &expenses, AccountType::Expense, depth),
+            ],
+            summary: "net income".to_string(),
+            total: net_income,
+        }
+    }
 
-            transactions_vec.push(Account {
-                account,
-                amount,
-                budget_month: None,
-                budget_year: None,
-            });
+    /// the accounts of `section` as a tree under `title`, along with the
+    /// section total. credit balances are negated for display
+    fn statement_section(
+        title: &str,
+        section: &AccountTree,
+        account_type: AccountType,
+        depth: Option<usize>,
+    ) -> StatementSection {
+        let credit = account_type.is_credit();
+
+        StatementSection {
+            title: title.to_string(),
+            rows: section
+                .tree_rows(depth)
+                .into_iter()
+                .flat_map(|r| {
+                    BalanceRow::from_totals(&r.account, &r.name, r.depth, r.totals, credit)
+                })
+                .collect(),
+            total: section
+                .totals()
+                .into_iter()
+                .map(|(code, amount)| (code, if credit { -amount } else { amount }))
+                .collect(),
         }
+    }
 
-        // loop over Vecs and increment(+)/decrement(-) totals
-        // for each transaction
-        for transaction in &transactions_vec {
-            for account in &mut accounts_vec {
-                if account.account.eq_ignore_ascii_case(&transaction.account) {
-                    account.amount += &transaction.amount;
+    /// totals of each account per period of `group`
+    pub fn register_period_report(self, group: Group) -> PeriodReport {
+        let commodities = self.commodities().to_vec();
+        let group_map = GroupMap::from_postings(LedgerFile::flatten_transactions(self), group);
+
+        let mut rows = Vec::new();
+        for (period, account_map) in group_map.group_map {
+            for (account, totals) in account_map {
+                for (currency, amount) in totals {
+                    rows.push(PeriodRow {
+                        period: period.to_string(),
+                        account: account.clone(),
+                        currency,
+                        amount,
+                    });
                 }
             }
         }
 
-        // create output
-        let mut check_figure: f64 = 0.0;
-        let mut current_account_type = String::new();
+        PeriodReport { commodities, rows }
+    }
+
+    /// totals of each account with the periods of `group` that span
+    /// `range` as columns
+    pub fn register_column_report(self, group: Group, range: &DateRange) -> Result<ColumnReport> {
+        let group_map =
+            GroupMap::from_postings(LedgerFile::flatten_transactions(self.clone()), group);
+
+        self.column_report(group_map, group, range, false)
+    }
+
+    pub fn register_report(self) -> RegisterReport {
+        let commodities = self.commodities().to_vec();
+        let rows = LedgerFile::flatten_transactions(self)
+            .into_iter()
+            .map(|t| {
+                let OptionalKeys {
+                    account,
+                    amount,
+                    currency,
+                    ..
+                } = OptionalKeys::match_optional_keys(&t);
+
+                RegisterRow {
+                    date: t.date,
+                    description: t.description,
+                    account,
+                    currency,
+                    amount,
+                }
+            })
+            .collect();
+
+        RegisterReport { commodities, rows }
+    }
 
-        for account in accounts_vec {
-            check_figure += account.amount;
-            let account_type: Vec<&str> = account.account.split(':').collect();
+    /// number of transactions of every payee, normalized by the payee
+    /// aliases, along with the total of their postings to income and
+    /// expense accounts. only transactions with a posting matching `query`
+    /// are counted, and only matching postings are totaled
+    pub fn payees_report(self, query: &Query) -> PayeeReport {
+        let payees = Payees::new(&self);
+        let mut counts: BTreeMap<String, (usize, Totals)> = BTreeMap::new();
+
+        for t in &self.transactions {
+            let postings: Vec<Transaction> = self
+                .flatten_transaction(t.clone())
+                .into_iter()
+                .filter(|p| query.matches(p, &payees))
+                .collect();
+            if postings.is_empty() {
+                continue;
+            }
 
-            if !current_account_type.eq(account_type[0]) {
-                current_account_type = account_type[0].to_string();
-                table.add_row(row![current_account_type]);
+            let (count, totals) = counts.entry(payees.payee(t)).or_default();
+            *count += 1;
+            for p in postings {
+                let OptionalKeys {
+                    account,
+                    amount,
+                    currency,
+                    ..
+                } = OptionalKeys::match_optional_keys(&p);
+
+                if matches!(
+                    AccountType::of(&account),
+                    Some(AccountType::Income | AccountType::Expense)
+                ) {
+                    *totals.entry(currency).or_default() += amount;
+                }
             }
+        }
 
-            table
-                .add_row(row![r->account.account, Money::from_str(&account.amount.to_string(), currency_code).unwrap()]);
+        let mut rows = Vec::new();
+        for (payee, (count, mut totals)) in counts {
+            if totals.is_empty() {
+                totals.insert(self.currency.clone(), Decimal::ZERO);
+            }
+            for (currency, amount) in totals {
+                rows.push(PayeeRow {
+                    payee: payee.clone(),
+                    transactions: count,
+                    currency,
+                    amount,
+                });
+            }
         }
 
-        table.add_empty_row();
-        table.add_row(row!["check", check_figure]);
-        table.printstd();
+        PayeeReport {
+            commodities: self.commodities().to_vec(),
+            rows,
+        }
     }
 
-    pub fn print_register_group(self, option: &str, group: Group) {
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row!["Date / Account", "Total"]);
+    /// every posting within `range` to `account` or one of its
+    /// sub-accounts, in date order, along with the balance carried into
+    /// `range`. balances start from the opening `amount` of the accounts
+    /// and include every posting dated before `range`
+    fn account_postings(&self, account: &str, range: &DateRange) -> (Totals, Vec<AccountPosting>) {
+        let in_account = |name: &str| {
+            name == account
+                || name
+                    .strip_prefix(account)
+                    .is_some_and(|rest| rest.starts_with(':'))
+        };
 
-        let currency_code = self.get_currency();
-        let mut group_map = GroupMap::new();
-        let filtered_transactions = LedgerFile::filter_transactions_by_option(self, option);
+        let mut balances = Totals::new();
+        for a in self.accounts.iter().filter(|a| in_account(&a.account)) {
+            let code = self.resolve_currency(&a.currency, &a.account);
+            *balances.entry(code).or_default() += a.amount;
+        }
 
-        for transaction in filtered_transactions {
-            let OptionalKeys {
-                amount,
-                account,
-                transactions,
-                ..
-            } = OptionalKeys::match_optional_keys(&transaction);
+        let mut transactions = self.transactions.clone();
+        transactions.sort_by_key(|t| t.date);
+
+        let mut carried = None;
+        let mut postings = Vec::new();
+
+        for transaction in transactions {
+            if range.end.is_some_and(|end| transaction.date > end) {
+                break;
+            }
+            if carried.is_none() && range.contains(transaction.date) {
+                carried = Some(balances.clone());
+            }
 
-            let year = transaction.date.format("%Y").to_string();
-            let month = transaction.date.format("%Y-%m").to_string();
-            let day = transaction.date.format("%Y-%m-%d").to_string();
+            let flattened_transactions = self.flatten_transaction(transaction);
+            let (own, others): (Vec<Transaction>, Vec<Transaction>) = flattened_transactions
+                .into_iter()
+                .partition(|p| in_account(p.account.as_deref().unwrap_or("")));
 
-            match group {
-                Group::Yearly => group_map.populate_group_map(year, account, amount, transactions),
-                Group::Monthly => {
-                    group_map.populate_group_map(month, account, amount, transactions)
+            let mut counter_accounts: Vec<String> = Vec::new();
+            for posting in &others {
+                let OptionalKeys { account, .. } = OptionalKeys::match_optional_keys(posting);
+                if !counter_accounts.contains(&account) {
+                    counter_accounts.push(account);
                 }
-                Group::Daily => group_map.populate_group_map(day, account, amount, transactions),
-                Group::None => (),
             }
-        }
 
-        for (date_string, account_map) in group_map.group_map.iter() {
-            table.add_row(row![date_string]);
-            for (account, amount) in account_map.iter() {
-                table.add_row(row![
+            for posting in own {
+                let OptionalKeys {
                     account,
-                    Money::from_str(&amount.to_string(), currency_code).unwrap()
-                ]);
+                    amount,
+                    currency,
+                    ..
+                } = OptionalKeys::match_optional_keys(&posting);
+
+                let balance = balances.entry(currency.clone()).or_default();
+                *balance += amount;
+
+                if range.contains(posting.date) {
+                    postings.push(AccountPosting {
+                        date: posting.date,
+                        description: posting.description,
+                        account,
+                        counter_accounts: counter_accounts.clone(),
+                        currency,
+                        amount,
+                        balance: Some(*balance),
+                    });
+                }
             }
         }
-        table.printstd();
+
+        (carried.unwrap_or(balances), postings)
     }
 
-    pub fn print_register(self, option: &str) {
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row!["Date", "Description", "Account", "Amount"]);
+    /// categorize a single transaction the way imports do, listing every
+    /// assigned field along with the rule that assigned it, and the
+    /// account suggested by the history of the ledger file
+    pub fn rules_test_report(
+        &self,
+        candidate: &Candidate,
+        fallback: Fallback,
+    ) -> Result<RuleTestReport> {
+        let categorization = Categorizer::new(self, fallback)?.categorize(candidate);
+        let row = |field: &str, assigned: Assigned| RuleTestRow {
+            field: field.to_string(),
+            value: assigned.value,
+            rule: assigned.rule,
+        };
 
-        let currency_code = self.get_currency();
-        let filtered_transactions = LedgerFile::filter_transactions_by_option(self, option);
+        let mut rows = vec![row("account", categorization.account)];
+        rows.extend(categorization.payee.map(|a| row("payee", a)));
+        rows.extend(categorization.description.map(|a| row("description", a)));
+        rows.extend(categorization.tags.into_iter().map(|a| row("tag", a)));
+        rows.extend(categorization.suggestion.map(|s| RuleTestRow {
+            field: "suggestion".to_string(),
+            value: s.account,
+            rule: format!("confidence {:.2}", s.confidence),
+        }));
+
+        Ok(RuleTestReport { rows })
+    }
 
-        for t in filtered_transactions {
-            let OptionalKeys {
-                account, amount, ..
-            } = OptionalKeys::match_optional_keys(&t);
+    /// every posting to `account` and its sub-accounts along with the
+    /// other accounts of the transaction. `running` registers carry the
+    /// balance of the account, starting from the balance carried into
+    /// `range`. `reverse` lists the latest postings first
+    pub fn account_register_report(
+        self,
+        account: &str,
+        range: &DateRange,
+        running: bool,
+        reverse: bool,
+    ) -> Result<AccountRegister> {
+        let (opening, mut postings) = self.account_postings(account, range);
+        if opening.is_empty() && postings.is_empty() {
+            return Err(Error::InvalidArg(format!("unknown account: {}", account)));
+        }
 
-            table.add_row(row![
-                t.date,
-                t.description,
-                account,
-                Money::from_str(&amount.to_string(), currency_code).unwrap()
-            ]);
+        if !running {
+            for posting in &mut postings {
+                posting.balance = None;
+            }
+        }
+        if reverse {
+            postings.reverse();
         }
-        table.printstd();
-    }
 
-    pub fn print_budget_actual(self, option: &str, group: Group) {
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row!["Date / Account", "Budget", "Actual", "Delta"]);
+        Ok(AccountRegister {
+            commodities: self.commodities().to_vec(),
+            reverse,
+            account: account.to_string(),
+            begin: range.begin,
+            opening: if running { Some(opening) } else { None },
+            postings,
+        })
+    }
 
-        let currency_code = self.get_currency();
-        let mut group_map = GroupMap::new();
-        let filtered_transactions =
-            LedgerFile::filter_income_expense_transactions(self.clone(), option, &group);
+    /// budget of `account` in currency `code` for one period of `group`.
+    /// budgets are expressed in the currency of the account and can only
+    /// be set per month or year
+    fn budget(&self, account: &str, code: &str, group: Group) -> Option<Decimal> {
+        let matching_account = self.accounts.iter().find(|x| x.account == account)?;
 
-        for transaction in filtered_transactions {
-            let OptionalKeys {
-                amount,
-                account,
-                transactions,
-                ..
-            } = OptionalKeys::match_optional_keys(&transaction);
+        if code != self.resolve_currency(&matching_account.currency, account) {
+            return None;
+        }
 
-            let year = transaction.date.format("%Y").to_string();
-            let month = transaction.date.format("%Y-%m").to_string();
-            let day = transaction.date.format("%Y-%m-%d").to_string();
+        match group {
+            Group::Yearly => matching_account.budget_year,
+            Group::Quarterly => matching_account.budget_month.map(|b| b * Decimal::from(3)),
+            Group::Monthly => matching_account.budget_month,
+            Group::Weekly | Group::Daily | Group::None => None,
+        }
+    }
 
-            match group {
-                Group::Yearly => group_map.populate_group_map(year, account, amount, transactions),
-                Group::Monthly => {
-                    group_map.populate_group_map(month, account, amount, transactions)
-                }
-                Group::Daily => group_map.populate_group_map(day, account, amount, transactions),
-                Group::None => (),
+    /// accounts as rows and the periods of `group_map` as columns,
+    /// followed by the total and average of each row and a grand total
+    /// row per currency. `with_budget` adds the budget per period of each
+    /// account
+    fn column_report(
+        &self,
+        group_map: GroupMap,
+        group: Group,
+        range: &DateRange,
+        with_budget: bool,
+    ) -> Result<ColumnReport> {
+        let periods = group_map.periods(range);
+        let columns = group_map.columns(&periods);
+
+        // grand totals per currency of each period and the budget
+        let mut grand_totals: BTreeMap<String, (Vec<Decimal>, Decimal)> = BTreeMap::new();
+        let mut rows = Vec::new();
+
+        for ((account, code), amounts) in columns {
+            let budget = self.budget(&account, &code, group);
+
+            let (totals, budget_total) = grand_totals
+                .entry(code.clone())
+                .or_insert_with(|| (vec![Decimal::ZERO; periods.len()], Decimal::ZERO));
+            for (total, amount) in totals.iter_mut().zip(&amounts) {
+                *total += *amount;
             }
+            *budget_total += budget.unwrap_or(Decimal::ZERO);
+
+            rows.push(ColumnRow::new(
+                &account,
+                &self.get_unit(&code)?,
+                amounts,
+                budget,
+            ));
         }
 
-        for (date_string, account_map) in group_map.group_map.iter() {
-            table.add_row(row![date_string]);
-            for (account, amount) in account_map.iter() {
-                let b = &Account {
-                    account: "".to_string(),
-                    amount: 0.0,
-                    budget_month: None,
-                    budget_year: None,
-                };
-                let matching_account = match self.accounts.iter().find(|x| &x.account == account) {
-                    Some(a) => a,
-                    None => b,
-                };
-
-                let budget = match &group {
-                    Group::Yearly => matching_account.budget_year,
-                    Group::Monthly => matching_account.budget_month,
-                    Group::Daily => None, // not possible to set daily budgets
-                    Group::None => None,
+        let currencies = grand_totals.len();
+        let totals = grand_totals
+            .into_iter()
+            .map(|(code, (totals, budget_total))| {
+                let label = match currencies {
+                    1 => "total".to_string(),
+                    _ => format!("total ({})", code),
                 };
+                let unit = self.get_unit(&code)?;
+                Ok(ColumnRow::new(&label, &unit, totals, Some(budget_total)))
+            })
+            .collect::<Result<Vec<ColumnRow>>>()?;
+
+        Ok(ColumnReport {
+            commodities: self.commodities().to_vec(),
+            with_budget,
+            periods: periods.iter().map(|p| p.to_string()).collect(),
+            rows,
+            totals,
+        })
+    }
 
-                let budget_amount = budget.unwrap_or(0.00);
-                let delta = budget_amount - amount;
-
-                table.add_row(row![
-                    account,
-                    Money::from_str(&budget_amount.to_string(), currency_code).unwrap(),
-                    Money::from_str(&amount.to_string(), currency_code).unwrap(),
-                    Money::from_str(&delta.to_string(), currency_code).unwrap(),
-                ]);
+    /// budgeted and actual amounts of income and expense accounts per
+    /// period of `group`
+    pub fn budget_period_report(self, group: Group) -> BudgetReport {
+        let postings = LedgerFile::filter_income_expense_transactions(self.clone());
+        let group_map = GroupMap::from_postings(postings, group);
+
+        let mut rows = Vec::new();
+        for (period, account_map) in &group_map.group_map {
+            for (account, totals) in account_map {
+                for (currency, amount) in totals {
+                    let budget = self
+                        .budget(account, currency, group)
+                        .unwrap_or(Decimal::ZERO);
+                    rows.push(BudgetRow {
+                        period: period.to_string(),
+                        account: account.clone(),
+                        currency: currency.clone(),
+                        budget,
+                        actual: *amount,
+                        delta: budget - amount,
+                    });
+                }
             }
         }
-        table.printstd();
+
+        BudgetReport {
+            commodities: self.commodities().to_vec(),
+            rows,
+        }
+    }
+
+    /// budgeted and actual amounts of income and expense accounts with the
+    /// periods of `group` that span `range` as columns
+    pub fn budget_column_report(self, group: Group, range: &DateRange) -> Result<ColumnReport> {
+        let postings = LedgerFile::filter_income_expense_transactions(self.clone());
+        let group_map = GroupMap::from_postings(postings, group);
+
+        self.column_report(group_map, group, range, true)
     }
 }
 
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
 #[cfg(test)]
 fn get_file() -> LedgerFile {
     let date = match NaiveDate::parse_from_str("2020-01-01", "%Y-%m-%d") {
@@ -469,29 +1756,38 @@ fn get_file() -> LedgerFile {
     };
 
     LedgerFile {
+        include: None,
         currency: "USD".to_string(),
+        commodities: None,
+        prices: None,
+        rules: None,
+        payee_aliases: None,
         accounts: vec![
             Account {
                 account: "asset:cash".to_string(),
-                amount: 100.00,
+                amount: dec!(100.00),
+                currency: None,
                 budget_month: None,
                 budget_year: None,
             },
             Account {
                 account: "expense:foo".to_string(),
-                amount: 0.00,
+                amount: dec!(0.00),
+                currency: None,
                 budget_month: None,
                 budget_year: None,
             },
             Account {
                 account: "expense:bar".to_string(),
-                amount: 0.00,
+                amount: dec!(0.00),
+                currency: None,
                 budget_month: None,
                 budget_year: None,
             },
             Account {
                 account: "expense:baz".to_string(),
-                amount: 0.00,
+                amount: dec!(0.00),
+                currency: None,
                 budget_month: None,
                 budget_year: None,
             },
@@ -500,39 +1796,84 @@ fn get_file() -> LedgerFile {
             Transaction {
                 date,
                 account: Some("asset:cash".to_string()),
-                amount: Some(10.00),
+                amount: Some(dec!(10.00)),
+                currency: None,
+                assert_balance: None,
                 description: "summary_transaction".to_string(),
                 offset_account: Some("expense:foo".to_string()),
                 transactions: None,
+                external_id: None,
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
             },
             Transaction {
                 date,
                 account: Some("asset:cash".to_string()),
-                amount: Some(-42.00),
+                amount: Some(dec!(-42.00)),
+                currency: None,
+                assert_balance: None,
                 description: "summary_transaction".to_string(),
                 offset_account: Some("expense:foo".to_string()),
                 transactions: None,
+                external_id: None,
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
             },
             Transaction {
                 date,
                 account: None,
                 amount: None,
+                currency: None,
+                assert_balance: None,
                 description: "detailed_transaction".to_string(),
                 offset_account: None,
                 transactions: Some(vec![
                     TransactionList {
                         account: "asset:cash".to_string(),
-                        amount: -50.00,
+                        amount: dec!(-50.00),
+                        currency: None,
+                        assert_balance: None,
+                        status: None,
+                        comment: None,
+                        tags: None,
+                        meta: None,
                     },
                     TransactionList {
                         account: "expense:bar".to_string(),
-                        amount: 20.00,
+                        amount: dec!(20.00),
+                        currency: None,
+                        assert_balance: None,
+                        status: None,
+                        comment: None,
+                        tags: None,
+                        meta: None,
                     },
                     TransactionList {
                         account: "expense:baz".to_string(),
-                        amount: 30.00,
+                        amount: dec!(30.00),
+                        currency: None,
+                        assert_balance: None,
+                        status: None,
+                        comment: None,
+                        tags: None,
+                        meta: None,
                     },
                 ]),
+                external_id: None,
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
             },
         ],
     }
@@ -555,7 +1896,8 @@ fn optional_keys() {
         result,
         OptionalKeys {
             account: "asset:cash".to_string(),
-            amount: 10.00,
+            amount: dec!(10.00),
+            currency: "".to_string(),
             offset_account: "expense:foo".to_string(),
             transactions: vec![],
         }
@@ -563,9 +1905,10 @@ fn optional_keys() {
 }
 
 #[test]
-fn filter_transactions_by_option_42() {
+fn filter_by_query_amounts() {
     let file = get_file();
-    let result = LedgerFile::filter_transactions_by_option(file, &"42".to_string());
+    let query = Query::parse("amt:42 or amt:-42").unwrap();
+    let result = LedgerFile::flatten_transactions(file.filter_by_query(&query));
     let date = match NaiveDate::parse_from_str("2020-01-01", "%Y-%m-%d") {
         Ok(d) => d,
         Err(e) => panic!("{:?}", e),
@@ -577,65 +1920,567 @@ fn filter_transactions_by_option_42() {
             Transaction {
                 date,
                 account: Some("asset:cash".to_string()),
-                amount: Some(-42.00),
+                amount: Some(dec!(-42.00)),
+                currency: Some("USD".to_string()),
+                assert_balance: None,
                 description: "summary_transaction".to_string(),
                 offset_account: None,
                 transactions: None,
+                external_id: None,
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
             },
             Transaction {
                 date,
                 account: Some("expense:foo".to_string()),
-                amount: Some(42.00),
+                amount: Some(dec!(42.00)),
+                currency: Some("USD".to_string()),
+                assert_balance: None,
                 description: "summary_transaction".to_string(),
                 offset_account: None,
                 transactions: None,
+                external_id: None,
+                payee: None,
+                tags: None,
+                meta: None,
+                status: None,
+                code: None,
+                comment: None,
             },
         ]
     )
 }
 
+#[test]
+fn filter_by_query_keeps_matching_openings() {
+    let file = get_file();
+    let query = Query::parse("acct:asset").unwrap();
+    let filtered = file.clone().filter_by_query(&query);
+
+    assert_eq!(filtered.accounts[0].amount, dec!(100.00));
+    assert_eq!(filtered.transactions.len(), 3);
+
+    let query = Query::parse("date:2020").unwrap();
+    let filtered = file.filter_by_query(&query);
+    assert_eq!(filtered.accounts[0].amount, Decimal::ZERO);
+    assert_eq!(filtered.transactions.len(), 7);
+}
+
 #[test]
 fn group_map() {
     let file = get_file();
     let mut group_map = GroupMap::new();
-    let filtered_transactions = LedgerFile::filter_transactions_by_option(file, &"42".to_string());
+    let query = Query::parse("amt:42 or amt:-42").unwrap();
+    let filtered_transactions = LedgerFile::flatten_transactions(file.filter_by_query(&query));
     for transaction in filtered_transactions {
         let OptionalKeys {
             amount,
             account,
+            currency,
             transactions,
             ..
         } = OptionalKeys::match_optional_keys(&transaction);
 
-        let day = transaction.date.format("%Y-%m-%d").to_string();
+        let day = Period::containing(transaction.date, Group::Daily).unwrap();
 
-        group_map.populate_group_map(day, account, amount, transactions);
+        group_map.populate_group_map(day, account, amount, currency, transactions);
     }
 
+    let day = Period::containing(NaiveDate::from_ymd(2020, 1, 1), Group::Daily).unwrap();
+
     assert_eq!(
         group_map
             .group_map
-            .get("2020-01-01")
+            .get(&day)
+            .unwrap()
+            .get("expense:foo")
             .unwrap()
-            .get("expense:foo"),
-        Some(&42.00)
+            .get("USD"),
+        Some(&dec!(42.00))
     );
     assert_eq!(
         group_map
             .group_map
-            .get("2020-01-01")
+            .get(&day)
             .unwrap()
-            .get("asset:cash"),
-        Some(&-42.00)
+            .get("asset:cash")
+            .unwrap()
+            .get("USD"),
+        Some(&dec!(-42.00))
     );
     assert_eq!(group_map.group_map.keys().count(), 1);
+    assert_eq!(group_map.group_map.get(&day).unwrap().keys().count(), 2);
+}
+
+#[test]
+fn filter_by_range_drops_openings_with_begin_date() {
+    let file = get_file();
+    let tree = file.clone().account_tree();
+
+    assert_eq!(tree.section(AccountType::Asset).totals()["USD"], dec!(18));
+    assert_eq!(tree.section(AccountType::Expense).totals()["USD"], dec!(82));
+
+    // opening balances are excluded once a begin date is given
+    let date = NaiveDate::from_ymd(2020, 1, 1);
+    let tree = file
+        .clone()
+        .filter_by_range(&DateRange {
+            begin: Some(date),
+            end: Some(date),
+        })
+        .account_tree();
+    assert_eq!(tree.section(AccountType::Asset).totals()["USD"], dec!(-82));
+
+    let tree = file
+        .filter_by_range(&DateRange {
+            begin: None,
+            end: date.pred_opt(),
+        })
+        .account_tree();
+    assert_eq!(tree.section(AccountType::Asset).totals()["USD"], dec!(100));
+}
+
+#[test]
+fn group_map_columns_fill_empty_periods() {
+    let mut file = get_file();
+    file.transactions[1].date = NaiveDate::from_ymd(2020, 3, 15);
+    let mut group_map = GroupMap::new();
+
+    for transaction in LedgerFile::flatten_transactions(file) {
+        let OptionalKeys {
+            amount,
+            account,
+            currency,
+            transactions,
+            ..
+        } = OptionalKeys::match_optional_keys(&transaction);
+
+        let month = Period::containing(transaction.date, Group::Monthly).unwrap();
+        group_map.populate_group_map(month, account, amount, currency, transactions);
+    }
+
+    let periods = group_map.periods(&DateRange::default());
+    let labels: Vec<String> = periods.iter().map(|p| p.to_string()).collect();
+    assert_eq!(labels, vec!["2020-01", "2020-02", "2020-03"]);
+
+    let columns = group_map.columns(&periods);
     assert_eq!(
-        group_map
-            .group_map
-            .get("2020-01-01")
-            .unwrap()
-            .keys()
-            .count(),
-        2
+        columns[&("asset:cash".to_string(), "USD".to_string())],
+        vec![dec!(-40.00), Decimal::ZERO, dec!(-42.00)]
+    );
+    assert_eq!(columns.len(), 4);
+}
+
+#[test]
+fn validate_amounts_rejects_sub_minor_unit_amounts() {
+    let mut file = get_file();
+    assert!(file.validate_amounts().is_ok());
+
+    file.transactions[0].amount = Some(dec!(10.005));
+    assert!(file.validate_amounts().is_err());
+}
+
+#[test]
+fn validate_amounts_rejects_unknown_currency() {
+    let mut file = get_file();
+    file.transactions[0].currency = Some("XYZ".to_string());
+
+    assert!(file.validate_amounts().is_err());
+}
+
+#[test]
+fn group_map_totals_per_currency() {
+    let mut file = get_file();
+    file.accounts[0].currency = Some("EUR".to_string());
+    let mut group_map = GroupMap::new();
+
+    for transaction in LedgerFile::flatten_transactions(file) {
+        let OptionalKeys {
+            amount,
+            account,
+            currency,
+            transactions,
+            ..
+        } = OptionalKeys::match_optional_keys(&transaction);
+
+        let year = Period::containing(transaction.date, Group::Yearly).unwrap();
+        group_map.populate_group_map(year, account, amount, currency, transactions);
+    }
+
+    let cash = group_map
+        .group_map
+        .values()
+        .next()
+        .unwrap()
+        .get("asset:cash")
+        .unwrap();
+
+    // summary transactions inherit the currency of their account, while
+    // detailed postings are denominated in the currency of each account
+    assert_eq!(cash.get("EUR"), Some(&dec!(-82.00)));
+    assert_eq!(cash.get("USD"), None);
+}
+
+#[test]
+fn value_converts_amounts_to_valuation_currency() {
+    let mut file = get_file();
+    file.accounts[0].currency = Some("EUR".to_string());
+    file.prices = Some(vec![Price {
+        date: NaiveDate::parse_from_str("2019-12-01", "%Y-%m-%d").unwrap(),
+        commodity: "EUR".to_string(),
+        price: dec!(1.105),
+        currency: "USD".to_string(),
+    }]);
+    let valuation = Valuation {
+        currency: "USD".to_string(),
+        date: NaiveDate::parse_from_str("2020-01-01", "%Y-%m-%d").unwrap(),
+        prices: vec![],
+    };
+
+    let result = file.value(&valuation).unwrap();
+
+    assert_eq!(result.accounts[0].amount, dec!(110.50));
+    assert_eq!(result.transactions[0].amount, Some(dec!(11.05)));
+    assert_eq!(
+        result.transactions[2].transactions.as_ref().unwrap()[0].amount,
+        dec!(-55.25)
+    );
+    assert!(result.validate_amounts().is_ok());
+}
+
+#[test]
+fn validate_assertions_checks_running_balance() {
+    let mut file = get_file();
+    file.transactions[0].assert_balance = Some(dec!(110.00));
+    file.transactions[2].transactions.as_mut().unwrap()[0].assert_balance = Some(dec!(18.00));
+
+    assert!(file.validate_assertions().is_ok());
+}
+
+#[test]
+fn validate_assertions_reports_failed_assertion() {
+    let mut file = get_file();
+    file.transactions[1].assert_balance = Some(dec!(100.00));
+
+    match file.validate_assertions() {
+        Err(Error::Validation(diagnostics)) => assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                "balance assertion failed on 2020-01-01 summary_transaction: \
+                 expected asset:cash USD to be 100.00 but was 68.00"
+                    .to_string(),
+                "transactions[1].assert_balance",
+            )]
+        ),
+        _ => panic!("balance assertion should fail"),
+    }
+}
+
+#[test]
+fn validate_rules_reports_invalid_patterns_and_accounts() {
+    let mut file = get_file();
+    file.rules = Some(vec![Rule {
+        name: Some("fuel".to_string()),
+        priority: None,
+        conditions: crate::import::categorize::RuleMatch {
+            payee: Some("/(shell/".to_string()),
+            ..Default::default()
+        },
+        set: crate::import::categorize::RuleActions {
+            account: Some("expense:fuel".to_string()),
+            ..Default::default()
+        },
+    }]);
+
+    match file.validate_rules() {
+        Err(Error::Validation(diagnostics)) => {
+            let paths: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+            assert_eq!(paths, vec!["rules[0].match.payee", "rules[0].set.account"]);
+            assert_eq!(
+                diagnostics[1].message,
+                "rule fuel assigns undeclared account expense:fuel"
+            );
+        }
+        _ => panic!("rules should be invalid"),
+    }
+}
+
+#[test]
+fn validate_transactions_accepts_valid_file() {
+    let file = get_file();
+
+    assert!(file.validate_transactions(false).is_ok());
+}
+
+#[test]
+fn validate_transactions_reports_each_problem() {
+    let mut file = get_file();
+    file.accounts.push(file.accounts[1].clone());
+    file.transactions[0].offset_account = None;
+    file.transactions[1].account = Some("expense:unknown".to_string());
+    file.transactions[2].transactions.as_mut().unwrap()[0].amount = dec!(-40.00);
+
+    match file.validate_transactions(false) {
+        Err(Error::Validation(diagnostics)) => assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.message.as_str(), d.path.as_str()))
+                .collect::<Vec<(&str, &str)>>(),
+            vec![
+                (
+                    "account expense:foo is declared more than once",
+                    "accounts[4].account"
+                ),
+                (
+                    "transaction 1 (2020-01-01 summary_transaction) is missing offset_account",
+                    "transactions[0].offset_account"
+                ),
+                (
+                    "transaction 2 (2020-01-01 summary_transaction) posts to undeclared account expense:unknown",
+                    "transactions[1].account"
+                ),
+                (
+                    "transaction 3 (2020-01-01 detailed_transaction) does not balance: postings sum to 10.00 USD",
+                    "transactions[2].transactions"
+                ),
+            ]
+        ),
+        _ => panic!("validation should fail"),
+    }
+}
+
+#[test]
+fn validate_transactions_balances_exchanges_at_prices() {
+    let source = "currency: USD
+accounts:
+  - account: asset:cash
+    amount: 0
+  - account: asset:eur
+    amount: 0
+    currency: EUR
+transactions:
+  - date: 2020-01-05
+    description: exchange
+    transactions:
+      - account: asset:eur
+        amount: 10.00
+      - account: asset:cash
+        amount: -11.03
+";
+    let mut file: LedgerFile = serde_yaml::from_str(source).unwrap();
+    let message = |file: &LedgerFile, strict_prices| match file.validate_transactions(strict_prices)
+    {
+        Err(Error::Validation(diagnostics)) => diagnostics[0].message.clone(),
+        _ => String::new(),
+    };
+
+    // the postings of an exchange between two currencies imply its rate
+    assert!(file.validate_transactions(false).is_ok());
+    assert_eq!(
+        message(&file, true),
+        "transaction 1 (2020-01-05 exchange) exchanges EUR and USD without a price on or before 2020-01-05"
+    );
+
+    // amounts within half a cent of the price balance
+    file.prices = Some(vec![Price {
+        date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        commodity: "EUR".to_string(),
+        price: dec!(1.1034),
+        currency: "USD".to_string(),
+    }]);
+    assert!(file.validate_transactions(true).is_ok());
+
+    file.transactions[0].transactions.as_mut().unwrap()[1].amount = dec!(-10.03);
+    assert!(file.validate_transactions(false).is_ok());
+    assert_eq!(
+        message(&file, true),
+        "transaction 1 (2020-01-05 exchange) does not balance: postings sum to 1.00 USD at the prices of 2020-01-05"
+    );
+
+    // but one currency must be given for the other
+    file.transactions[0].transactions.as_mut().unwrap()[1].amount = dec!(10.03);
+    assert_eq!(
+        message(&file, false),
+        "transaction 1 (2020-01-05 exchange) does not balance: postings sum to 10.00 EUR and 10.03 USD"
+    );
+}
+
+#[test]
+fn account_postings_running_balance() {
+    let file = get_file();
+    let (carried, postings) = file.account_postings("asset", &DateRange::default());
+
+    assert_eq!(carried["USD"], dec!(100.00));
+    let balances: Vec<Decimal> = postings.iter().filter_map(|p| p.balance).collect();
+    assert_eq!(balances, vec![dec!(110.00), dec!(68.00), dec!(18.00)]);
+    assert_eq!(postings[0].counter_accounts, vec!["expense:foo"]);
+    assert_eq!(
+        postings[2].counter_accounts,
+        vec!["expense:bar", "expense:baz"]
+    );
+
+    // postings before the range are carried into the opening balance
+    let range = DateRange {
+        begin: NaiveDate::from_ymd_opt(2020, 1, 2),
+        end: None,
+    };
+    let (carried, postings) = file.account_postings("asset:cash", &range);
+    assert_eq!(carried["USD"], dec!(18.00));
+    assert!(postings.is_empty());
+
+    // accounts only match whole segments
+    let (carried, postings) = file.account_postings("asset:ca", &DateRange::default());
+    assert!(carried.is_empty() && postings.is_empty());
+}
+
+#[test]
+fn to_yaml_round_trips() {
+    let file = get_file();
+    let yaml = file.to_yaml().unwrap();
+
+    assert!(!yaml.contains("~"));
+    assert!(yaml.contains("date: 2020-01-01"));
+    assert_eq!(LedgerFile::parse(&yaml).unwrap(), file);
+}
+
+#[test]
+fn postings_resolve_offsets_and_currencies() {
+    let postings = get_file().postings();
+
+    assert_eq!(postings.len(), 7);
+    assert_eq!(postings[1].account, "expense:foo");
+    assert_eq!(postings[1].amount, dec!(-10.00));
+    assert!(postings.iter().all(|p| p.currency == "USD"));
+}
+
+#[cfg(test)]
+const TAGGED: &str = "currency: USD
+accounts:
+  - account: asset:cash
+    amount: 100
+  - account: equity:opening
+    amount: -100
+  - account: expense:travel
+    amount: 0
+transactions:
+  - date: 2020-01-02
+    description: train
+    tags: [trip, project:berlin]
+    meta:
+      invoice: 14
+      paid: true
+    transactions:
+      - account: expense:travel
+        amount: 80
+        tags: [trip, reimbursable]
+        meta:
+          invoice: 15
+      - account: asset:cash
+        amount: -80
+  - date: 2020-01-03
+    description: lunch
+    account: asset:cash
+    amount: -20
+    offset_account: expense:travel
+";
+
+#[test]
+fn postings_inherit_tags_and_metadata() {
+    let file = LedgerFile::parse(TAGGED).unwrap();
+    let postings = LedgerFile::flatten_transactions(file);
+
+    assert_eq!(
+        postings[0].tags,
+        Some(vec![
+            "trip".to_string(),
+            "project:berlin".to_string(),
+            "reimbursable".to_string()
+        ])
+    );
+    let meta = postings[0].meta.clone().unwrap();
+    assert_eq!(meta["invoice"], "15");
+    assert_eq!(meta["paid"], "true");
+    assert_eq!(postings[1].meta.clone().unwrap()["invoice"], "14");
+    assert_eq!(postings[2].tags, None);
+}
+
+#[test]
+fn pivot_groups_postings_by_tag() {
+    let file = LedgerFile::parse(TAGGED).unwrap();
+    let pivot = Pivot::parse("tag:project").unwrap();
+    let pivoted = file.pivot(&pivot);
+
+    let accounts: Vec<(&str, Decimal)> = pivoted
+        .accounts
+        .iter()
+        .map(|a| (a.account.as_str(), a.amount))
+        .collect();
+    assert_eq!(accounts, vec![("(none)", dec!(0)), ("berlin", dec!(0))]);
+
+    let postings: Vec<(String, Decimal)> = pivoted
+        .postings()
+        .into_iter()
+        .map(|p| (p.account, p.amount))
+        .collect();
+    assert_eq!(
+        postings,
+        vec![
+            ("berlin".to_string(), dec!(80)),
+            ("berlin".to_string(), dec!(-80)),
+            ("(none)".to_string(), dec!(-20)),
+            ("(none)".to_string(), dec!(20)),
+        ]
     );
 }
+
+#[test]
+fn validate_payee_aliases_reports_invalid_patterns() {
+    let mut file = get_file();
+    file.payee_aliases = Some(vec![PayeeAlias {
+        payee: "Shell".to_string(),
+        patterns: vec!["shell oil".to_string(), "/(shell/".to_string()],
+    }]);
+
+    match file.validate_payee_aliases() {
+        Err(Error::Validation(diagnostics)) => {
+            let paths: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+            assert_eq!(paths, vec!["payee_aliases[0].match[1]"]);
+            assert!(diagnostics[0]
+                .message
+                .starts_with("payee alias Shell: invalid regex"));
+        }
+        _ => panic!("payee aliases should be invalid"),
+    }
+}
+
+#[test]
+fn payees_report_counts_transactions_per_payee() {
+    let mut file = LedgerFile::parse(TAGGED).unwrap();
+    file.transactions[1].payee = Some("Deutsche Bahn".to_string());
+    file.payee_aliases = Some(vec![PayeeAlias {
+        payee: "Deutsche Bahn".to_string(),
+        patterns: vec!["train".to_string()],
+    }]);
+
+    let rows: Vec<(String, usize, Decimal)> = file
+        .clone()
+        .payees_report(&Query::Any)
+        .rows
+        .into_iter()
+        .map(|r| (r.payee, r.transactions, r.amount))
+        .collect();
+    assert_eq!(rows, vec![("Deutsche Bahn".to_string(), 2, dec!(100))]);
+
+    let query = Query::parse("amt:<0").unwrap();
+    let rows: Vec<(String, usize, Decimal)> = file
+        .payees_report(&query)
+        .rows
+        .into_iter()
+        .map(|r| (r.payee, r.transactions, r.amount))
+        .collect();
+    assert_eq!(rows, vec![("Deutsche Bahn".to_string(), 2, dec!(0))]);
+}
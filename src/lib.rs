@@ -0,0 +1,36 @@
+//! loading, validating and reporting on `rust_ledger` yaml files.
+//!
+//! ```
+//! use rust_ledger::account_tree::Layout;
+//! use rust_ledger::LedgerFile;
+//!
+//! let ledger_file = LedgerFile::read("examples/example.yaml")?;
+//! for posting in ledger_file.postings() {
+//!     println!("{} {} {}", posting.date, posting.account, posting.amount);
+//! }
+//!
+//! let report = ledger_file.balance_report(Layout::Flat, None);
+//! assert!(report.check.values().all(|c| c.is_zero()));
+//! # Ok::<(), rust_ledger::Error>(())
+//! ```
+
+pub mod account_tree;
+pub mod commodity;
+pub mod convert;
+pub mod error;
+pub mod import;
+mod include;
+pub mod ledger;
+pub mod payee;
+pub mod period;
+pub mod price;
+pub mod query;
+pub mod report;
+mod source_map;
+pub mod yaml;
+
+#[macro_use]
+extern crate prettytable;
+
+pub use error::{Error, Result};
+pub use ledger::{Account, Group, LedgerFile, Posting, Transaction, TransactionList};
@@ -1,10 +1,8 @@
 mod cli;
-mod error;
-mod ledger;
 
-#[macro_use]
-extern crate prettytable;
-
-fn main() -> Result<(), error::Error> {
-    cli::run()
+fn main() {
+    if let Err(err) = cli::run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,110 @@
+use crate::ledger::{LedgerFile, Transaction};
+use crate::query::Matcher;
+use serde::{Deserialize, Serialize};
+
+/// canonical name of a payee along with the names banks give it, declared
+/// under the `payee_aliases` key of a ledger file. patterns match like
+/// query terms, i.e. plain text matches any part of a name regardless of
+/// case and `/regex/` or `/regex/i` a regular expression
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PayeeAlias {
+    pub payee: String,
+    #[serde(rename = "match")]
+    pub patterns: Vec<String>,
+}
+
+/// payee aliases of a ledger file, in the order of the file
+pub struct Payees {
+    aliases: Vec<(String, Vec<Matcher>)>,
+}
+
+impl Payees {
+    /// compile the payee aliases of `ledger_file`. invalid patterns are
+    /// reported when the ledger file is validated, so they are skipped
+    pub fn new(ledger_file: &LedgerFile) -> Payees {
+        let aliases = ledger_file
+            .payee_aliases
+            .iter()
+            .flatten()
+            .map(|alias| {
+                let patterns = alias
+                    .patterns
+                    .iter()
+                    .filter_map(|p| Matcher::parse(p).ok())
+                    .collect();
+                (alias.payee.clone(), patterns)
+            })
+            .collect();
+
+        Payees { aliases }
+    }
+
+    /// canonical payee of the first alias matching `name`
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|p| p.matches(name)))
+            .map(|(payee, _)| payee.as_str())
+    }
+
+    /// payee of a transaction, which is its `payee` or else its
+    /// description, normalized by the payee aliases
+    pub fn payee(&self, transaction: &Transaction) -> String {
+        let name = transaction
+            .payee
+            .as_deref()
+            .unwrap_or(&transaction.description);
+
+        self.alias(name).unwrap_or(name).to_string()
+    }
+}
+
+#[cfg(test)]
+fn get_file() -> LedgerFile {
+    LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:shopping
+    amount: 0
+transactions:
+  - date: 2020-01-02
+    description: AMZN MKTP US*2K3
+    account: expense:shopping
+    amount: 25
+    offset_account: asset:checking
+  - date: 2020-01-03
+    description: books
+    payee: Amazon.com
+    account: expense:shopping
+    amount: 10
+    offset_account: asset:checking
+  - date: 2020-01-04
+    description: corner store
+    account: expense:shopping
+    amount: 5
+    offset_account: asset:checking
+payee_aliases:
+  - payee: Amazon
+    match: [/^AMZN MKTP/, amazon.com]
+  - payee: Corner Store
+    match: [corner]
+",
+    )
+    .unwrap()
+}
+
+#[test]
+fn aliases_normalize_payees() {
+    let file = get_file();
+    let payees = Payees::new(&file);
+
+    assert_eq!(payees.alias("AMZN MKTP US*2K3"), Some("Amazon"));
+    assert_eq!(payees.alias("www.AMAZON.COM"), Some("Amazon"));
+    assert_eq!(payees.alias("amzn mktp"), None);
+
+    let names: Vec<String> = file.transactions.iter().map(|t| payees.payee(t)).collect();
+    assert_eq!(names, vec!["Amazon", "Amazon", "Corner Store"]);
+}
@@ -0,0 +1,321 @@
+use crate::error::{Error, Result};
+use crate::ledger::Group;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::fmt;
+
+/// span of dates, both inclusive, that amounts are grouped into. periods
+/// order chronologically
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub group: Group,
+}
+
+impl Period {
+    /// period of `group` that contains `date`. `Group::None` does not
+    /// divide dates into periods
+    pub fn containing(date: NaiveDate, group: Group) -> Option<Period> {
+        let start = match group {
+            Group::Daily => date,
+            Group::Weekly => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Group::Monthly => NaiveDate::from_ymd(date.year(), date.month(), 1),
+            Group::Quarterly => NaiveDate::from_ymd(date.year(), (date.month() - 1) / 3 * 3 + 1, 1),
+            Group::Yearly => NaiveDate::from_ymd(date.year(), 1, 1),
+            Group::None => return None,
+        };
+
+        let next_start = match group {
+            Group::Daily => start + Duration::days(1),
+            Group::Weekly => start + Duration::days(7),
+            Group::Monthly => add_months(start, 1),
+            Group::Quarterly => add_months(start, 3),
+            _ => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+        };
+
+        Some(Period {
+            start,
+            end: next_start - Duration::days(1),
+            group,
+        })
+    }
+
+    /// every period of `group` from the one containing `first` through the
+    /// one containing `last`, in chronological order and without gaps
+    pub fn range(first: NaiveDate, last: NaiveDate, group: Group) -> Vec<Period> {
+        let mut periods = Vec::new();
+        let mut current = Period::containing(first, group);
+
+        while let Some(period) = current {
+            if period.start > last {
+                break;
+            }
+            periods.push(period);
+            current = Period::containing(period.end + Duration::days(1), group);
+        }
+
+        periods
+    }
+}
+
+/// first day of the month `months` after the month of `date`
+fn add_months(date: NaiveDate, months: u32) -> NaiveDate {
+    let index = date.year() * 12 + date.month0() as i32 + months as i32;
+    NaiveDate::from_ymd(index / 12, index as u32 % 12 + 1, 1)
+}
+
+/// labels periods as `2020-01-31`, `2020-W05`, `2020-01`, `2020-Q1` or `2020`
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.group {
+            Group::Daily => write!(f, "{}", self.start.format("%Y-%m-%d")),
+            Group::Weekly => write!(f, "{}", self.start.format("%G-W%V")),
+            Group::Monthly => write!(f, "{}", self.start.format("%Y-%m")),
+            Group::Quarterly => write!(f, "{}-Q{}", self.start.year(), self.start.month0() / 3 + 1),
+            Group::Yearly | Group::None => write!(f, "{}", self.start.format("%Y")),
+        }
+    }
+}
+
+/// dates between `begin` and `end`, both inclusive. a missing bound
+/// leaves the range open on that side
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct DateRange {
+    pub begin: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// build a `DateRange` from command line arguments. `begin` and `end`
+    /// are dates in `YYYY-MM-DD` format that override the respective
+    /// bound of the `period` expression
+    pub fn from_args(begin: &str, end: &str, period: &str) -> Result<DateRange> {
+        let today = chrono::Local::today().naive_local();
+        let mut range = match period {
+            "" => DateRange::default(),
+            p => DateRange::parse(p, today)?,
+        };
+
+        if !begin.is_empty() {
+            range.begin = Some(parse_date(begin)?);
+        }
+        if !end.is_empty() {
+            range.end = Some(parse_date(end)?);
+        }
+
+        match (range.begin, range.end) {
+            (Some(b), Some(e)) if b > e => Err(Error::InvalidArg(format!(
+                "invalid date range: begins on {} after it ends on {}",
+                b, e
+            ))),
+            _ => Ok(range),
+        }
+    }
+
+    /// parse a period expression relative to `today`. supported forms are
+    /// years (`2024`), quarters (`2024-Q2`), months (`2024-06`), days
+    /// (`2024-06-30`), `today`, `yesterday`, `this`, `last` or `next`
+    /// followed by `day`, `week`, `month`, `quarter` or `year`, and ranges
+    /// of those joined by `..`, where either side may be left open. ranges
+    /// must not end before they begin
+    pub fn parse(expression: &str, today: NaiveDate) -> Result<DateRange> {
+        let expression = expression.trim();
+
+        if let Some((from, to)) = expression.split_once("..") {
+            let begin = match from.trim() {
+                "" => None,
+                f => Some(parse_span(f, today)?.start),
+            };
+            let end = match to.trim() {
+                "" => None,
+                t => Some(parse_span(t, today)?.end),
+            };
+            if let (Some(b), Some(e)) = (begin, end) {
+                if b > e {
+                    return Err(Error::InvalidArg(format!(
+                        "invalid period: {} ends before it begins",
+                        expression
+                    )));
+                }
+            }
+            return Ok(DateRange { begin, end });
+        }
+
+        let span = parse_span(expression, today)?;
+        Ok(DateRange {
+            begin: Some(span.start),
+            end: Some(span.end),
+        })
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.begin.is_none_or(|b| date >= b) && self.end.is_none_or(|e| date <= e)
+    }
+}
+
+/// period named by a single expression, e.g. `2024-Q2` or `last month`
+fn parse_span(expression: &str, today: NaiveDate) -> Result<Period> {
+    let invalid = || Error::InvalidArg(format!("invalid period: {}", expression));
+    let words: Vec<String> = expression
+        .split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect();
+    let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+
+    let (date, group) = match words.as_slice() {
+        ["today"] => (today, Group::Daily),
+        ["yesterday"] => (today - Duration::days(1), Group::Daily),
+        [offset, unit] => {
+            let group = match *unit {
+                "day" => Group::Daily,
+                "week" => Group::Weekly,
+                "month" => Group::Monthly,
+                "quarter" => Group::Quarterly,
+                "year" => Group::Yearly,
+                _ => return Err(invalid()),
+            };
+            let current = Period::containing(today, group).ok_or_else(invalid)?;
+            let date = match *offset {
+                "this" => today,
+                "last" => current.start - Duration::days(1),
+                "next" => current.end + Duration::days(1),
+                _ => return Err(invalid()),
+            };
+            (date, group)
+        }
+        [date] => {
+            let parts: Vec<&str> = date.split('-').collect();
+            let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+
+            match parts.as_slice() {
+                [y] if y.len() == 4 => (
+                    NaiveDate::from_ymd_opt(number(y)? as i32, 1, 1).ok_or_else(invalid)?,
+                    Group::Yearly,
+                ),
+                [y, q] if q.starts_with('q') => {
+                    let quarter = number(&q[1..])?;
+                    if !(1..=4).contains(&quarter) {
+                        return Err(invalid());
+                    }
+                    (
+                        NaiveDate::from_ymd_opt(number(y)? as i32, quarter * 3 - 2, 1)
+                            .ok_or_else(invalid)?,
+                        Group::Quarterly,
+                    )
+                }
+                [y, m] => (
+                    NaiveDate::from_ymd_opt(number(y)? as i32, number(m)?, 1)
+                        .ok_or_else(invalid)?,
+                    Group::Monthly,
+                ),
+                [_, _, _] => (parse_date(date)?, Group::Daily),
+                _ => return Err(invalid()),
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    Period::containing(date, group).ok_or_else(invalid)
+}
+
+/// parse a date in `YYYY-MM-DD` format
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| Error::InvalidArg(format!("invalid date {}: {}", date, e)))
+}
+
+#[cfg(test)]
+fn date(d: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn containing_period_per_group() {
+    let d = date("2020-05-14");
+
+    let weekly = Period::containing(d, Group::Weekly).unwrap();
+    assert_eq!(
+        (weekly.start, weekly.end),
+        (date("2020-05-11"), date("2020-05-17"))
+    );
+    assert_eq!(weekly.to_string(), "2020-W20");
+
+    let quarterly = Period::containing(d, Group::Quarterly).unwrap();
+    assert_eq!(
+        (quarterly.start, quarterly.end),
+        (date("2020-04-01"), date("2020-06-30"))
+    );
+    assert_eq!(quarterly.to_string(), "2020-Q2");
+
+    let monthly = Period::containing(date("2020-12-31"), Group::Monthly).unwrap();
+    assert_eq!(monthly.end, date("2020-12-31"));
+    assert_eq!(monthly.to_string(), "2020-12");
+
+    assert_eq!(Period::containing(d, Group::None), None);
+}
+
+#[test]
+fn range_includes_empty_periods() {
+    let periods = Period::range(date("2019-11-20"), date("2020-02-01"), Group::Monthly);
+    let labels: Vec<String> = periods.iter().map(|p| p.to_string()).collect();
+
+    assert_eq!(labels, vec!["2019-11", "2019-12", "2020-01", "2020-02"]);
+    assert_eq!(
+        Period::range(date("2020-01-01"), date("2020-12-31"), Group::Quarterly).len(),
+        4
+    );
+}
+
+#[test]
+fn parse_period_expressions() {
+    let today = date("2024-05-14");
+    let range = |e| DateRange::parse(e, today).unwrap();
+
+    assert_eq!(
+        range("2024"),
+        DateRange {
+            begin: Some(date("2024-01-01")),
+            end: Some(date("2024-12-31"))
+        }
+    );
+    assert_eq!(range("2024-Q2").begin, Some(date("2024-04-01")));
+    assert_eq!(range("2024-Q2").end, Some(date("2024-06-30")));
+    assert_eq!(range("2024-02").end, Some(date("2024-02-29")));
+    assert_eq!(range("last month").begin, Some(date("2024-04-01")));
+    assert_eq!(range("Last Month").end, Some(date("2024-04-30")));
+    assert_eq!(range("this year").end, Some(date("2024-12-31")));
+    assert_eq!(range("last year").begin, Some(date("2023-01-01")));
+    assert_eq!(
+        range("2023-07..2024-06"),
+        DateRange {
+            begin: Some(date("2023-07-01")),
+            end: Some(date("2024-06-30"))
+        }
+    );
+    assert_eq!(range("2024-03..").end, None);
+
+    assert!(DateRange::parse("2024-Q5", today).is_err());
+    assert!(DateRange::parse("some day", today).is_err());
+    assert!(DateRange::parse("2024-13", today).is_err());
+
+    // ranges that end before they begin would select nothing
+    assert_eq!(
+        DateRange::parse("2020-12-01..2020-01-01", today)
+            .unwrap_err()
+            .to_string(),
+        "error: invalid period: 2020-12-01..2020-01-01 ends before it begins"
+    );
+    assert_eq!(range("2024-06..2024-06").begin, Some(date("2024-06-01")));
+}
+
+#[test]
+fn begin_and_end_override_period() {
+    let range = DateRange::from_args("2024-02-15", "", "2024-Q1").unwrap();
+
+    assert_eq!(range.begin, Some(date("2024-02-15")));
+    assert_eq!(range.end, Some(date("2024-03-31")));
+    assert!(range.contains(date("2024-03-31")));
+    assert!(!range.contains(date("2024-02-14")));
+    assert!(DateRange::default().contains(date("1900-01-01")));
+    assert!(DateRange::from_args("2024-04-01", "", "2024-Q1").is_err());
+}
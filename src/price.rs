@@ -0,0 +1,193 @@
+use crate::error::{Error, Result};
+use crate::ledger::deserialize_date_from_str;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// price of one unit of `commodity` expressed in `currency` as of `date`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Price {
+    #[serde(deserialize_with = "deserialize_date_from_str")]
+    pub date: NaiveDate,
+    pub commodity: String,
+    pub price: Decimal,
+    pub currency: String,
+}
+
+/// price history kept outside of the ledger file
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PriceFile {
+    pub prices: Vec<Price>,
+}
+
+impl PriceFile {
+    pub fn read(filename: &str) -> Result<PriceFile> {
+        let source = std::fs::read_to_string(filename)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+
+        serde_yaml::from_str(&source).map_err(|e| Error::from(e).locate(filename, &source))
+    }
+}
+
+/// target currency and date that amounts are converted to, along with
+/// any prices supplied in addition to those of the ledger file
+#[derive(Debug, PartialEq, Clone)]
+pub struct Valuation {
+    pub currency: String,
+    pub date: NaiveDate,
+    pub prices: Vec<Price>,
+}
+
+impl Valuation {
+    /// build a `Valuation` from command line arguments. `None` is returned
+    /// when no target currency was requested. the valuation date defaults
+    /// to today
+    pub fn from_args(currency: &str, date: &str, price_file: &str) -> Result<Option<Valuation>> {
+        if currency.is_empty() {
+            return Ok(None);
+        }
+
+        let date = match date {
+            "" => chrono::Local::today().naive_local(),
+            d => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                .map_err(|e| Error::InvalidArg(format!("invalid date {}: {}", d, e)))?,
+        };
+
+        let prices = match price_file {
+            "" => Vec::new(),
+            f => PriceFile::read(f)?.prices,
+        };
+
+        Ok(Some(Valuation {
+            currency: currency.to_string(),
+            date,
+            prices,
+        }))
+    }
+
+    /// exchange rate that converts one unit of `from` into the valuation
+    /// currency, using the latest price of each pair on or before the
+    /// valuation date. prices can be applied in either direction and
+    /// chained through intermediate currencies
+    pub fn rate(&self, from: &str, prices: &[Price]) -> Result<Decimal> {
+        if from == self.currency {
+            return Ok(Decimal::ONE);
+        }
+
+        let mut latest: HashMap<(&str, &str), &Price> = HashMap::new();
+        for price in prices.iter().filter(|p| p.date <= self.date) {
+            let entry = latest
+                .entry((&price.commodity, &price.currency))
+                .or_insert(price);
+            if price.date >= entry.date {
+                *entry = price;
+            }
+        }
+
+        let mut rates: HashMap<&str, Decimal> = HashMap::new();
+        let mut queue = VecDeque::new();
+        rates.insert(from, Decimal::ONE);
+        queue.push_back(from);
+
+        while let Some(code) = queue.pop_front() {
+            let rate = rates[code];
+
+            for price in latest.values() {
+                let (next, next_rate) = if price.commodity == code {
+                    (price.currency.as_str(), rate.checked_mul(price.price))
+                } else if price.currency == code {
+                    (price.commodity.as_str(), rate.checked_div(price.price))
+                } else {
+                    continue;
+                };
+
+                if rates.contains_key(next) {
+                    continue;
+                }
+
+                if let Some(r) = next_rate {
+                    if next == self.currency {
+                        return Ok(r);
+                    }
+                    rates.insert(next, r);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Err(Error::MissingPrice(format!(
+            "no price to convert {} to {} on or before {}",
+            from, self.currency, self.date
+        )))
+    }
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+fn get_prices() -> Vec<Price> {
+    let date = |d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+
+    vec![
+        Price {
+            date: date("2020-01-01"),
+            commodity: "EUR".to_string(),
+            price: dec!(1.10),
+            currency: "USD".to_string(),
+        },
+        Price {
+            date: date("2020-06-01"),
+            commodity: "EUR".to_string(),
+            price: dec!(1.20),
+            currency: "USD".to_string(),
+        },
+        Price {
+            date: date("2020-01-01"),
+            commodity: "VTI".to_string(),
+            price: dec!(150),
+            currency: "USD".to_string(),
+        },
+    ]
+}
+
+#[test]
+fn rate_uses_latest_price_on_or_before_date() {
+    let mut valuation = Valuation {
+        currency: "USD".to_string(),
+        date: NaiveDate::parse_from_str("2020-03-01", "%Y-%m-%d").unwrap(),
+        prices: vec![],
+    };
+
+    assert_eq!(valuation.rate("EUR", &get_prices()).unwrap(), dec!(1.10));
+
+    valuation.date = NaiveDate::parse_from_str("2020-06-01", "%Y-%m-%d").unwrap();
+    assert_eq!(valuation.rate("EUR", &get_prices()).unwrap(), dec!(1.20));
+    assert_eq!(valuation.rate("USD", &get_prices()).unwrap(), dec!(1));
+}
+
+#[test]
+fn rate_is_inverted_and_chained() {
+    let valuation = Valuation {
+        currency: "EUR".to_string(),
+        date: NaiveDate::parse_from_str("2020-03-01", "%Y-%m-%d").unwrap(),
+        prices: vec![],
+    };
+
+    assert_eq!(
+        valuation.rate("VTI", &get_prices()).unwrap().round_dp(4),
+        dec!(136.3636)
+    );
+}
+
+#[test]
+fn rate_without_price_is_error() {
+    let valuation = Valuation {
+        currency: "USD".to_string(),
+        date: NaiveDate::parse_from_str("2019-01-01", "%Y-%m-%d").unwrap(),
+        prices: vec![],
+    };
+
+    assert!(valuation.rate("EUR", &get_prices()).is_err());
+}
@@ -0,0 +1,494 @@
+use crate::error::{Error, Result};
+use crate::ledger::Transaction;
+use crate::payee::Payees;
+use crate::period::DateRange;
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// text pattern of a query term. plain text matches any part of a value
+/// regardless of case, `/regex/` matches a regular expression and
+/// `/regex/i` does so regardless of case
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Text(String),
+    Regex(Regex),
+}
+
+/// comparison of an `amt:` term
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+/// syntax tree of a query. terms separated by whitespace must all match,
+/// `or` separates alternatives and `not:` negates the term that follows
+#[derive(Debug, Clone, Default)]
+pub enum Query {
+    #[default]
+    Any,
+    Account(Matcher),
+    Description(Matcher),
+    /// payee of the transaction, normalized by the payee aliases
+    Payee(Matcher),
+    Currency(Matcher),
+    Amount(Comparison, Decimal),
+    Date(DateRange),
+    /// tag name, along with the pattern of its value if given as
+    /// `tag:NAME=VALUE`
+    Tag(Matcher, Option<Matcher>),
+    /// metadata key, along with the pattern of its value
+    Meta(Matcher, Option<Matcher>),
+    Text(Matcher),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+impl Matcher {
+    pub(crate) fn parse(value: &str) -> Result<Matcher> {
+        let regex = value
+            .strip_prefix('/')
+            .and_then(|v| match v.strip_suffix("/i") {
+                Some(pattern) => Some((pattern, true)),
+                None => v.strip_suffix('/').map(|pattern| (pattern, false)),
+            });
+
+        match regex {
+            Some((pattern, case_insensitive)) => RegexBuilder::new(pattern)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|e| Error::InvalidArg(format!("invalid regex {}: {}", value, e))),
+            None => Ok(Matcher::Text(value.to_lowercase())),
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Text(text) => value.to_lowercase().contains(text),
+            Matcher::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+impl Comparison {
+    fn compare(&self, amount: Decimal, value: Decimal) -> bool {
+        match self {
+            Comparison::Equal => amount == value,
+            Comparison::Greater => amount > value,
+            Comparison::GreaterOrEqual => amount >= value,
+            Comparison::Less => amount < value,
+            Comparison::LessOrEqual => amount <= value,
+        }
+    }
+}
+
+impl Query {
+    /// parse a query such as `acct:expense:auto desc:/shell/i amt:>100`.
+    /// relative dates of `date:` terms are resolved against today
+    pub fn parse(query: &str) -> Result<Query> {
+        Query::parse_at(query, chrono::Local::today().naive_local())
+    }
+
+    fn parse_at(query: &str, today: NaiveDate) -> Result<Query> {
+        let mut alternatives = Vec::new();
+        let mut terms = Vec::new();
+
+        for token in tokenize(query)? {
+            if token.eq_ignore_ascii_case("or") {
+                alternatives.push(Query::all(std::mem::take(&mut terms)));
+                continue;
+            }
+            terms.push(Query::parse_term(&token, today)?);
+        }
+
+        if alternatives.is_empty() {
+            return Ok(Query::all(terms));
+        }
+        alternatives.push(Query::all(terms));
+
+        Ok(Query::Or(alternatives))
+    }
+
+    fn all(mut terms: Vec<Query>) -> Query {
+        match terms.len() {
+            0 => Query::Any,
+            1 => terms.remove(0),
+            _ => Query::And(terms),
+        }
+    }
+
+    fn parse_term(term: &str, today: NaiveDate) -> Result<Query> {
+        let invalid = |message: &str| Error::InvalidArg(format!("{}: {}", message, term));
+
+        let (prefix, value) = match term.split_once(':') {
+            Some((prefix, value)) => (prefix.to_lowercase(), value),
+            None => return Ok(Query::Text(Matcher::parse(term)?)),
+        };
+
+        match prefix.as_str() {
+            "not" => Ok(Query::Not(Box::new(Query::parse_term(value, today)?))),
+            "acct" | "account" => Ok(Query::Account(Matcher::parse(value)?)),
+            "desc" | "description" => Ok(Query::Description(Matcher::parse(value)?)),
+            "payee" => Ok(Query::Payee(Matcher::parse(value)?)),
+            "cur" | "currency" => Ok(Query::Currency(Matcher::parse(value)?)),
+            "tag" | "meta" => {
+                let (name, pattern) = match value.split_once('=') {
+                    Some((name, pattern)) => (name, Some(Matcher::parse(pattern)?)),
+                    None => (value, None),
+                };
+                if name.is_empty() {
+                    return Err(invalid("missing name in query"));
+                }
+                match prefix.as_str() {
+                    "tag" => Ok(Query::Tag(Matcher::parse(name)?, pattern)),
+                    _ => Ok(Query::Meta(Matcher::parse(name)?, pattern)),
+                }
+            }
+            "date" => Ok(Query::Date(DateRange::parse(value, today)?)),
+            "amt" | "amount" => {
+                let (comparison, number) = [
+                    (">=", Comparison::GreaterOrEqual),
+                    ("<=", Comparison::LessOrEqual),
+                    (">", Comparison::Greater),
+                    ("<", Comparison::Less),
+                    ("=", Comparison::Equal),
+                ]
+                .iter()
+                .find_map(|(op, c)| value.strip_prefix(op).map(|n| (*c, n)))
+                .unwrap_or((Comparison::Equal, value));
+
+                let amount =
+                    Decimal::from_str(number).map_err(|_| invalid("invalid amount in query"))?;
+                Ok(Query::Amount(comparison, amount))
+            }
+            // account names contain `:` as well, so anything else is plain text
+            _ => Ok(Query::Text(Matcher::parse(term)?)),
+        }
+    }
+
+    pub fn is_any(&self) -> bool {
+        matches!(self, Query::Any)
+    }
+
+    /// evaluate the query against a flattened posting, whose payee is
+    /// normalized by `payees`
+    pub fn matches(&self, posting: &Transaction, payees: &Payees) -> bool {
+        let account = posting.account.as_deref().unwrap_or("");
+
+        match self {
+            Query::Any => true,
+            Query::Account(m) => m.matches(account),
+            Query::Description(m) => m.matches(&posting.description),
+            Query::Payee(m) => m.matches(&payees.payee(posting)),
+            Query::Currency(m) => m.matches(posting.currency.as_deref().unwrap_or("")),
+            Query::Amount(c, value) => c.compare(posting.amount.unwrap_or(Decimal::ZERO), *value),
+            Query::Date(range) => range.contains(posting.date),
+            Query::Tag(name, pattern) => posting.tags.iter().flatten().any(|tag| {
+                let (tag, value) = split_tag(tag);
+                name.matches(tag) && value_matches(pattern, value)
+            }),
+            Query::Meta(key, pattern) => posting
+                .meta
+                .iter()
+                .flatten()
+                .any(|(k, v)| key.matches(k) && value_matches(pattern, Some(v))),
+            Query::Text(m) => {
+                m.matches(account)
+                    || m.matches(&posting.description)
+                    || m.matches(&payees.payee(posting))
+            }
+            Query::Not(q) => !q.matches(posting, payees),
+            Query::And(queries) => queries.iter().all(|q| q.matches(posting, payees)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches(posting, payees)),
+        }
+    }
+}
+
+/// name and value of a `name:value` tag. plain tags have no value
+fn split_tag(tag: &str) -> (&str, Option<&str>) {
+    match tag.split_once(':') {
+        Some((name, value)) => (name, Some(value)),
+        None => (tag, None),
+    }
+}
+
+/// whether `value` matches the value pattern of a `tag:` or `meta:` term.
+/// terms without pattern match any value
+fn value_matches(pattern: &Option<Matcher>, value: Option<&str>) -> bool {
+    match (pattern, value) {
+        (None, _) => true,
+        (Some(pattern), Some(value)) => pattern.matches(value),
+        (Some(_), None) => false,
+    }
+}
+
+/// field of postings that reports group postings by in place of their
+/// account, e.g. `tag:project` for the values of `project:NAME` tags
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pivot {
+    Payee,
+    Tag(String),
+    Meta(String),
+}
+
+impl Pivot {
+    pub fn parse(pivot: &str) -> Result<Pivot> {
+        match pivot.split_once(':') {
+            None if pivot == "payee" => Ok(Pivot::Payee),
+            Some(("tag", name)) if !name.is_empty() => Ok(Pivot::Tag(name.to_string())),
+            Some(("meta", key)) if !key.is_empty() => Ok(Pivot::Meta(key.to_string())),
+            _ => Err(Error::InvalidArg(format!(
+                "invalid pivot {}: expected payee, tag:NAME or meta:KEY",
+                pivot
+            ))),
+        }
+    }
+
+    pub fn from_args(pivot: &str) -> Result<Option<Pivot>> {
+        match pivot {
+            "" => Ok(None),
+            p => Pivot::parse(p).map(Some),
+        }
+    }
+
+    /// value of the field for a flattened posting. tags without a value
+    /// yield their name, and payees are normalized by `payees`
+    pub fn value(&self, posting: &Transaction, payees: &Payees) -> Option<String> {
+        match self {
+            Pivot::Payee => Some(payees.payee(posting)),
+            Pivot::Tag(name) => posting.tags.iter().flatten().find_map(|tag| {
+                let (tag, value) = split_tag(tag);
+                tag.eq_ignore_ascii_case(name)
+                    .then(|| value.unwrap_or(tag).to_string())
+            }),
+            Pivot::Meta(key) => posting.meta.as_ref().and_then(|m| m.get(key)).cloned(),
+        }
+    }
+}
+
+/// split a query at whitespace, except within quotes or `/regex/` values
+fn tokenize(query: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_regex = false;
+    let mut previous: Option<char> = None;
+
+    for c in query.chars() {
+        match c {
+            '"' | '\'' if quote.is_none() && !in_regex => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '/' if quote.is_none() => {
+                let value_start = token.is_empty() || previous == Some(':');
+                if in_regex && previous != Some('\\') {
+                    in_regex = false;
+                } else if value_start {
+                    in_regex = true;
+                }
+                token.push(c);
+            }
+            c if c.is_whitespace() && quote.is_none() && !in_regex => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
+        }
+        previous = Some(c);
+    }
+
+    if quote.is_some() || in_regex {
+        return Err(Error::InvalidArg(format!("unterminated query: {}", query)));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+fn posting(account: &str, description: &str, amount: Decimal, date: &str) -> Transaction {
+    Transaction {
+        date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+        account: Some(account.to_string()),
+        amount: Some(amount),
+        currency: Some("USD".to_string()),
+        description: description.to_string(),
+        offset_account: None,
+        assert_balance: None,
+        transactions: None,
+        external_id: None,
+        payee: None,
+        tags: None,
+        meta: None,
+        status: None,
+        code: None,
+        comment: None,
+    }
+}
+
+#[cfg(test)]
+use crate::ledger::LedgerFile;
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[test]
+fn tokenize_respects_quotes_and_regexes() {
+    assert_eq!(
+        tokenize(r#"desc:"weekly groceries" desc:/shell gas/i acct:expense"#).unwrap(),
+        vec!["desc:weekly groceries", "desc:/shell gas/i", "acct:expense"]
+    );
+    assert!(tokenize("desc:\"open").is_err());
+}
+
+#[test]
+fn query_terms_are_combined() {
+    let today = NaiveDate::from_ymd(2024, 5, 14);
+    let query = Query::parse_at(
+        "acct:expense:auto desc:/shell/i amt:>100 date:2024-01.. not:acct:equity",
+        today,
+    )
+    .unwrap();
+    let payees = Payees::new(&LedgerFile::default());
+
+    assert!(query.matches(
+        &posting(
+            "expense:auto:fuel",
+            "SHELL station",
+            dec!(120),
+            "2024-02-01"
+        ),
+        &payees
+    ));
+    assert!(!query.matches(
+        &posting(
+            "expense:auto:fuel",
+            "SHELL station",
+            dec!(100),
+            "2024-02-01"
+        ),
+        &payees
+    ));
+    assert!(!query.matches(
+        &posting("expense:auto:fuel", "shell", dec!(120), "2023-12-31"),
+        &payees
+    ));
+    assert!(!query.matches(
+        &posting("expense:food", "shell", dec!(120), "2024-02-01"),
+        &payees
+    ));
+}
+
+#[test]
+fn query_alternatives_and_negation() {
+    let query = Query::parse("acct:income or not:amt:<0").unwrap();
+    let payees = Payees::new(&LedgerFile::default());
+
+    assert!(query.matches(
+        &posting("income:salary", "pay", dec!(-10), "2020-01-01"),
+        &payees
+    ));
+    assert!(query.matches(
+        &posting("asset:cash", "pay", dec!(10), "2020-01-01"),
+        &payees
+    ));
+    assert!(!query.matches(
+        &posting("asset:cash", "pay", dec!(-10), "2020-01-01"),
+        &payees
+    ));
+
+    // amounts are compared as numbers rather than as text
+    let query = Query::parse("amt:42").unwrap();
+    assert!(query.matches(
+        &posting("asset:cash", "x", dec!(42.00), "2042-01-01"),
+        &payees
+    ));
+    assert!(!query.matches(
+        &posting("asset:cash", "x", dec!(142), "2042-01-01"),
+        &payees
+    ));
+}
+
+#[test]
+fn query_tags_and_metadata() {
+    let mut p = posting("expense:travel", "train", dec!(80), "2020-01-01");
+    p.tags = Some(vec!["Trip".to_string(), "project:house".to_string()]);
+    p.meta = Some([("invoice".to_string(), "2020-14".to_string())].into());
+
+    let payees = Payees::new(&LedgerFile::default());
+    let matches = |query: &str| Query::parse(query).unwrap().matches(&p, &payees);
+    assert!(matches("tag:trip"));
+    assert!(matches("tag:project"));
+    assert!(matches("tag:project=hou"));
+    assert!(!matches("tag:project=garden"));
+    assert!(!matches("tag:trip=x"));
+    assert!(!matches("tag:work"));
+    assert!(matches("meta:invoice"));
+    assert!(matches("meta:invoice=/^2020-/"));
+    assert!(!matches("meta:receipt"));
+    assert!(matches("not:tag:work"));
+}
+
+#[test]
+fn query_payees() {
+    let file = LedgerFile::parse(
+        "currency: USD
+accounts: []
+transactions: []
+payee_aliases:
+  - payee: Amazon
+    match: [/^AMZN MKTP/]
+",
+    )
+    .unwrap();
+    let payees = Payees::new(&file);
+    let p = posting(
+        "expense:shopping",
+        "AMZN MKTP US*2K3",
+        dec!(25),
+        "2020-01-01",
+    );
+
+    let matches = |query: &str| Query::parse(query).unwrap().matches(&p, &payees);
+    assert!(matches("payee:amazon"));
+    assert!(matches("payee:/^Amazon$/"));
+    assert!(!matches("payee:amzn"));
+    assert!(matches("amazon"));
+    assert!(matches("amzn"));
+    assert!(!matches("desc:amazon"));
+}
+
+#[test]
+fn pivot_values() {
+    let mut p = posting("expense:travel", "train", dec!(80), "2020-01-01");
+    p.tags = Some(vec!["trip".to_string(), "project:house".to_string()]);
+    p.meta = Some([("invoice".to_string(), "2020-14".to_string())].into());
+
+    let payees = Payees::new(&LedgerFile::default());
+    let value = |pivot: &str| Pivot::parse(pivot).unwrap().value(&p, &payees);
+    assert_eq!(value("tag:Project"), Some("house".to_string()));
+    assert_eq!(value("tag:trip"), Some("trip".to_string()));
+    assert_eq!(value("tag:work"), None);
+    assert_eq!(value("meta:invoice"), Some("2020-14".to_string()));
+    assert_eq!(value("payee"), Some("train".to_string()));
+    assert!(Pivot::parse("payees").is_err());
+    assert!(Pivot::from_args("").unwrap().is_none());
+}
+
+#[test]
+fn invalid_query_terms_are_errors() {
+    assert!(Query::parse("amt:>abc").is_err());
+    assert!(Query::parse("desc:/(/").is_err());
+    assert!(Query::parse("date:someday").is_err());
+    assert!(Query::parse("tag:").is_err());
+    assert!(Query::parse("meta:=scans").is_err());
+    assert!(Query::parse("").unwrap().is_any());
+}
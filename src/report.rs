@@ -0,0 +1,1088 @@
+use crate::account_tree::Layout;
+use crate::commodity::{Commodity, Totals, Unit};
+use crate::error::{Error, Result};
+use chrono::NaiveDate;
+use prettytable::format::Alignment;
+use prettytable::{format, Cell, Row, Table};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, stdout, Write};
+
+/// enumerates the formats reports can be written in
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Csv,
+    Tsv,
+    Json,
+    Yaml,
+    Markdown,
+    Html,
+}
+
+impl OutputFormat {
+    pub fn parse(format: &str) -> Result<OutputFormat> {
+        match format {
+            "" | "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            f => Err(Error::InvalidArg(format!("invalid output format: {}", f))),
+        }
+    }
+}
+
+/// format and destination of reports. reports are written to stdout
+/// unless a `file` is given
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub file: Option<String>,
+}
+
+/// report that can be rendered in every `OutputFormat`. `table` lays the
+/// report out for a terminal, `records` lists a header followed by one
+/// record per row with plain amounts, which the csv, tsv, markdown and
+/// html formats are built from. json and yaml serialize the report itself.
+/// `rescale` gives every amount the decimal places of the minor unit of
+/// its currency before the report is rendered
+pub trait Report: Serialize {
+    fn table(&self) -> Result<Table>;
+    fn records(&self) -> Vec<Vec<String>>;
+    fn rescale(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// rescale `amount` to the minor unit of `code`, e.g. `0` USD to `0.00`
+fn rescale(amount: &mut Decimal, code: &str, commodities: &[Commodity]) -> Result<()> {
+    amount.rescale(Unit::find(code, commodities)?.exponent());
+    Ok(())
+}
+
+fn rescale_totals(totals: &mut Totals, commodities: &[Commodity]) -> Result<()> {
+    for (code, amount) in totals.iter_mut() {
+        rescale(amount, code, commodities)?;
+    }
+    Ok(())
+}
+
+impl Output {
+    /// build an `Output` from command line arguments
+    pub fn from_args(format: &str, file: &str) -> Result<Output> {
+        Ok(Output {
+            format: OutputFormat::parse(format)?,
+            file: match file {
+                "" => None,
+                f => Some(f.to_string()),
+            },
+        })
+    }
+
+    pub fn write<R: Report>(&self, report: R) -> Result<()> {
+        match &self.file {
+            // printing directly keeps the styling of terminal tables
+            None if self.format == OutputFormat::Table => {
+                report.table()?.printstd();
+                Ok(())
+            }
+            None => Ok(stdout().write_all(self.render(report)?.as_bytes())?),
+            Some(file) => Ok(fs::write(file, self.render(report)?)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file, e)))?),
+        }
+    }
+
+    pub fn render<R: Report>(&self, mut report: R) -> Result<String> {
+        report.rescale()?;
+
+        match self.format {
+            OutputFormat::Table => Ok(report.table()?.to_string()),
+            OutputFormat::Csv => delimited(&report.records(), b','),
+            OutputFormat::Tsv => delimited(&report.records(), b'\t'),
+            OutputFormat::Json => serde_json::to_string_pretty(&report)
+                .map(|json| json + "\n")
+                .map_err(|e| Error::IO(e.into())),
+            OutputFormat::Yaml => crate::yaml::to_string(&report),
+            OutputFormat::Markdown => Ok(markdown(&report.records())),
+            OutputFormat::Html => Ok(html(&report.records())),
+        }
+    }
+}
+
+fn delimited(records: &[Vec<String>], delimiter: u8) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+
+    for record in records {
+        writer.write_record(record)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| io::Error::new(e.error().kind(), e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| Error::IO(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+fn markdown(records: &[Vec<String>]) -> String {
+    let line = |record: &[String]| {
+        let cells: Vec<String> = record.iter().map(|c| c.replace('|', "\\|")).collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    let mut rendered = String::new();
+    for (i, record) in records.iter().enumerate() {
+        rendered.push_str(&line(record));
+        if i == 0 {
+            rendered.push_str(&line(&vec!["---".to_string(); record.len()]));
+        }
+    }
+    rendered
+}
+
+fn html(records: &[Vec<String>]) -> String {
+    let escape = |c: &str| {
+        c.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let line = |record: &[String], tag: &str| {
+        let cells: Vec<String> = record
+            .iter()
+            .map(|c| format!("<{}>{}</{}>", tag, escape(c), tag))
+            .collect();
+        format!("    <tr>{}</tr>\n", cells.join(""))
+    };
+
+    let mut rendered = String::from("<table>\n");
+    if let Some((header, rows)) = records.split_first() {
+        rendered.push_str("  <thead>\n");
+        rendered.push_str(&line(header, "th"));
+        rendered.push_str("  </thead>\n  <tbody>\n");
+        for row in rows {
+            rendered.push_str(&line(row, "td"));
+        }
+        rendered.push_str("  </tbody>\n");
+    }
+    rendered.push_str("</table>\n");
+    rendered
+}
+
+fn new_table(titles: Row) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(titles);
+    table
+}
+
+fn header(titles: &[&str]) -> Vec<String> {
+    titles.iter().map(|t| t.to_string()).collect()
+}
+
+/// list of every account of the ledger file
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AccountList {
+    pub accounts: Vec<String>,
+}
+
+impl Report for AccountList {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Account"]);
+        for account in &self.accounts {
+            table.add_row(row![account]);
+        }
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&["account"])];
+        records.extend(self.accounts.iter().map(|a| vec![a.clone()]));
+        records
+    }
+}
+
+/// balance of an account in one currency. `depth` is the level of the
+/// account within the account tree, where top level accounts are 1
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct BalanceRow {
+    pub account: String,
+    pub name: String,
+    pub depth: usize,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+impl BalanceRow {
+    /// one row per currency of `totals`. zero totals are only kept when
+    /// the account holds nothing else. `negate` reverses the sign of
+    /// credit balances for display
+    pub fn from_totals(
+        account: &str,
+        name: &str,
+        depth: usize,
+        totals: Totals,
+        negate: bool,
+    ) -> Vec<BalanceRow> {
+        let has_balance = totals.values().any(|a| !a.is_zero());
+
+        totals
+            .into_iter()
+            .filter(|(_, amount)| !(amount.is_zero() && has_balance))
+            .map(|(currency, amount)| BalanceRow {
+                account: account.to_string(),
+                name: name.to_string(),
+                depth,
+                currency,
+                amount: if negate { -amount } else { amount },
+            })
+            .collect()
+    }
+}
+
+/// balance of every account along with the check figure per currency,
+/// which is zero when the ledger balances
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BalanceReport {
+    #[serde(skip)]
+    pub layout: Layout,
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    pub rows: Vec<BalanceRow>,
+    pub check: Totals,
+}
+
+impl Report for BalanceReport {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Account", "Balance"]);
+        let mut current_account_type = "";
+
+        for row in &self.rows {
+            let unit = Unit::find(&row.currency, &self.commodities)?;
+
+            match self.layout {
+                Layout::Tree => {
+                    let label = format!("{}{}", "  ".repeat(row.depth - 1), row.name);
+                    table.add_row(row![label, unit.format(row.amount)]);
+                }
+                Layout::Flat => {
+                    let account_type = row.account.split(':').next().unwrap_or("");
+                    if row.depth > 1 && current_account_type != account_type {
+                        current_account_type = account_type;
+                        table.add_row(row![current_account_type]);
+                    }
+                    table.add_row(row![r->row.account, unit.format(row.amount)]);
+                }
+            };
+        }
+
+        table.add_empty_row();
+        for (code, check_figure) in &self.check {
+            if self.check.len() == 1 {
+                table.add_row(row!["check", check_figure.normalize()]);
+            } else {
+                table.add_row(row![format!("check ({})", code), check_figure.normalize()]);
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&["account", "depth", "currency", "amount"])];
+        records.extend(self.rows.iter().map(|r| {
+            vec![
+                r.account.clone(),
+                r.depth.to_string(),
+                r.currency.clone(),
+                r.amount.to_string(),
+            ]
+        }));
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for r in &mut self.rows {
+            rescale(&mut r.amount, &r.currency, &self.commodities)?;
+        }
+        rescale_totals(&mut self.check, &self.commodities)
+    }
+}
+
+/// accounts of one type within a financial statement, along with their
+/// total. credit balances are shown as positive amounts
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StatementSection {
+    pub title: String,
+    pub rows: Vec<BalanceRow>,
+    pub total: Totals,
+}
+
+/// financial statement such as the balance sheet or the income statement,
+/// made up of sections followed by a `summary` total such as net worth
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Statement {
+    #[serde(skip)]
+    pub heading: String,
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    pub sections: Vec<StatementSection>,
+    pub summary: String,
+    pub total: Totals,
+}
+
+impl Statement {
+    /// add one row per currency of `totals`. zero totals are only shown
+    /// when there is nothing else to show
+    fn add_totals(&self, table: &mut Table, label: &str, totals: &Totals) -> Result<()> {
+        let rows = BalanceRow::from_totals(label, label, 0, totals.clone(), false);
+
+        if rows.is_empty() {
+            table.add_row(row![label, "0"]);
+        }
+        for row in rows {
+            let unit = Unit::find(&row.currency, &self.commodities)?;
+            table.add_row(row![label, unit.format(row.amount)]);
+        }
+
+        Ok(())
+    }
+}
+
+impl Report for Statement {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Account", self.heading]);
+
+        for section in &self.sections {
+            table.add_row(row![b->section.title]);
+
+            for row in &section.rows {
+                let label = format!("{}{}", "  ".repeat(row.depth), row.name);
+                let unit = Unit::find(&row.currency, &self.commodities)?;
+                table.add_row(row![label, unit.format(row.amount)]);
+            }
+
+            let label = format!("total {}", section.title);
+            self.add_totals(&mut table, &label, &section.total)?;
+            table.add_empty_row();
+        }
+        self.add_totals(&mut table, &self.summary, &self.total)?;
+
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&[
+            "section", "account", "depth", "currency", "amount",
+        ])];
+        let record = |section: &str, account: &str, depth: String, code: &str, amount: &Decimal| {
+            vec![
+                section.to_string(),
+                account.to_string(),
+                depth,
+                code.to_string(),
+                amount.to_string(),
+            ]
+        };
+
+        for section in &self.sections {
+            for r in &section.rows {
+                let depth = r.depth.to_string();
+                records.push(record(
+                    &section.title,
+                    &r.account,
+                    depth,
+                    &r.currency,
+                    &r.amount,
+                ));
+            }
+            let label = format!("total {}", section.title);
+            for (code, amount) in &section.total {
+                records.push(record(&section.title, &label, String::new(), code, amount));
+            }
+        }
+        for (code, amount) in &self.total {
+            records.push(record("", &self.summary, String::new(), code, amount));
+        }
+
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for section in &mut self.sections {
+            for r in &mut section.rows {
+                rescale(&mut r.amount, &r.currency, &self.commodities)?;
+            }
+            rescale_totals(&mut section.total, &self.commodities)?;
+        }
+        rescale_totals(&mut self.total, &self.commodities)
+    }
+}
+
+/// posting of a general ledger register
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RegisterRow {
+    pub date: NaiveDate,
+    pub description: String,
+    pub account: String,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+/// every posting of the general ledger
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RegisterReport {
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    pub rows: Vec<RegisterRow>,
+}
+
+impl Report for RegisterReport {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Date", "Description", "Account", "Amount"]);
+        for r in &self.rows {
+            let unit = Unit::find(&r.currency, &self.commodities)?;
+            table.add_row(row![
+                r.date,
+                r.description,
+                r.account,
+                unit.format(r.amount)
+            ]);
+        }
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&[
+            "date",
+            "description",
+            "account",
+            "currency",
+            "amount",
+        ])];
+        records.extend(self.rows.iter().map(|r| {
+            vec![
+                r.date.to_string(),
+                r.description.clone(),
+                r.account.clone(),
+                r.currency.clone(),
+                r.amount.to_string(),
+            ]
+        }));
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for r in &mut self.rows {
+            rescale(&mut r.amount, &r.currency, &self.commodities)?;
+        }
+        Ok(())
+    }
+}
+
+/// posting to a single account along with the other accounts of its
+/// transaction and, for running registers, the balance of the account
+/// after the posting
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AccountPosting {
+    pub date: NaiveDate,
+    pub description: String,
+    pub account: String,
+    pub counter_accounts: Vec<String>,
+    pub currency: String,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<Decimal>,
+}
+
+/// postings to an account and its sub-accounts. running registers start
+/// from the `opening` balance carried into the date range, if any
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AccountRegister {
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    #[serde(skip)]
+    pub reverse: bool,
+    pub account: String,
+    pub begin: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening: Option<Totals>,
+    pub postings: Vec<AccountPosting>,
+}
+
+impl AccountRegister {
+    fn running(&self) -> bool {
+        self.opening.is_some()
+    }
+}
+
+impl Report for AccountRegister {
+    fn table(&self) -> Result<Table> {
+        let mut titles = row![
+            "Date",
+            "Description",
+            "Account",
+            "Counter Account",
+            "Amount"
+        ];
+        if self.running() {
+            titles.add_cell(Cell::new("Balance"));
+        }
+        let mut table = new_table(titles);
+
+        let mut opening_rows = Vec::new();
+        for (code, amount) in self.opening.iter().flatten() {
+            let unit = Unit::find(code, &self.commodities)?;
+            let date = self.begin.map(|d| d.to_string()).unwrap_or_default();
+            opening_rows.push(row![
+                date,
+                "opening balance",
+                self.account,
+                "",
+                "",
+                unit.format(*amount)
+            ]);
+        }
+
+        if !self.reverse {
+            for row in opening_rows.drain(..) {
+                table.add_row(row);
+            }
+        }
+
+        for p in &self.postings {
+            let unit = Unit::find(&p.currency, &self.commodities)?;
+            let mut row = row![
+                p.date,
+                p.description,
+                p.account,
+                p.counter_accounts.join(", "),
+                unit.format(p.amount)
+            ];
+            if let Some(balance) = p.balance {
+                row.add_cell(Cell::new(&unit.format(balance)));
+            }
+            table.add_row(row);
+        }
+
+        for row in opening_rows {
+            table.add_row(row);
+        }
+
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut titles = vec![
+            "date",
+            "description",
+            "account",
+            "counter_accounts",
+            "currency",
+            "amount",
+        ];
+        if self.running() {
+            titles.push("balance");
+        }
+
+        let mut opening_records = Vec::new();
+        for (code, amount) in self.opening.iter().flatten() {
+            opening_records.push(vec![
+                self.begin.map(|d| d.to_string()).unwrap_or_default(),
+                "opening balance".to_string(),
+                self.account.clone(),
+                String::new(),
+                code.clone(),
+                String::new(),
+                amount.to_string(),
+            ]);
+        }
+
+        let mut records = vec![header(&titles)];
+        if !self.reverse {
+            records.append(&mut opening_records);
+        }
+        for p in &self.postings {
+            let mut record = vec![
+                p.date.to_string(),
+                p.description.clone(),
+                p.account.clone(),
+                p.counter_accounts.join(", "),
+                p.currency.clone(),
+                p.amount.to_string(),
+            ];
+            if let Some(balance) = p.balance {
+                record.push(balance.to_string());
+            }
+            records.push(record);
+        }
+        records.append(&mut opening_records);
+
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        if let Some(opening) = &mut self.opening {
+            rescale_totals(opening, &self.commodities)?;
+        }
+        for p in &mut self.postings {
+            rescale(&mut p.amount, &p.currency, &self.commodities)?;
+            if let Some(balance) = &mut p.balance {
+                rescale(balance, &p.currency, &self.commodities)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// total of an account in one currency over a period
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PeriodRow {
+    pub period: String,
+    pub account: String,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+/// totals of each account per period
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PeriodReport {
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    pub rows: Vec<PeriodRow>,
+}
+
+impl Report for PeriodReport {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Date / Account", "Total"]);
+        let mut current_period = "";
+
+        for r in &self.rows {
+            if current_period != r.period {
+                current_period = &r.period;
+                table.add_row(row![current_period]);
+            }
+            let unit = Unit::find(&r.currency, &self.commodities)?;
+            table.add_row(row![r.account, unit.format(r.amount)]);
+        }
+
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&["period", "account", "currency", "amount"])];
+        records.extend(self.rows.iter().map(|r| {
+            vec![
+                r.period.clone(),
+                r.account.clone(),
+                r.currency.clone(),
+                r.amount.to_string(),
+            ]
+        }));
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for r in &mut self.rows {
+            rescale(&mut r.amount, &r.currency, &self.commodities)?;
+        }
+        Ok(())
+    }
+}
+
+/// budgeted and actual amount of an account in one currency over a period
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BudgetRow {
+    pub period: String,
+    pub account: String,
+    pub currency: String,
+    pub budget: Decimal,
+    pub actual: Decimal,
+    pub delta: Decimal,
+}
+
+/// budgeted and actual amounts of each account per period
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BudgetReport {
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    pub rows: Vec<BudgetRow>,
+}
+
+impl Report for BudgetReport {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Date / Account", "Budget", "Actual", "Delta"]);
+        let mut current_period = "";
+
+        for r in &self.rows {
+            if current_period != r.period {
+                current_period = &r.period;
+                table.add_row(row![current_period]);
+            }
+            let unit = Unit::find(&r.currency, &self.commodities)?;
+            table.add_row(row![
+                r.account,
+                unit.format(r.budget),
+                unit.format(r.actual),
+                unit.format(r.delta),
+            ]);
+        }
+
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&[
+            "period", "account", "currency", "budget", "actual", "delta",
+        ])];
+        records.extend(self.rows.iter().map(|r| {
+            vec![
+                r.period.clone(),
+                r.account.clone(),
+                r.currency.clone(),
+                r.budget.to_string(),
+                r.actual.to_string(),
+                r.delta.to_string(),
+            ]
+        }));
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for r in &mut self.rows {
+            for amount in [&mut r.budget, &mut r.actual, &mut r.delta] {
+                rescale(amount, &r.currency, &self.commodities)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// amounts of an account in one currency per period, followed by their
+/// total and average and, for budget reports, the budget per period
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ColumnRow {
+    pub account: String,
+    pub currency: String,
+    pub amounts: Vec<Decimal>,
+    pub total: Decimal,
+    pub average: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<Decimal>,
+}
+
+impl ColumnRow {
+    /// row of `amounts` in `unit`. the average is rounded to the minor
+    /// unit
+    pub fn new(account: &str, unit: &Unit, amounts: Vec<Decimal>, budget: Option<Decimal>) -> Self {
+        let total: Decimal = amounts.iter().sum();
+        let average = match amounts.len() {
+            0 => Decimal::ZERO,
+            n => (total / Decimal::from(n)).round_dp(unit.exponent()),
+        };
+
+        ColumnRow {
+            account: account.to_string(),
+            currency: unit.code().to_string(),
+            amounts,
+            total,
+            average,
+            budget,
+        }
+    }
+}
+
+/// accounts as rows and periods as columns, followed by a grand total
+/// row per currency
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ColumnReport {
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    #[serde(skip)]
+    pub with_budget: bool,
+    pub periods: Vec<String>,
+    pub rows: Vec<ColumnRow>,
+    pub totals: Vec<ColumnRow>,
+}
+
+impl Report for ColumnReport {
+    fn table(&self) -> Result<Table> {
+        let mut titles = vec![Cell::new("Account")];
+        titles.extend(self.periods.iter().map(|p| Cell::new(p)));
+        titles.push(Cell::new("Total"));
+        titles.push(Cell::new("Average"));
+        if self.with_budget {
+            titles.push(Cell::new("Budget"));
+        }
+        let mut table = new_table(Row::new(titles));
+
+        for r in self.rows.iter().chain(&self.totals) {
+            let unit = Unit::find(&r.currency, &self.commodities)?;
+            let cell = |amount: &Decimal| Cell::new_align(&unit.format(*amount), Alignment::RIGHT);
+
+            let mut cells = vec![Cell::new(&r.account)];
+            cells.extend(r.amounts.iter().map(cell));
+            cells.push(cell(&r.total));
+            cells.push(cell(&r.average));
+            if self.with_budget {
+                let budget = r.budget.map(|b| unit.format(b)).unwrap_or_default();
+                cells.push(Cell::new_align(&budget, Alignment::RIGHT));
+            }
+            table.add_row(Row::new(cells));
+        }
+
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut titles = vec!["account".to_string(), "currency".to_string()];
+        titles.extend(self.periods.iter().cloned());
+        titles.push("total".to_string());
+        titles.push("average".to_string());
+        if self.with_budget {
+            titles.push("budget".to_string());
+        }
+
+        let mut records = vec![titles];
+        for r in self.rows.iter().chain(&self.totals) {
+            let mut record = vec![r.account.clone(), r.currency.clone()];
+            record.extend(r.amounts.iter().map(|a| a.to_string()));
+            record.push(r.total.to_string());
+            record.push(r.average.to_string());
+            if self.with_budget {
+                record.push(r.budget.map(|b| b.to_string()).unwrap_or_default());
+            }
+            records.push(record);
+        }
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for r in self.rows.iter_mut().chain(&mut self.totals) {
+            let amounts = r.amounts.iter_mut().chain([&mut r.total, &mut r.average]);
+            for amount in amounts.chain(&mut r.budget) {
+                rescale(amount, &r.currency, &self.commodities)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// number of transactions of a payee along with their total in one
+/// currency
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PayeeRow {
+    pub payee: String,
+    pub transactions: usize,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+/// every payee of the general ledger
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PayeeReport {
+    #[serde(skip)]
+    pub commodities: Vec<Commodity>,
+    pub rows: Vec<PayeeRow>,
+}
+
+impl Report for PayeeReport {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Payee", "Transactions", "Total"]);
+        for r in &self.rows {
+            let unit = Unit::find(&r.currency, &self.commodities)?;
+            table.add_row(row![r.payee, r->r.transactions, unit.format(r.amount)]);
+        }
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&["payee", "transactions", "currency", "amount"])];
+        records.extend(self.rows.iter().map(|r| {
+            vec![
+                r.payee.clone(),
+                r.transactions.to_string(),
+                r.currency.clone(),
+                r.amount.to_string(),
+            ]
+        }));
+        records
+    }
+
+    fn rescale(&mut self) -> Result<()> {
+        for r in &mut self.rows {
+            rescale(&mut r.amount, &r.currency, &self.commodities)?;
+        }
+        Ok(())
+    }
+}
+
+/// field assigned to a transaction by categorization, along with the rule
+/// that assigned it
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct RuleTestRow {
+    pub field: String,
+    pub value: String,
+    pub rule: String,
+}
+
+/// outcome of categorizing a single transaction with the rules of the
+/// ledger file
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RuleTestReport {
+    pub rows: Vec<RuleTestRow>,
+}
+
+impl Report for RuleTestReport {
+    fn table(&self) -> Result<Table> {
+        let mut table = new_table(row!["Field", "Value", "Rule"]);
+        for r in &self.rows {
+            table.add_row(row![r.field, r.value, r.rule]);
+        }
+        Ok(table)
+    }
+
+    fn records(&self) -> Vec<Vec<String>> {
+        let mut records = vec![header(&["field", "value", "rule"])];
+        records.extend(
+            self.rows
+                .iter()
+                .map(|r| vec![r.field.clone(), r.value.clone(), r.rule.clone()]),
+        );
+        records
+    }
+}
+
+#[cfg(test)]
+use rust_decimal_macros::dec;
+
+#[cfg(test)]
+fn get_report() -> RegisterReport {
+    RegisterReport {
+        commodities: Vec::new(),
+        rows: vec![RegisterRow {
+            date: NaiveDate::from_ymd(2020, 1, 1),
+            description: "fish | chips <to go>".to_string(),
+            account: "expense:food".to_string(),
+            currency: "USD".to_string(),
+            amount: dec!(12.50),
+        }],
+    }
+}
+
+#[test]
+fn render_delimited_formats() {
+    let output = |format| Output { format, file: None }.render(get_report()).unwrap();
+
+    assert_eq!(
+        output(OutputFormat::Csv),
+        "date,description,account,currency,amount\n\
+         2020-01-01,fish | chips <to go>,expense:food,USD,12.50\n"
+    );
+    assert_eq!(
+        output(OutputFormat::Tsv).lines().nth(1),
+        Some("2020-01-01\tfish | chips <to go>\texpense:food\tUSD\t12.50")
+    );
+}
+
+#[test]
+fn render_markup_formats_escape_cells() {
+    let output = |format| Output { format, file: None }.render(get_report()).unwrap();
+
+    let markdown = output(OutputFormat::Markdown);
+    assert_eq!(
+        markdown.lines().nth(1),
+        Some("| --- | --- | --- | --- | --- |")
+    );
+    assert!(markdown.contains("| fish \\| chips <to go> |"));
+
+    let html = output(OutputFormat::Html);
+    assert!(html.contains("<th>description</th>"));
+    assert!(html.contains("<td>fish | chips &lt;to go&gt;</td>"));
+}
+
+#[test]
+fn render_serialized_formats() {
+    let output = |format| Output { format, file: None }.render(get_report()).unwrap();
+
+    assert!(output(OutputFormat::Json).contains("\"description\": \"fish | chips <to go>\""));
+    assert!(output(OutputFormat::Yaml).contains("account: \"expense:food\""));
+    assert!(output(OutputFormat::Table).contains("$12.50"));
+    assert!(OutputFormat::parse("xml").is_err());
+    assert_eq!(OutputFormat::parse("").unwrap(), OutputFormat::Table);
+}
+
+#[test]
+fn column_averages_are_rounded_to_the_minor_unit() {
+    let unit = Unit::find("USD", &[]).unwrap();
+    let report = || ColumnReport {
+        commodities: Vec::new(),
+        with_budget: false,
+        periods: vec![
+            "2020-01".to_string(),
+            "2020-02".to_string(),
+            "2020-03".to_string(),
+        ],
+        rows: vec![ColumnRow::new(
+            "expense:food",
+            &unit,
+            vec![dec!(-100.01), dec!(0.00), dec!(0.00)],
+            None,
+        )],
+        totals: Vec::new(),
+    };
+    let output = |format| Output { format, file: None }.render(report()).unwrap();
+
+    assert_eq!(
+        output(OutputFormat::Csv).lines().nth(1),
+        Some("expense:food,USD,-100.01,0.00,0.00,-100.01,-33.34")
+    );
+    assert!(output(OutputFormat::Json).contains("\"average\": \"-33.34\""));
+}
+
+#[test]
+fn render_amounts_with_the_scale_of_their_currency() {
+    let report = || RegisterReport {
+        commodities: Vec::new(),
+        rows: [dec!(0), dec!(-100), dec!(-0.01), dec!(3.504)]
+            .into_iter()
+            .map(|amount| RegisterRow {
+                date: NaiveDate::from_ymd(2020, 1, 1),
+                description: "groceries".to_string(),
+                account: "expense:food".to_string(),
+                currency: "USD".to_string(),
+                amount,
+            })
+            .collect(),
+    };
+    let output = |format| Output { format, file: None }.render(report()).unwrap();
+
+    let amounts: Vec<String> = output(OutputFormat::Csv)
+        .lines()
+        .skip(1)
+        .map(|l| l.rsplit(',').next().unwrap().to_string())
+        .collect();
+    assert_eq!(amounts, vec!["0.00", "-100.00", "-0.01", "3.50"]);
+    assert!(output(OutputFormat::Json).contains("\"amount\": \"-100.00\""));
+    assert!(output(OutputFormat::Yaml).contains("amount: 0.00"));
+}
+
+#[test]
+fn write_failures_are_io_errors() {
+    let output = Output {
+        format: OutputFormat::Csv,
+        file: Some("/nonexistent/rust_ledger/report.csv".to_string()),
+    };
+
+    match output.write(get_report()) {
+        Err(Error::IO(e)) => assert!(e
+            .to_string()
+            .starts_with("/nonexistent/rust_ledger/report.csv: ")),
+        other => panic!("expected an I/O error, got {:?}", other),
+    }
+}
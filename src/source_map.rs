@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// line and column of every node in a YAML document, keyed by the path of
+/// the node, e.g. `transactions[2].amount`. used to point diagnostics at
+/// the offending position of a ledger file
+#[derive(Debug, PartialEq)]
+pub struct SourceMap {
+    nodes: HashMap<String, (usize, usize)>,
+}
+
+/// container that is currently being parsed, along with its path and the
+/// position of the next child node
+enum Frame {
+    Sequence(String, usize),
+    Mapping(String, Option<String>),
+}
+
+struct Builder {
+    nodes: HashMap<String, (usize, usize)>,
+    stack: Vec<Frame>,
+    pending: Vec<String>,
+}
+
+impl Builder {
+    /// path of the node that is about to be parsed
+    fn next_path(&mut self) -> String {
+        match self.stack.last_mut() {
+            None => String::new(),
+            Some(Frame::Sequence(path, index)) => {
+                let p = format!("{}[{}]", path, index);
+                *index += 1;
+                p
+            }
+            Some(Frame::Mapping(path, key)) => {
+                let key = key.take().unwrap_or_default();
+                if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                }
+            }
+        }
+    }
+
+    /// record the position of the node at `path`. containers are positioned
+    /// at their first scalar, as yaml-rust marks block mappings at the first
+    /// `:` rather than at the first key
+    fn record(&mut self, path: &str, mark: Marker) {
+        // yaml-rust lines are 1-indexed while columns are 0-indexed
+        let position = (mark.line(), mark.col() + 1);
+
+        for p in self.pending.drain(..) {
+            self.nodes.entry(p).or_insert(position);
+        }
+        self.nodes.entry(path.to_string()).or_insert(position);
+    }
+}
+
+impl MarkedEventReceiver for Builder {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                // scalars in key position name the next value of a mapping
+                if let Some(Frame::Mapping(path, key @ None)) = self.stack.last_mut() {
+                    *key = Some(value);
+                    let path = path.clone();
+                    self.record(&path, mark);
+                    return;
+                }
+                let path = self.next_path();
+                self.record(&path, mark);
+            }
+            Event::Alias(_) => {
+                let path = self.next_path();
+                self.record(&path, mark);
+            }
+            Event::SequenceStart(_) => {
+                let path = self.next_path();
+                self.pending.push(path.clone());
+                self.stack.push(Frame::Sequence(path, 0));
+            }
+            Event::MappingStart(_) => {
+                let path = self.next_path();
+                self.pending.push(path.clone());
+                self.stack.push(Frame::Mapping(path, None));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+            }
+            _ => (),
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn new(source: &str) -> SourceMap {
+        let mut builder = Builder {
+            nodes: HashMap::new(),
+            stack: Vec::new(),
+            pending: Vec::new(),
+        };
+
+        // documents that fail to parse are reported by serde_yaml, so any
+        // nodes recorded before the failure are kept as they are
+        let _ = Parser::new(source.chars()).load(&mut builder, false);
+
+        SourceMap {
+            nodes: builder.nodes,
+        }
+    }
+
+    /// line and column of the node at `path`. when the node does not exist,
+    /// e.g. a required key is missing, the closest parent node is used
+    pub fn lookup(&self, path: &str) -> Option<(usize, usize)> {
+        let mut path = path;
+
+        loop {
+            if let Some(position) = self.nodes.get(path) {
+                return Some(*position);
+            }
+
+            match path.rfind(['.', '[']) {
+                Some(i) => path = &path[..i],
+                None => return None,
+            }
+        }
+    }
+}
+
+#[test]
+fn lookup_nodes_by_path() {
+    let source = "currency: USD\n\
+                  transactions:\n  \
+                    - date: 2020-01-01\n    \
+                      description: stuff\n    \
+                      transactions:\n      \
+                        - amount: 10\n        \
+                          account: expense:foo\n";
+    let source_map = SourceMap::new(source);
+
+    assert_eq!(source_map.lookup("currency"), Some((1, 11)));
+    assert_eq!(source_map.lookup("transactions[0]"), Some((3, 5)));
+    assert_eq!(
+        source_map.lookup("transactions[0].description"),
+        Some((4, 18))
+    );
+    assert_eq!(
+        source_map.lookup("transactions[0].transactions[0].account"),
+        Some((7, 18))
+    );
+    assert_eq!(source_map.lookup("transactions[0].amount"), Some((3, 5)));
+    assert_eq!(
+        source_map.lookup("transactions[0].transactions"),
+        Some((6, 9))
+    );
+    assert_eq!(source_map.lookup("accounts"), None);
+}
@@ -0,0 +1,123 @@
+use crate::error::{Diagnostic, Error, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::str::FromStr;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter};
+
+/// keys of ledger files and reports whose values are amounts, or lists and
+/// maps of amounts
+const AMOUNT_KEYS: [&str; 15] = [
+    "amount",
+    "amounts",
+    "assert_balance",
+    "budget_month",
+    "budget_year",
+    "price",
+    "min_amount",
+    "max_amount",
+    "balance",
+    "opening",
+    "check",
+    "total",
+    "average",
+    "budget",
+    "actual",
+];
+
+/// keys whose values are data of the user, such as metadata, which are
+/// written as they are even under amount keys
+const DATA_KEYS: [&str; 1] = ["meta"];
+
+/// what the values below a key of a ledger file or report hold
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Node {
+    /// typed fields, whose keys tell whether they are amounts
+    Field,
+    /// amounts, or lists and maps of amounts
+    Amount,
+    /// data of the user, written as is
+    Data,
+}
+
+/// serialize `value` into yaml like `serde_yaml::to_string`, except that
+/// amounts are written as the exact decimal text they hold. decimals
+/// serialize as strings, which yaml would otherwise quote
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let yaml = to_yaml(serde_yaml::to_value(value)?, Node::Field);
+
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&yaml)
+        .map_err(|e| Error::Yaml(Diagnostic::new(e.to_string(), "")))?;
+    out.push('\n');
+
+    Ok(out)
+}
+
+fn to_yaml(value: Value, node: Node) -> Yaml {
+    match value {
+        Value::Null => Yaml::Null,
+        Value::Bool(b) => Yaml::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Yaml::Integer(i),
+            None => Yaml::Real(n.to_string()),
+        },
+        Value::String(s) if node == Node::Amount && Decimal::from_str(&s).is_ok() => Yaml::Real(s),
+        Value::String(s) => Yaml::String(s),
+        Value::Sequence(items) => {
+            Yaml::Array(items.into_iter().map(|v| to_yaml(v, node)).collect())
+        }
+        Value::Mapping(mapping) => {
+            let mut hash = Hash::new();
+            for (key, value) in mapping {
+                let child = match (node, key.as_str()) {
+                    (Node::Field, Some(k)) if AMOUNT_KEYS.contains(&k) => Node::Amount,
+                    (Node::Field, Some(k)) if DATA_KEYS.contains(&k) => Node::Data,
+                    (node, _) => node,
+                };
+                hash.insert(to_yaml(key, Node::Data), to_yaml(value, child));
+            }
+            Yaml::Hash(hash)
+        }
+    }
+}
+
+#[test]
+fn amounts_are_written_as_decimal_text() {
+    use crate::ledger::LedgerFile;
+
+    let file = LedgerFile::parse(
+        "currency: USD
+accounts:
+  - account: asset:cash
+    amount: 12345678901234567.89
+  - account: equity:opening
+    amount: -12345678901234567.89
+transactions:
+  - date: 2020-01-01
+    description: \"2020\"
+    code: \"1001\"
+    account: asset:cash
+    amount: 455.00
+    offset_account: equity:opening
+    meta:
+      rate: 1.50
+      amount: 5.00
+      total: \"-3\"
+",
+    )
+    .unwrap();
+
+    let yaml = to_string(&file).unwrap();
+    assert!(yaml.contains("amount: 12345678901234567.89\n"));
+    assert!(yaml.contains("amount: -12345678901234567.89\n"));
+    assert!(yaml.contains("amount: 455.00\n"));
+    assert!(yaml.contains("description: \"2020\"\n"));
+    assert!(yaml.contains("code: \"1001\"\n"));
+    assert!(yaml.contains("rate: \"1.50\"\n"));
+    assert!(yaml.contains("amount: \"5.00\"\n"));
+    assert!(yaml.contains("total: \"-3\"\n"));
+    assert_eq!(LedgerFile::parse(&yaml).unwrap(), file);
+}
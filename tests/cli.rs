@@ -6,7 +6,7 @@ mod test {
         let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
 
         let assert = cmd
-            .args(&["account", "-f", "./examples/example.yaml"])
+            .args(["account", "-f", "./examples/example.yaml"])
             .assert();
         assert.success();
     }
@@ -16,31 +16,372 @@ mod test {
         let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
 
         let assert = cmd
-            .args(&["balance", "-f", "./examples/example.yaml"])
+            .args(["balance", "-f", "./examples/example.yaml"])
             .assert();
         assert.success();
     }
 
+    #[test]
+    fn print_balance_tree_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args(["balance", "-f", "./examples/example.yaml", "--depth", "1"])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains(" expense   | $3,655.00"));
+        assert!(!stdout.contains("grocery"));
+    }
+
+    #[test]
+    fn print_flat_balances_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args(["balance", "-f", "./examples/example.yaml", "--flat"])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("expense:grocery | $635.00"));
+    }
+
+    #[test]
+    fn print_balance_sheet_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "balancesheet",
+                "-f",
+                "./examples/example.yaml",
+                "-e",
+                "2020-12-31",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("total liabilities         | $455.00"));
+        assert!(stdout.contains("current year earnings | -$2,900.00"));
+        assert!(stdout.contains("net worth                 | $145.00"));
+    }
+
+    #[test]
+    fn print_income_statement_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "incomestatement",
+                "-f",
+                "./examples/example.yaml",
+                "-b",
+                "2020-01-01",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("total expenses | $3,200.00"));
+        assert!(stdout.contains("net income     | -$2,900.00"));
+    }
+
     #[test]
     fn print_register_to_stdout() {
         let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
 
         let assert = cmd
-            .args(&["register", "-f", "./examples/example.yaml"])
+            .args(["register", "-f", "./examples/example.yaml"])
             .assert();
         assert.success();
     }
 
+    #[test]
+    fn print_register_columns_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "register",
+                "-f",
+                "./examples/example.yaml",
+                "-g",
+                "monthly",
+                "--columns",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("| 2019-12  | 2020-01    | Total      | Average"));
+        assert!(
+            stdout.contains("expense:grocery     |  $455.00 |    $180.00 |    $635.00 |   $317.50")
+        );
+    }
+
+    #[test]
+    fn print_register_for_period_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "register",
+                "-f",
+                "./examples/example.yaml",
+                "--period",
+                "2019-Q4",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("2019-12-31 | weekly groceries"));
+        assert!(!stdout.contains("2020-01-01"));
+    }
+
+    #[test]
+    fn print_register_for_query_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "register",
+                "-f",
+                "./examples/example.yaml",
+                "-q",
+                "acct:expense amt:>=200 not:desc:/^mort/i",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("weekly groceries | expense:grocery | $455.00"));
+        assert!(stdout.contains("stuff            | expense:general | $1,000.00"));
+        assert!(!stdout.contains("mortgage"));
+    }
+
+    #[test]
+    fn print_balance_pivoted_on_tag_to_stdout() {
+        let path = std::env::temp_dir().join("rust_ledger_pivot.yaml");
+        std::fs::write(
+            &path,
+            "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:travel
+    amount: 0
+transactions:
+  - date: 2024-03-01
+    description: flight
+    account: expense:travel
+    offset_account: asset:checking
+    amount: 600
+    tags: [trip, project:alpha]
+  - date: 2024-03-05
+    description: dinner
+    tags: [trip]
+    transactions:
+      - account: expense:travel
+        amount: 40
+        tags: [project:beta]
+      - account: asset:checking
+        amount: -40
+",
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "balance",
+                "-f",
+                path.to_str().unwrap(),
+                "--pivot",
+                "tag:project",
+                "-q",
+                "acct:expense",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains(" alpha   | $600.00 "));
+        assert!(stdout.contains(" beta    | $40.00 "));
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["balance", "-f", path.to_str().unwrap(), "--pivot", "trip"])
+            .assert()
+            .failure();
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+
+        assert!(stderr.contains("invalid pivot trip"));
+    }
+
+    #[test]
+    fn print_payees_to_stdout() {
+        let path = std::env::temp_dir().join("rust_ledger_payees.yaml");
+        std::fs::write(
+            &path,
+            "currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: expense:shopping
+    amount: 0
+transactions:
+  - date: 2024-03-01
+    description: AMZN MKTP US*2K3
+    account: expense:shopping
+    offset_account: asset:checking
+    amount: 25
+  - date: 2024-03-05
+    description: books
+    payee: Amazon.com
+    account: expense:shopping
+    offset_account: asset:checking
+    amount: 15.50
+  - date: 2024-03-06
+    description: corner store
+    account: expense:shopping
+    offset_account: asset:checking
+    amount: 5
+payee_aliases:
+  - payee: Amazon
+    match: [/^AMZN MKTP/, amazon.com]
+",
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["payees", "-f", path.to_str().unwrap(), "-O", "csv"])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert_eq!(
+            stdout,
+            "payee,transactions,currency,amount\nAmazon,2,USD,40.50\ncorner store,1,USD,5.00\n"
+        );
+
+        // queries match the normalized payee
+        for query in ["payee:amazon", "amazon"] {
+            let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+            let assert = cmd
+                .args(["register", "-f", path.to_str().unwrap(), "-q", query])
+                .assert();
+            let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+            assert!(stdout.contains("AMZN MKTP US*2K3"));
+            assert!(stdout.contains("books"));
+            assert!(!stdout.contains("corner store"));
+        }
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "payees",
+                "-f",
+                path.to_str().unwrap(),
+                "-q",
+                "payee:amazon",
+                "-O",
+                "csv",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert_eq!(
+            stdout,
+            "payee,transactions,currency,amount\nAmazon,2,USD,40.50\n"
+        );
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "register",
+                "-f",
+                path.to_str().unwrap(),
+                "--pivot",
+                "payee",
+                "-q",
+                "acct:expense",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("2024-03-01 | AMZN MKTP US*2K3 | Amazon       | $25.00"));
+        assert!(stdout.contains("2024-03-05 | books            | Amazon       | $15.50"));
+    }
+
+    #[test]
+    fn print_account_register_with_running_balance_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "register",
+                "-f",
+                "./examples/example.yaml",
+                "--account",
+                "asset:cash_checking",
+                "--running",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("opening balance    | asset:cash_checking |"));
+        assert!(stdout.contains("$1,500.00"));
+        assert!(stdout.contains("expense:general, expense:grocery | -$200.00   | -$700.00"));
+        assert!(stdout.contains("income:general                   | $300.00    | -$400.00"));
+    }
+
+    #[test]
+    fn account_register_carries_balance_into_range() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "register",
+                "-f",
+                "./examples/example.yaml",
+                "-a",
+                "liability",
+                "--running",
+                "--reverse",
+                "--begin",
+                "2020-01-01",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("2020-01-01 | opening balance | liability |"));
+        assert!(stdout.contains("-$455.00"));
+        assert!(!stdout.contains("weekly groceries"));
+    }
+
+    #[test]
+    fn invalid_period_is_error() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        cmd.args([
+            "balance",
+            "-f",
+            "./examples/example.yaml",
+            "--period",
+            "2020-Q5",
+        ])
+        .assert()
+        .failure()
+        .code(1);
+    }
+
     #[test]
     fn print_budget_to_stdout() {
         let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
 
         let assert = cmd
-            .args(&[
+            .args([
                 "budget",
                 "-f",
                 "./examples/example.yaml",
-                "-o",
+                "--period",
                 "2020",
                 "-g",
                 "yearly",
@@ -48,4 +389,615 @@ mod test {
             .assert();
         assert.success();
     }
+
+    #[test]
+    fn print_budget_columns_of_period_to_stdout() {
+        let path = std::env::temp_dir().join("rust_ledger_budget.yaml");
+        std::fs::write(
+            &path,
+            "currency: USD
+accounts:
+  - account: asset:cash
+    amount: 1000
+  - account: expense:grocery
+    amount: 0
+    budget_month: 100
+  - account: equity:opening
+    amount: -1000
+transactions:
+  - date: 2020-03-05
+    description: market
+    account: expense:grocery
+    amount: 40
+    offset_account: asset:cash
+  - date: 2021-03-05
+    description: market
+    account: expense:grocery
+    amount: 60
+    offset_account: asset:cash
+",
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "budget",
+                "-f",
+                path.to_str().unwrap(),
+                "-g",
+                "monthly",
+                "--period",
+                "2021-Q1",
+                "--columns",
+                "-O",
+                "csv",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert_eq!(
+            stdout,
+            "account,currency,2021-01,2021-02,2021-03,total,average,budget
+expense:grocery,USD,0.00,0.00,60.00,60.00,20.00,100.00
+total,USD,0.00,0.00,60.00,60.00,20.00,100.00
+"
+        );
+    }
+
+    #[test]
+    fn print_valued_balances_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "balance",
+                "-f",
+                "./examples/example.yaml",
+                "--value",
+                "USD",
+                "--at",
+                "2020-01-01",
+            ])
+            .assert();
+        assert.success();
+    }
+
+    #[test]
+    fn check_ledger_file() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args(["check", "-f", "./examples/example.yaml"])
+            .assert();
+        assert.success();
+    }
+
+    #[test]
+    fn check_exchanges_at_prices_when_strict() {
+        let path = std::env::temp_dir().join("rust_ledger_wire.yaml");
+        std::fs::write(
+            &path,
+            "currency: USD
+prices:
+  - date: 2020-01-01
+    commodity: EUR
+    price: 1.10
+    currency: USD
+accounts:
+  - account: asset:checking
+    amount: 0
+  - account: asset:eur_checking
+    amount: 0
+    currency: EUR
+transactions:
+  - date: 2020-01-05
+    description: wire
+    transactions:
+      - account: asset:checking
+        amount: -100
+      - account: asset:eur_checking
+        amount: 91.37
+",
+        )
+        .unwrap();
+        let filename = path.to_str().unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        cmd.args(["balance", "-f", filename]).assert().success();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["check", "-f", filename, "--strict-prices"])
+            .assert()
+            .failure();
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+
+        assert!(stderr.contains("postings sum to 0.51 USD at the prices of 2020-01-05"));
+    }
+
+    #[test]
+    fn invalid_ledger_file_reports_location() {
+        let path = std::env::temp_dir().join("rust_ledger_invalid.yaml");
+        std::fs::write(
+            &path,
+            "currency: USD\n\
+             accounts:\n  \
+               - account: asset:cash\n    \
+                 amount: 1.005\n\
+             transactions: []\n",
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["balance", "-f", path.to_str().unwrap()])
+            .assert()
+            .failure()
+            .code(1);
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+
+        assert!(stderr.contains("rust_ledger_invalid.yaml:4:13"));
+        assert!(stderr.contains("4 |     amount: 1.005"));
+    }
+
+    #[test]
+    fn print_balance_as_csv_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "balance",
+                "-f",
+                "./examples/example.yaml",
+                "--output-format",
+                "csv",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.starts_with("account,depth,currency,amount\n"));
+        assert!(stdout.contains("asset:cash_checking,2,USD,-400.00\n"));
+    }
+
+    #[test]
+    fn write_register_as_json_to_output_file() {
+        let path = std::env::temp_dir().join("rust_ledger_register.json");
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        cmd.args([
+            "-O",
+            "json",
+            "register",
+            "-f",
+            "./examples/example.yaml",
+            "--output-file",
+            path.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout("");
+        let json = std::fs::read_to_string(&path).unwrap();
+
+        assert!(json.starts_with("{\n  \"rows\": ["));
+        assert!(json.contains("\"description\": \"weekly groceries\""));
+    }
+
+    #[test]
+    fn print_exact_amounts_to_stdout() {
+        let path = std::env::temp_dir().join("rust_ledger_exact.yaml");
+        std::fs::write(
+            &path,
+            "currency: USD
+accounts:
+  - account: asset:cash
+    amount: 12345678901234567.89
+  - account: equity:opening
+    amount: -12345678901234567.89
+transactions: []
+",
+        )
+        .unwrap();
+
+        for (format, expected) in [
+            ("yaml", "amount: 12345678901234567.89\n"),
+            ("json", "\"amount\": \"12345678901234567.89\""),
+        ] {
+            let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+            let assert = cmd
+                .args(["-O", format, "balance", "-f", path.to_str().unwrap()])
+                .assert();
+            let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+            assert!(stdout.contains(expected), "{}", stdout);
+        }
+    }
+
+    #[test]
+    fn import_ofx_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "import",
+                "ofx",
+                "-f",
+                "./examples/example.yaml",
+                "-o",
+                "./examples/example.ofx",
+                "-a",
+                "asset:cash_checking",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("description: SHELL OIL & GAS"));
+        assert!(stdout.contains("assert_balance: 1754.9"));
+        assert!(stdout.contains("external_id: \"2020010301\""));
+    }
+
+    #[test]
+    fn import_ofx_in_declared_charset() {
+        let path = std::env::temp_dir().join("rust_ledger_1252.ofx");
+        let ofx = std::fs::read_to_string("./examples/example.ofx")
+            .unwrap()
+            .replace(
+                "VERSION:102\n",
+                "VERSION:102\nENCODING:USASCII\nCHARSET:1252\n",
+            )
+            .replace("SHELL OIL &amp; GAS", "CAFÉ DU MONDE");
+        let (bytes, _, _) = encoding_rs::WINDOWS_1252.encode(&ofx);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "import",
+                "ofx",
+                "-f",
+                "./examples/example.yaml",
+                "-o",
+                path.to_str().unwrap(),
+                "-a",
+                "asset:cash_checking",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert!(stdout.contains("description: CAFÉ DU MONDE\n"));
+    }
+
+    #[test]
+    fn csv_with_rules_to_stdout() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "csv",
+                "-f",
+                "./examples/example.yaml",
+                "-c",
+                "./examples/example.csv",
+                "-r",
+                "./examples/example.rules.yaml",
+            ])
+            .assert();
+        let assert = assert.success();
+        let output = assert.get_output();
+        let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+
+        assert!(stdout.contains("date: 2020-01-05"));
+        assert!(stdout.contains("amount: -1250.5"));
+        assert!(stdout.contains("offset_account: \"asset:cash_checking\""));
+        // the mortgage payment is already in the ledger file
+        assert!(!stdout.contains("date: 2020-01-03"));
+        assert!(stderr.contains("1 new, 1 skipped, 0 ambiguous"));
+        assert!(stderr.contains("2020-01-03 mortage 2000.00 (matches 2020-01-01 mortage)"));
+    }
+
+    #[test]
+    fn rules_test_shows_assigning_rules() {
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+
+        let assert = cmd
+            .args([
+                "rules",
+                "test",
+                "-f",
+                "./examples/example.yaml",
+                "-p",
+                "MORTGAGE PMT",
+                "--amount",
+                "2000",
+                "-a",
+                "asset:cash_checking",
+                "-O",
+                "csv",
+            ])
+            .assert();
+
+        assert.success().stdout(
+            "field,value,rule\n\
+             account,expense:mortgage,mortgage\n\
+             payee,Bank of Springfield,mortgage\n\
+             tag,house,mortgage\n",
+        );
+    }
+
+    #[test]
+    fn csv_lists_uncategorized_transactions() {
+        let args = [
+            "csv",
+            "-f",
+            "./examples/example.yaml",
+            "-c",
+            "./examples/example.csv",
+            "-r",
+            "./examples/example.rules.yaml",
+        ];
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd.args(args).assert().success();
+        let output = assert.get_output();
+        let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+
+        assert!(stdout.contains("account: \"expense:uncategorized\""));
+        assert!(stderr.contains(
+            "2020-01-05 donut sale -1250.50 (suggested income:general, confidence 0.40)"
+        ));
+        assert!(stderr.contains(
+            "accounts that are not declared in ./examples/example.yaml:\n  expense:uncategorized\n"
+        ));
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(args)
+            .args(["--min-confidence", "0.3"])
+            .assert()
+            .success();
+        let output = assert.get_output();
+
+        assert!(String::from_utf8(output.stdout.clone())
+            .unwrap()
+            .contains("account: \"income:general\""));
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+        assert!(!stderr.contains("could not be categorized"));
+        assert!(!stderr.contains("not declared"));
+    }
+
+    #[test]
+    fn csv_write_merges_into_ledger_file() {
+        let path = std::env::temp_dir().join("rust_ledger_merge.yaml");
+        let original = std::fs::read_to_string("./examples/example.yaml").unwrap();
+        std::fs::write(&path, &original).unwrap();
+        let args = [
+            "csv",
+            "-f",
+            path.to_str().unwrap(),
+            "-c",
+            "./examples/example.csv",
+            "-r",
+            "./examples/example.rules.yaml",
+        ];
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd.args(args).arg("--dry-run").assert().success();
+        let diff = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+
+        assert!(diff.contains("+  - account: \"expense:uncategorized\"\n+    amount: 0\n"));
+        assert!(diff.contains("+  - date: 2020-01-05\n+    account: \"expense:uncategorized\"\n"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        cmd.args(args).arg("--write").assert().success().stdout("");
+        let merged = std::fs::read_to_string(&path).unwrap();
+        let backup = std::fs::read_to_string(path.with_extension("yaml.bak")).unwrap();
+
+        assert_eq!(backup, original);
+        assert!(merged.starts_with(original.split("\n\ntransactions:").next().unwrap()));
+        assert!(merged.contains("    amount: 0\n  - account: \"expense:uncategorized\"\n    amount: 0\n\ntransactions:\n"));
+        assert!(merged.contains("    description: donut sale\n"));
+
+        // importing again finds nothing new
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd.args(args).arg("--write").assert().success();
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+
+        assert!(stderr.starts_with("0 new, 2 skipped, 0 ambiguous"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), merged);
+    }
+
+    #[test]
+    fn included_files_merge_into_ledger_file() {
+        let dir = std::env::temp_dir().join("rust_ledger_include");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("2020")).unwrap();
+        std::fs::copy("./examples/example.yaml", dir.join("2020/example.yaml")).unwrap();
+        let path = dir.join("ledger.yaml");
+        std::fs::write(&path, "currency: USD\ninclude: [\"2020/*.yaml\"]\n").unwrap();
+
+        let balance = |filename: &str| {
+            let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+            let assert = cmd.args(["balance", "-f", filename]).assert().success();
+            assert.get_output().stdout.clone()
+        };
+        assert_eq!(
+            balance(path.to_str().unwrap()),
+            balance("./examples/example.yaml")
+        );
+
+        let args = [
+            "csv",
+            "-f",
+            path.to_str().unwrap(),
+            "-c",
+            "./examples/example.csv",
+            "-r",
+            "./examples/example.rules.yaml",
+            "--write",
+        ];
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        cmd.args(args).assert().success().stdout("");
+        let merged = std::fs::read_to_string(&path).unwrap();
+
+        assert!(merged.starts_with(
+            "currency: USD\ninclude: [\"2020/*.yaml\"]\naccounts:\n  - account: \"expense:uncategorized\"\n    amount: 0\ntransactions:\n"
+        ));
+        assert!(merged.contains("    description: donut sale\n"));
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd.args(args).assert().success();
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+
+        assert!(stderr.starts_with("0 new, 2 skipped, 0 ambiguous"));
+    }
+
+    #[test]
+    fn convert_round_trips_through_journal() {
+        let journal = std::env::temp_dir().join("rust_ledger_convert.journal");
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        cmd.args([
+            "convert",
+            "-f",
+            "./examples/example.yaml",
+            "--output-file",
+            journal.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout("");
+        let converted = std::fs::read_to_string(&journal).unwrap();
+
+        assert!(converted.starts_with("D 1.00 USD\n"));
+        assert!(converted.contains("\n~ Monthly\n    expense:grocery   500.00 USD\n"));
+        assert!(converted.contains(
+            "\n2020-01-01 donut sale to dale\n    asset:cash_checking  300.00 USD = -400.00 USD\n"
+        ));
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["convert", "-f", journal.to_str().unwrap()])
+            .assert()
+            .success();
+        let yaml = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+
+        assert!(yaml.starts_with("---\ncurrency: USD\n"));
+        assert!(yaml.contains("    description: donut sale to dale\n"));
+    }
+
+    #[test]
+    fn convert_reports_unsupported_journal_features() {
+        let journal = std::env::temp_dir().join("rust_ledger_unsupported.journal");
+        std::fs::write(&journal, "2020-01-01 x\n    a:b  $5\n    (c)\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["convert", "-f", journal.to_str().unwrap()])
+            .assert()
+            .failure();
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+
+        assert!(stderr.contains("virtual postings are not supported: (c)"));
+        assert!(stderr.contains("rust_ledger_unsupported.journal:3:1"));
+    }
+
+    #[test]
+    fn convert_round_trips_through_beancount() {
+        let beancount = std::env::temp_dir().join("rust_ledger_convert.beancount");
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "convert",
+                "-f",
+                "./examples/example.yaml",
+                "--to",
+                "beancount",
+                "--output-file",
+                beancount.to_str().unwrap(),
+            ])
+            .assert()
+            .success()
+            .stdout("");
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+        let converted = std::fs::read_to_string(&beancount).unwrap();
+
+        assert!(converted.starts_with("option \"operating_currency\" \"USD\"\n"));
+        assert!(converted.contains("open Assets:Cash-Checking\n"));
+        assert!(stderr.starts_with("dropped:\n  2 categorization rules dropped\napproximated:\n"));
+        assert!(stderr.contains("  account asset:cash_checking renamed to Assets:Cash-Checking\n"));
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args(["convert", "-f", beancount.to_str().unwrap()])
+            .assert()
+            .success()
+            .stderr("");
+        let yaml = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+
+        assert!(yaml.starts_with("---\ncurrency: USD\n"));
+        assert!(yaml.contains("    description: donut sale to dale\n"));
+    }
+
+    #[test]
+    fn budget_of_converted_beancount_file() {
+        let beancount = std::env::temp_dir().join("rust_ledger_budget.beancount");
+        let yaml = std::env::temp_dir().join("rust_ledger_budget_beancount.yaml");
+        std::fs::write(
+            &beancount,
+            r#"option "operating_currency" "USD"
+2020-01-01 open Assets:Checking
+2020-01-01 open Assets:Expense-Refunds
+2020-01-01 open Expenses:Grocery
+2020-01-01 open Income:Salary
+2020-01-01 custom "budget" Expenses:Grocery "monthly" 500.00 USD
+2020-01-05 * "market"
+  Expenses:Grocery  120.00 USD
+  Assets:Checking
+2020-01-06 * "refund"
+  Assets:Expense-Refunds  40.00 USD
+  Assets:Checking
+2020-01-31 * "salary"
+  Assets:Checking  2000.00 USD
+  Income:Salary
+"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        cmd.args([
+            "convert",
+            "-f",
+            beancount.to_str().unwrap(),
+            "--output-file",
+            yaml.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+        let mut cmd = Command::cargo_bin("rust_ledger").unwrap();
+        let assert = cmd
+            .args([
+                "budget",
+                "-f",
+                yaml.to_str().unwrap(),
+                "-g",
+                "monthly",
+                "-O",
+                "csv",
+            ])
+            .assert();
+        let stdout = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+
+        assert_eq!(
+            stdout,
+            "period,account,currency,budget,actual,delta
+2020-01,Expenses:Grocery,USD,500.00,120.00,380.00
+2020-01,Income:Salary,USD,0.00,-2000.00,2000.00
+"
+        );
+    }
 }